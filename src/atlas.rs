@@ -0,0 +1,319 @@
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
+use crate::{
+    BdfBlock,
+    Bitmap,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PageSize {
+    // every page is exactly this size
+    Fixed(u32, u32),
+    // each page is the smallest power of two rectangle that holds its glyphs, at most this on either side
+    PowerOfTwo(u32),
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct AtlasOptions {
+    pub page_size: PageSize,
+    // empty pixels kept around every bitmap, so filtering never bleeds a neighbour in
+    pub padding: u32,
+    // further empty pixels between the padded bitmaps, not along the page edges
+    pub spacing: u32,
+    // identical bitmaps share one rectangle
+    pub dedup: bool,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            page_size: PageSize::PowerOfTwo(1024),
+            padding: 0,
+            spacing: 1,
+            dedup: true,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Placement {
+    pub page: usize,
+    // the bitmap on the page, without padding, empty bitmaps get an empty rectangle
+    pub rect: Rect,
+    // from the pen position on the baseline to the bottom left of the bitmap, y grows up as in BDF
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub advance: i32,
+}
+
+pub struct Atlas {
+    pub pages: Vec<Bitmap>,
+    pub glyphs: BTreeMap<char, Placement>,
+}
+
+impl Atlas {
+    pub fn get(&self, c: char) -> Option<&Placement> {
+        self.glyphs.get(&c)
+    }
+}
+
+//
+
+// the tops of the columns filled so far, as runs of (x, y, width) from left to right
+struct Skyline {
+    width: u32,
+    height: u32,
+    segments: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            segments: vec![(0, 0, width)],
+        }
+    }
+
+    // the highest spot a box fits in, leftmost first, as the segment it starts at and its corner
+    fn find(&self, width: u32, height: u32) -> Option<(usize, u32, u32)> {
+        let mut best: Option<(usize, u32, u32)> = None;
+
+        for (i, &(x, _, _)) in self.segments.iter().enumerate() {
+            if x + width > self.width {
+                break;
+            }
+
+            let mut y = 0;
+            let mut right = x;
+            for &(sx, sy, sw) in &self.segments[i..] {
+                if right >= x + width {
+                    break;
+                }
+                y = y.max(sy);
+                right = sx + sw;
+            }
+
+            if y + height <= self.height && best.map_or(true, |(_, _, by)| y < by) {
+                best = Some((i, x, y));
+            }
+        }
+
+        best
+    }
+
+    fn place(&mut self, i: usize, x: u32, y: u32, width: u32, height: u32) {
+        let right = x + width;
+
+        while i < self.segments.len() && self.segments[i].0 < right {
+            let (sx, sy, sw) = self.segments[i];
+            if sx + sw <= right {
+                self.segments.remove(i);
+            } else {
+                self.segments[i] = (right, sy, sx + sw - right);
+                break;
+            }
+        }
+        self.segments.insert(i, (x, y + height, width));
+
+        let mut j = 0;
+        while j + 1 < self.segments.len() {
+            if self.segments[j].1 == self.segments[j + 1].1 {
+                self.segments[j].2 += self.segments[j + 1].2;
+                self.segments.remove(j + 1);
+            } else {
+                j += 1;
+            }
+        }
+    }
+}
+
+// places as many of the boxes as fit on a page, returning (index, x, y) for those that did
+fn fill(width: u32, height: u32, spacing: u32, boxes: &[(u32, u32)], order: &[usize]) -> Vec<(usize, u32, u32)> {
+    // the spacing is added after every box, so the page is that much larger to let boxes touch its far edges
+    let mut skyline = Skyline::new(width + spacing, height + spacing);
+    let mut placed = Vec::new();
+
+    for &i in order {
+        let (w, h) = (boxes[i].0 + spacing, boxes[i].1 + spacing);
+        if let Some((segment, x, y)) = skyline.find(w, h) {
+            skyline.place(segment, x, y, w, h);
+            placed.push((i, x, y));
+        }
+    }
+
+    placed
+}
+
+// page, x and y of a box
+type Position = (usize, u32, u32);
+
+// spreads the boxes over pages, giving the page sizes and where every box went
+fn pack_boxes(boxes: &[(u32, u32)], options: &AtlasOptions) -> (Vec<(u32, u32)>, Vec<Position>) {
+    let mut order: Vec<usize> = (0..boxes.len()).collect();
+    order.sort_by_key(|&i| (std::cmp::Reverse(boxes[i].1), std::cmp::Reverse(boxes[i].0)));
+
+    let mut pages = Vec::new();
+    let mut positions = vec![(0, 0, 0); boxes.len()];
+    let mut done = vec![false; boxes.len()];
+
+    while !order.is_empty() || pages.is_empty() {
+        let (width, height, placed) = match options.page_size {
+            PageSize::Fixed(width, height) => (width, height, fill(width, height, options.spacing, boxes, &order)),
+            PageSize::PowerOfTwo(max) => {
+                let side = |f: fn(&(u32, u32)) -> u32| {
+                    order.iter().map(|&i| f(&boxes[i])).max().unwrap_or(1).max(1).next_power_of_two().min(max)
+                };
+                let (mut width, mut height) = (side(|b| b.0), side(|b| b.1));
+
+                loop {
+                    let placed = fill(width, height, options.spacing, boxes, &order);
+                    if placed.len() == order.len() || (width >= max && height >= max) {
+                        break (width, height, placed);
+                    }
+
+                    if width <= height && width < max || height >= max {
+                        width = (width * 2).min(max);
+                    } else {
+                        height = (height * 2).min(max);
+                    }
+                }
+            }
+        };
+
+        for &(i, x, y) in &placed {
+            positions[i] = (pages.len(), x, y);
+            done[i] = true;
+        }
+        order.retain(|&i| !done[i]);
+        pages.push((width, height));
+    }
+
+    (pages, positions)
+}
+
+fn pack_glyphs(font: &Font, glyphs: Vec<(char, &Glyph)>, options: &AtlasOptions) -> Result<Atlas, Error> {
+    let (max_width, max_height) = match options.page_size {
+        PageSize::Fixed(width, height) => (width, height),
+        PageSize::PowerOfTwo(max) => (max, max),
+    };
+    if max_width == 0 || max_height == 0 {
+        return Err(Error::FontValidation("atlas pages need a size"));
+    }
+
+    // the padded boxes to pack, and which box every glyph uses
+    let mut boxes = Vec::new();
+    let mut bitmaps: Vec<&Bitmap> = Vec::new();
+    let mut seen: HashMap<&Bitmap, usize> = HashMap::new();
+    let mut uses = Vec::with_capacity(glyphs.len());
+
+    for (_, glyph) in &glyphs {
+        let bitmap = &glyph.bitmap;
+        if bitmap.width() == 0 || bitmap.height() == 0 {
+            uses.push(None);
+            continue;
+        }
+
+        if let Some(&i) = seen.get(bitmap).filter(|_| options.dedup) {
+            uses.push(Some(i));
+            continue;
+        }
+
+        let (width, height) = (bitmap.width() as u32 + 2 * options.padding, bitmap.height() as u32 + 2 * options.padding);
+        if width > max_width || height > max_height {
+            return Err(Error::GlyphValidation(glyph.name.clone(), "glyph larger than an atlas page"));
+        }
+
+        seen.insert(bitmap, boxes.len());
+        uses.push(Some(boxes.len()));
+        boxes.push((width, height));
+        bitmaps.push(bitmap);
+    }
+
+    let (sizes, positions) = pack_boxes(&boxes, options);
+
+    let mut pages: Vec<Bitmap> = sizes.iter().map(|&(w, h)| Bitmap::new(w as usize, h as usize)).collect();
+    for (bitmap, &(page, x, y)) in bitmaps.iter().zip(&positions) {
+        let (left, top) = ((x + options.padding) as usize, (y + options.padding) as usize);
+        for row in 0..bitmap.height() {
+            for col in 0..bitmap.width() {
+                if bitmap.get(col, row) == Some(true) {
+                    pages[page].set(left + col, top + row, true);
+                }
+            }
+        }
+    }
+
+    let mut placements = BTreeMap::new();
+    for ((c, glyph), used) in glyphs.into_iter().zip(uses) {
+        let (page, rect) = match used {
+            Some(i) => {
+                let (page, x, y) = positions[i];
+                let rect = Rect {
+                    x: x + options.padding,
+                    y: y + options.padding,
+                    width: bitmaps[i].width() as u32,
+                    height: bitmaps[i].height() as u32,
+                };
+                (page, rect)
+            }
+            None => (0, Rect::default()),
+        };
+
+        placements.entry(c).or_insert(Placement {
+            page,
+            rect,
+            x_offset: glyph.bounding_box.x_offset,
+            y_offset: glyph.bounding_box.y_offset,
+            advance: glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0),
+        });
+    }
+
+    Ok(Atlas { pages, glyphs: placements })
+}
+
+//
+
+// packs every glyph with a unicode character, the first glyph wins when several map to the same one
+pub fn pack(font: &Font, options: &AtlasOptions) -> Result<Atlas, Error> {
+    font.validate()?;
+
+    let glyphs = font.unicode_chars()?
+                     .into_iter()
+                     .zip(&font.glyphs)
+                     .filter_map(|(c, glyph)| c.map(|c| (c, glyph)))
+                     .collect();
+
+    pack_glyphs(font, glyphs, options)
+}
+
+// packs only the glyphs for `chars`, characters the font lacks are left out of the atlas
+pub fn pack_chars(font: &Font, chars: &[char], options: &AtlasOptions) -> Result<Atlas, Error> {
+    font.validate()?;
+
+    let mut glyphs: Vec<(char, &Glyph)> = font.unicode_chars()?
+                                              .into_iter()
+                                              .zip(&font.glyphs)
+                                              .filter_map(|(c, glyph)| c.filter(|c| chars.contains(c)).map(|c| (c, glyph)))
+                                              .collect();
+    glyphs.sort_by_key(|&(c, _)| c);
+    glyphs.dedup_by_key(|&mut (c, _)| c);
+
+    pack_glyphs(font, glyphs, options)
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+};
+
+use crate::{
+    atlas::{
+        self,
+        AtlasOptions,
+        PageSize,
+        Placement,
+    },
+    png::{
+        self,
+        ColorType,
+    },
+    Bitmap,
+    Error,
+    Font,
+};
+
+//
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum BmFontFormat {
+    Text,
+    Xml,
+    Binary,
+}
+
+pub struct BmFontOptions {
+    pub format: BmFontFormat,
+    // size of every texture page, glyphs that do not fit on one page go on to the next
+    pub page_width: u32,
+    pub page_height: u32,
+    // empty pixels left between glyphs on a page
+    pub spacing: u32,
+    // pages are named `{page_name}_{n}.png`, n zero padded to the width of the last page number
+    pub page_name: String,
+}
+
+impl Default for BmFontOptions {
+    fn default() -> Self {
+        Self {
+            format: BmFontFormat::Text,
+            page_width: 256,
+            page_height: 256,
+            spacing: 1,
+            page_name: String::from("font"),
+        }
+    }
+}
+
+// a texture page, white with the glyphs in the alpha channel
+pub struct Page {
+    pub file: String,
+    pub png: Vec<u8>,
+}
+
+pub struct BmFont {
+    pub descriptor: Vec<u8>,
+    pub pages: Vec<Page>,
+}
+
+//
+
+// white, with the glyphs in the alpha channel
+fn draw_page(page: &Bitmap) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(page.width() * page.height() * 4);
+    for row in page.rows() {
+        for on in row.iter() {
+            pixels.extend_from_slice(&[255, 255, 255, if on { 255 } else { 0 }]);
+        }
+    }
+
+    pixels
+}
+
+//
+
+// what the descriptor says about the font as a whole
+struct Info {
+    face: String,
+    size: i32,
+    bold: bool,
+    italic: bool,
+    line_height: i32,
+    base: i32,
+}
+
+impl Info {
+    fn new(font: &Font) -> Self {
+        let (ascent, descent) = font.ascent_descent();
+
+        let face = match &font.xlfd.family_name {
+            Some(family) if !family.is_empty() => family.clone(),
+            _ => font.name.clone(),
+        };
+
+        Self {
+            face: face.replace('"', "'"),
+            size: font.xlfd.pixel_size.filter(|&p| p > 0).unwrap_or(ascent + descent),
+            bold: font.xlfd.weight_class() >= 700,
+            italic: font.xlfd.is_italic(),
+            line_height: ascent + descent,
+            base: ascent,
+        }
+    }
+}
+
+// xoffset, yoffset and xadvance, the offsets from the pen at the top of the line to the bitmap
+fn char_metrics(info: &Info, placement: &Placement) -> (i32, i32, i32) {
+    (placement.x_offset, info.base - (placement.y_offset + placement.rect.height as i32), placement.advance)
+}
+
+fn write_text(info: &Info, chars: &BTreeMap<char, Placement>, pages: &[Page], options: &BmFontOptions) -> Vec<u8> {
+    let mut out = String::new();
+
+    out += &format!("info face=\"{}\" size={} bold={} italic={} charset=\"\" unicode=1 stretchH=100 smooth=0 aa=1 \
+                     padding=0,0,0,0 spacing={},{} outline=0\n",
+                    info.face, info.size, u8::from(info.bold), u8::from(info.italic), options.spacing, options.spacing);
+    out += &format!("common lineHeight={} base={} scaleW={} scaleH={} pages={} packed=0 \
+                     alphaChnl=0 redChnl=4 greenChnl=4 blueChnl=4\n",
+                    info.line_height, info.base, options.page_width, options.page_height, pages.len());
+
+    for (i, page) in pages.iter().enumerate() {
+        out += &format!("page id={} file=\"{}\"\n", i, page.file);
+    }
+
+    out += &format!("chars count={}\n", chars.len());
+    for (&c, p) in chars {
+        let (x_offset, y_offset, advance) = char_metrics(info, p);
+        out += &format!("char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} page={} chnl=15\n",
+                        c as u32, p.rect.x, p.rect.y, p.rect.width, p.rect.height,
+                        x_offset, y_offset, advance, p.page);
+    }
+
+    out.into_bytes()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn write_xml(info: &Info, chars: &BTreeMap<char, Placement>, pages: &[Page], options: &BmFontOptions) -> Vec<u8> {
+    let mut out = String::from("<?xml version=\"1.0\"?>\n<font>\n");
+
+    out += &format!("  <info face=\"{}\" size=\"{}\" bold=\"{}\" italic=\"{}\" charset=\"\" unicode=\"1\" stretchH=\"100\" \
+                     smooth=\"0\" aa=\"1\" padding=\"0,0,0,0\" spacing=\"{},{}\" outline=\"0\"/>\n",
+                    xml_escape(&info.face), info.size, u8::from(info.bold), u8::from(info.italic), options.spacing, options.spacing);
+    out += &format!("  <common lineHeight=\"{}\" base=\"{}\" scaleW=\"{}\" scaleH=\"{}\" pages=\"{}\" packed=\"0\" \
+                     alphaChnl=\"0\" redChnl=\"4\" greenChnl=\"4\" blueChnl=\"4\"/>\n",
+                    info.line_height, info.base, options.page_width, options.page_height, pages.len());
+
+    out += "  <pages>\n";
+    for (i, page) in pages.iter().enumerate() {
+        out += &format!("    <page id=\"{}\" file=\"{}\" />\n", i, xml_escape(&page.file));
+    }
+    out += "  </pages>\n";
+
+    out += &format!("  <chars count=\"{}\">\n", chars.len());
+    for (&c, p) in chars {
+        let (x_offset, y_offset, advance) = char_metrics(info, p);
+        out += &format!("    <char id=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" xoffset=\"{}\" yoffset=\"{}\" \
+                         xadvance=\"{}\" page=\"{}\" chnl=\"15\" />\n",
+                        c as u32, p.rect.x, p.rect.y, p.rect.width, p.rect.height,
+                        x_offset, y_offset, advance, p.page);
+    }
+    out += "  </chars>\n</font>\n";
+
+    out.into_bytes()
+}
+
+// version 3 of the binary format, blocks of a type byte and a little endian length
+fn write_binary(info: &Info, chars: &BTreeMap<char, Placement>, pages: &[Page], options: &BmFontOptions) -> Result<Vec<u8>, Error> {
+    let too_big = |_| Error::FontValidation("metrics do not fit in a binary bmfont");
+    let u8_of = |v: u32| u8::try_from(v).map_err(too_big);
+    let u16_of = |v: i64| u16::try_from(v).map_err(too_big);
+    let i16_of = |v: i32| i16::try_from(v).map_err(too_big);
+
+    let mut out = Vec::from(&b"BMF\x03"[..]);
+    let block = |out: &mut Vec<u8>, kind: u8, data: &[u8]| {
+        out.push(kind);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    };
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&i16_of(info.size)?.to_le_bytes());
+    // bits from the top are smooth, unicode, italic, bold and fixed height
+    data.push(0x40 | u8::from(info.italic) << 5 | u8::from(info.bold) << 4);
+    data.push(0); // charset, unused for unicode
+    data.extend_from_slice(&100u16.to_le_bytes());
+    data.push(1); // supersampling
+    data.extend_from_slice(&[0; 4]); // padding
+    data.push(u8_of(options.spacing)?);
+    data.push(u8_of(options.spacing)?);
+    data.push(0); // outline
+    data.extend_from_slice(info.face.as_bytes());
+    data.push(0);
+    block(&mut out, 1, &data);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&u16_of(i64::from(info.line_height))?.to_le_bytes());
+    data.extend_from_slice(&u16_of(i64::from(info.base))?.to_le_bytes());
+    data.extend_from_slice(&u16_of(i64::from(options.page_width))?.to_le_bytes());
+    data.extend_from_slice(&u16_of(i64::from(options.page_height))?.to_le_bytes());
+    data.extend_from_slice(&u16_of(pages.len() as i64)?.to_le_bytes());
+    data.push(0); // not packed
+    data.extend_from_slice(&[0, 4, 4, 4]);
+    block(&mut out, 2, &data);
+
+    let mut data = Vec::new();
+    for page in pages {
+        data.extend_from_slice(page.file.as_bytes());
+        data.push(0);
+    }
+    block(&mut out, 3, &data);
+
+    let mut data = Vec::new();
+    for (&c, p) in chars {
+        let (x_offset, y_offset, advance) = char_metrics(info, p);
+
+        data.extend_from_slice(&(c as u32).to_le_bytes());
+        data.extend_from_slice(&u16_of(i64::from(p.rect.x))?.to_le_bytes());
+        data.extend_from_slice(&u16_of(i64::from(p.rect.y))?.to_le_bytes());
+        data.extend_from_slice(&u16_of(i64::from(p.rect.width))?.to_le_bytes());
+        data.extend_from_slice(&u16_of(i64::from(p.rect.height))?.to_le_bytes());
+        data.extend_from_slice(&i16_of(x_offset)?.to_le_bytes());
+        data.extend_from_slice(&i16_of(y_offset)?.to_le_bytes());
+        data.extend_from_slice(&i16_of(advance)?.to_le_bytes());
+        data.push(u8_of(p.page as u32)?);
+        data.push(15); // all channels
+    }
+    block(&mut out, 4, &data);
+
+    Ok(out)
+}
+
+//
+
+// packs the glyphs onto texture pages and describes them, glyphs are addressed by unicode character
+pub fn export(font: &Font, options: &BmFontOptions) -> Result<BmFont, Error> {
+    let atlas_options = AtlasOptions {
+        page_size: PageSize::Fixed(options.page_width, options.page_height),
+        padding: 0,
+        spacing: options.spacing,
+        dedup: true,
+    };
+    let atlas = atlas::pack(font, &atlas_options)?;
+
+    // the binary pages block needs every file name the same length
+    let digits = atlas.pages.len().saturating_sub(1).to_string().len();
+
+    let mut pages = Vec::new();
+    for (i, page) in atlas.pages.iter().enumerate() {
+        pages.push(Page {
+            file: format!("{}_{:0width$}.png", options.page_name, i, width = digits),
+            png: png::write_png(Vec::new(), options.page_width, options.page_height, ColorType::Rgba, &draw_page(page))?,
+        });
+    }
+
+    let info = Info::new(font);
+    let descriptor = match options.format {
+        BmFontFormat::Text => write_text(&info, &atlas.glyphs, &pages, options),
+        BmFontFormat::Xml => write_xml(&info, &atlas.glyphs, &pages, options),
+        BmFontFormat::Binary => write_binary(&info, &atlas.glyphs, &pages, options)?,
+    };
+
+    Ok(BmFont { descriptor, pages })
+}
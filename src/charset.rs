@@ -0,0 +1,146 @@
+use crate::Xlfd;
+
+mod tables;
+
+//
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Charset {
+    Iso8859(u8),
+    Koi8R,
+    Koi8U,
+    Cp1251,
+    Cp1252,
+    JisX0201,
+    JisX0208,
+    Gb2312,
+    Ksc5601,
+    Big5,
+}
+
+impl Charset {
+    // registry and encoding as in CHARSET_REGISTRY and CHARSET_ENCODING,
+    //   for the 94x94 sets encoding 0 is the GL form and 1 the GR (euc) form, both are accepted
+    pub fn from_registry(registry: &str, encoding: &str) -> Option<Self> {
+        let registry = registry.to_ascii_uppercase();
+        let encoding = encoding.to_ascii_uppercase();
+
+        let family = registry.split('.').next().unwrap_or("");
+
+        let charset = match (family, encoding.as_str()) {
+            ("ISO8859", n) => match n.parse() {
+                Ok(n @ 1..=11) | Ok(n @ 13..=16) => Charset::Iso8859(n),
+                _ => return None,
+            },
+            ("KOI8", "R") => Charset::Koi8R,
+            ("KOI8", "U") => Charset::Koi8U,
+            ("MICROSOFT", "CP1251") | ("CP1251", _) => Charset::Cp1251,
+            ("MICROSOFT", "CP1252") | ("CP1252", _) => Charset::Cp1252,
+            ("JISX0201", _) => Charset::JisX0201,
+            ("JISX0208", "0") | ("JISX0208", "1") => Charset::JisX0208,
+            ("GB2312", "0") | ("GB2312", "1") => Charset::Gb2312,
+            ("KSC5601", "0") | ("KSC5601", "1") => Charset::Ksc5601,
+            ("BIG5", _) => Charset::Big5,
+            _ => return None,
+        };
+
+        Some(charset)
+    }
+
+    pub fn from_xlfd(xlfd: &Xlfd) -> Option<Self> {
+        match (&xlfd.charset_registry, &xlfd.charset_encoding) {
+            (Some(registry), Some(encoding)) => Self::from_registry(registry, encoding),
+            _ => None,
+        }
+    }
+
+    pub fn to_unicode(&self, code: u32) -> Option<char> {
+        use Charset::*;
+
+        let single = |table: &[u16; 128]| match code {
+            0..=0x7f => Some(code),
+            0x80..=0xff => Some(table[code as usize - 0x80] as u32).filter(|&u| u != 0xffff),
+            _ => None,
+        };
+
+        let u = match self {
+            Iso8859(n) => single(iso8859_table(*n)?)?,
+            Koi8R => single(&tables::KOI8_R)?,
+            Koi8U => single(&tables::KOI8_U)?,
+            Cp1251 => single(&tables::CP1251)?,
+            Cp1252 => single(&tables::CP1252)?,
+            JisX0201 => match code {
+                0x5c => 0xa5,
+                0x7e => 0x203e,
+                _ => single(&tables::JISX0201)?,
+            },
+            JisX0208 => double94(&tables::JISX0208, code)?,
+            Gb2312 => double94(&tables::GB2312, code)?,
+            Ksc5601 => double94(&tables::KSC5601, code)?,
+            Big5 => big5(code)?,
+        };
+
+        char::from_u32(u)
+    }
+}
+
+fn iso8859_table(n: u8) -> Option<&'static [u16; 128]> {
+    let table = match n {
+        1 => &tables::ISO8859_1,
+        2 => &tables::ISO8859_2,
+        3 => &tables::ISO8859_3,
+        4 => &tables::ISO8859_4,
+        5 => &tables::ISO8859_5,
+        6 => &tables::ISO8859_6,
+        7 => &tables::ISO8859_7,
+        8 => &tables::ISO8859_8,
+        9 => &tables::ISO8859_9,
+        10 => &tables::ISO8859_10,
+        11 => &tables::ISO8859_11,
+        13 => &tables::ISO8859_13,
+        14 => &tables::ISO8859_14,
+        15 => &tables::ISO8859_15,
+        16 => &tables::ISO8859_16,
+        _ => return None,
+    };
+
+    Some(table)
+}
+
+fn double94(table: &[u16], code: u32) -> Option<u32> {
+    let code = code & 0x7f7f;
+    let (row, cell) = (code >> 8, code & 0xff);
+
+    if !(0x21..=0x7e).contains(&row) || !(0x21..=0x7e).contains(&cell) {
+        return None;
+    }
+
+    let u = table[((row - 0x21) * 94 + (cell - 0x21)) as usize];
+    Some(u as u32).filter(|&u| u != 0)
+}
+
+fn big5(code: u32) -> Option<u32> {
+    let (row, cell) = (code >> 8, code & 0xff);
+
+    if !(0xa1..=0xf9).contains(&row) {
+        return None;
+    }
+
+    let cell = match cell {
+        0x40..=0x7e => cell - 0x40,
+        0xa1..=0xfe => cell - 0xa1 + 63,
+        _ => return None,
+    };
+
+    let u = tables::BIG5[((row - 0xa1) * 157 + cell) as usize];
+    Some(u as u32).filter(|&u| u != 0)
+}
+
+//
+
+// a glyph left without a unicode code point by Font::convert_to_unicode
+#[derive(Clone, Debug)]
+pub struct Unmapped {
+    pub name: String,
+    pub code: u32,
+}
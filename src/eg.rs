@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{
+        Point,
+        Size,
+    },
+    image::ImageRaw,
+    mono_font::{
+        mapping::GlyphMapping,
+        DecorationDimensions,
+        MonoFont,
+    },
+    pixelcolor::{
+        BinaryColor,
+        PixelColor,
+    },
+    primitives::{
+        PointsIter,
+        Rectangle,
+    },
+    text::{
+        renderer::{
+            CharacterStyle,
+            TextMetrics,
+            TextRenderer,
+        },
+        Baseline,
+    },
+    Pixel,
+};
+
+use crate::{
+    ids,
+    BdfBlock,
+    Bitmap,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+// a font prepared for drawing, with its glyphs looked up by character
+pub struct EgFont<'a> {
+    glyphs: BTreeMap<char, &'a Glyph>,
+    default_glyph: Option<&'a Glyph>,
+    advance: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+impl<'a> EgFont<'a> {
+    pub fn new(font: &'a Font) -> Result<Self, Error> {
+        font.validate()?;
+
+        let mut glyphs = BTreeMap::new();
+        for (c, glyph) in font.text_chars().into_iter().zip(&font.glyphs) {
+            if let Some(c) = c {
+                glyphs.entry(c).or_insert(glyph);
+            }
+        }
+
+        // DEFAULT_CHAR stands in for missing characters, or a question mark when the font has none
+        let default_glyph = font.default_glyph().or_else(|| glyphs.get(&'?').copied());
+        let (ascent, descent) = font.ascent_descent();
+
+        Ok(Self {
+            glyphs,
+            default_glyph,
+            advance: font.device_width.map(|d| d.x).unwrap_or(0),
+            ascent,
+            descent,
+        })
+    }
+
+    fn glyph(&self, c: char) -> Option<&'a Glyph> {
+        self.glyphs.get(&c).copied().or(self.default_glyph)
+    }
+
+    fn advance(&self, glyph: Option<&Glyph>) -> i32 {
+        glyph.and_then(|g| g.device_width).map(|d| d.x).unwrap_or(self.advance)
+    }
+
+    fn line_height(&self) -> u32 {
+        (self.ascent + self.descent).max(0) as u32
+    }
+
+    // from the top of the line to the position the baseline asks for, the alphabetic baseline
+    //   is the lowest row above it as in embedded-graphics' own fonts
+    fn baseline_offset(&self, baseline: Baseline) -> i32 {
+        match baseline {
+            Baseline::Top => 0,
+            Baseline::Bottom => self.line_height() as i32 - 1,
+            Baseline::Middle => (self.line_height() as i32 - 1) / 2,
+            Baseline::Alphabetic => self.ascent - 1,
+        }
+    }
+
+    fn width(&self, text: &str) -> i32 {
+        text.chars().map(|c| self.advance(self.glyph(c))).sum()
+    }
+}
+
+//
+
+// draws proportionally, each glyph with its own advance and offsets
+#[derive(Clone)]
+pub struct BdfTextStyle<'a, C> {
+    pub font: &'a EgFont<'a>,
+    pub text_color: Option<C>,
+    pub background_color: Option<C>,
+}
+
+impl<'a, C: PixelColor> BdfTextStyle<'a, C> {
+    pub fn new(font: &'a EgFont<'a>, text_color: C) -> Self {
+        Self {
+            font,
+            text_color: Some(text_color),
+            background_color: None,
+        }
+    }
+
+    // draws the cell of one advance and the glyph's ink, each pixel once
+    fn draw_glyph<D>(&self, glyph: Option<&Glyph>, pen: Point, advance: i32, target: &mut D) -> Result<(), D::Error>
+        where D: DrawTarget<Color = C>
+    {
+        // the bitmap and its top left corner
+        let placed = glyph.map(|g| {
+            let bbx = &g.bounding_box;
+            (&g.bitmap, pen.x + bbx.x_offset, pen.y + self.font.ascent - (bbx.y_offset + g.bitmap.height() as i32))
+        });
+        let ink = |p: Point| {
+            placed.is_some_and(|(bitmap, left, top)| {
+                p.x >= left && p.y >= top && bitmap.get((p.x - left) as usize, (p.y - top) as usize) == Some(true)
+            })
+        };
+
+        let cell = Rectangle::new(pen, Size::new(advance.max(0) as u32, self.font.line_height()));
+        if let Some(background) = self.background_color {
+            target.draw_iter(cell.points().filter_map(|p| {
+                if ink(p) { self.text_color.map(|color| Pixel(p, color)) } else { Some(Pixel(p, background)) }
+            }))?;
+        }
+
+        if let (Some(color), Some((bitmap, left, top))) = (self.text_color, placed) {
+            let painted = self.background_color.is_some();
+            let pixels = (0..bitmap.height())
+                .flat_map(|y| (0..bitmap.width()).map(move |x| (x, y)))
+                .filter(|&(x, y)| bitmap.get(x, y) == Some(true))
+                .map(|(x, y)| Point::new(left + x as i32, top + y as i32))
+                .filter(|&p| !(painted && cell.contains(p)))
+                .map(|p| Pixel(p, color));
+
+            target.draw_iter(pixels)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: PixelColor> CharacterStyle for BdfTextStyle<'_, C> {
+    type Color = C;
+
+    fn set_text_color(&mut self, text_color: Option<C>) {
+        self.text_color = text_color;
+    }
+
+    fn set_background_color(&mut self, background_color: Option<C>) {
+        self.background_color = background_color;
+    }
+}
+
+impl<C: PixelColor> TextRenderer for BdfTextStyle<'_, C> {
+    type Color = C;
+
+    fn draw_string<D>(&self, text: &str, position: Point, baseline: Baseline, target: &mut D) -> Result<Point, D::Error>
+        where D: DrawTarget<Color = C>
+    {
+        let offset = self.font.baseline_offset(baseline);
+        let mut pen = position - Point::new(0, offset);
+
+        for c in text.chars() {
+            let glyph = self.font.glyph(c);
+            let advance = self.font.advance(glyph);
+
+            self.draw_glyph(glyph, pen, advance, target)?;
+            pen.x += advance;
+        }
+
+        Ok(pen + Point::new(0, offset))
+    }
+
+    fn draw_whitespace<D>(&self, width: u32, position: Point, baseline: Baseline, target: &mut D) -> Result<Point, D::Error>
+        where D: DrawTarget<Color = C>
+    {
+        let offset = self.font.baseline_offset(baseline);
+        let top_left = position - Point::new(0, offset);
+
+        if let (Some(background), true) = (self.background_color, width > 0) {
+            target.fill_solid(&Rectangle::new(top_left, Size::new(width, self.font.line_height())), background)?;
+        }
+
+        Ok(position + Point::new(width as i32, 0))
+    }
+
+    fn measure_string(&self, text: &str, position: Point, baseline: Baseline) -> TextMetrics {
+        let width = self.font.width(text).max(0);
+        let top_left = position - Point::new(0, self.font.baseline_offset(baseline));
+
+        TextMetrics {
+            bounding_box: Rectangle::new(top_left, Size::new(width as u32, self.font.line_height())),
+            next_position: position + Point::new(width, 0),
+        }
+    }
+
+    fn line_height(&self) -> u32 {
+        self.font.line_height()
+    }
+}
+
+//
+
+const CELLS_PER_ROW: usize = 16;
+
+// cell indices in character order, characters the font lacks get the replacement
+struct CharMapping {
+    chars: Vec<char>,
+    replacement: usize,
+}
+
+impl GlyphMapping for CharMapping {
+    fn index(&self, c: char) -> usize {
+        self.chars.binary_search(&c).unwrap_or(self.replacement)
+    }
+}
+
+// the glyph image and mapping a MonoFont borrows, for fonts whose glyphs all share one advance
+pub struct MonoFontData {
+    image: Vec<u8>,
+    image_width: u32,
+    mapping: CharMapping,
+    character_size: Size,
+    baseline: u32,
+    underline: DecorationDimensions,
+    strikethrough: DecorationDimensions,
+}
+
+impl MonoFontData {
+    pub fn new(font: &Font) -> Result<Self, Error> {
+        let prepared = EgFont::new(font)?;
+        let (ascent, height) = (prepared.ascent, prepared.line_height() as i32);
+
+        let mut advances = prepared.glyphs.values().map(|&g| prepared.advance(Some(g)));
+        let width = advances.next().unwrap_or(0);
+        if advances.any(|a| a != width) {
+            return Err(Error::FontValidation("glyphs differ in advance, not a monospaced font"));
+        }
+        if width <= 0 || height <= 0 {
+            return Err(Error::FontValidation("monospaced fonts need a positive advance and height"));
+        }
+
+        let chars: Vec<char> = prepared.glyphs.keys().copied().collect();
+        let columns = chars.len().clamp(1, CELLS_PER_ROW);
+        let rows = chars.len().div_ceil(CELLS_PER_ROW).max(1);
+        let mut image = Bitmap::new(columns * width as usize, rows * height as usize);
+
+        for (i, glyph) in prepared.glyphs.values().enumerate() {
+            let bbx = &glyph.bounding_box;
+            let (left, top) = ((i % CELLS_PER_ROW) as i32 * width, (i / CELLS_PER_ROW) as i32 * height);
+
+            for y in 0..glyph.bitmap.height() {
+                for x in 0..glyph.bitmap.width() {
+                    if glyph.bitmap.get(x, y) != Some(true) {
+                        continue;
+                    }
+
+                    let (cx, cy) = (bbx.x_offset + x as i32, ascent - (bbx.y_offset + glyph.bitmap.height() as i32) + y as i32);
+                    if cx < 0 || cx >= width || cy < 0 || cy >= height {
+                        return Err(Error::GlyphValidation(glyph.name.clone(), "ink lies outside the character cell"));
+                    }
+
+                    image.set((left + cx) as usize, (top + cy) as usize, true);
+                }
+            }
+        }
+
+        let replacement = prepared.default_glyph
+                                  .and_then(|d| prepared.glyphs.values().position(|&g| std::ptr::eq(g, d)))
+                                  .unwrap_or(0);
+
+        let underline_position = font.int_property(ids::UNDERLINE_POSITION).unwrap_or(1);
+        let underline_thickness = font.int_property(ids::UNDERLINE_THICKNESS).unwrap_or(1);
+        let strikethrough = match font.int_property(ids::X_HEIGHT) {
+            Some(x_height) => DecorationDimensions::new((ascent - (x_height + 1) / 2).max(0) as u32, 1),
+            None => DecorationDimensions::default_strikethrough(height as u32),
+        };
+
+        Ok(Self {
+            image: image.rows().iter().flat_map(|row| row.to_bytes()).collect(),
+            image_width: image.width() as u32,
+            mapping: CharMapping { chars, replacement },
+            character_size: Size::new(width as u32, height as u32),
+            baseline: (ascent - 1).max(0) as u32,
+            underline: DecorationDimensions::new((ascent + underline_position).max(0) as u32, underline_thickness.max(1) as u32),
+            strikethrough,
+        })
+    }
+
+    pub fn mono_font(&self) -> MonoFont<'_> {
+        MonoFont {
+            image: ImageRaw::<BinaryColor>::new(&self.image, self.image_width),
+            character_size: self.character_size,
+            character_spacing: 0,
+            baseline: self.baseline,
+            strikethrough: self.strikethrough,
+            underline: self.underline,
+            glyph_mapping: &self.mapping,
+        }
+    }
+}
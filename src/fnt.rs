@@ -0,0 +1,771 @@
+use std::{
+    convert::TryFrom,
+    io::{
+        Read,
+        Write,
+    },
+};
+
+use crate::{
+    charset::Charset,
+    ids,
+    unicode_glyph_name,
+    xlfd::weight_name,
+    BdfBlock,
+    Bitmap,
+    BoundingBox,
+    Encoding,
+    Error,
+    Font,
+    FontSize,
+    Glyph,
+    MetricsSet,
+    Property,
+    PropertyValue,
+    XYPair,
+    Xlfd,
+};
+
+//
+
+const V2_HEADER: usize = 118;
+const V3_HEADER: usize = 148;
+
+// the part of the header repeated in a .FON font directory entry
+const DIRENTRY_HEADER: usize = 113;
+
+const RT_FONTDIR: u16 = 0x8007;
+const RT_FONT: u16 = 0x8008;
+
+// dfCharSet values and the xlfd charsets they stand for
+const CHARSETS: &[(u8, &str, &str)] = &[
+    (0, "MICROSOFT", "CP1252"),
+    (2, "MICROSOFT", "SYMBOL"),
+    (128, "MICROSOFT", "CP932"),
+    (129, "MICROSOFT", "CP949"),
+    (134, "MICROSOFT", "CP936"),
+    (136, "MICROSOFT", "CP950"),
+    (161, "MICROSOFT", "CP1253"),
+    (162, "MICROSOFT", "CP1254"),
+    (163, "MICROSOFT", "CP1258"),
+    (177, "MICROSOFT", "CP1255"),
+    (178, "MICROSOFT", "CP1256"),
+    (186, "MICROSOFT", "CP1257"),
+    (204, "MICROSOFT", "CP1251"),
+    (222, "MICROSOFT", "CP874"),
+    (238, "MICROSOFT", "CP1250"),
+    (255, "IBM", "CP437"),
+];
+
+const DEFAULT_CHARSET: u8 = 1;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::InvalidFormat("fnt", msg)
+}
+
+fn invalid_fon(msg: &'static str) -> Error {
+    Error::InvalidFormat("fon", msg)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn asciz(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    data[..end].iter().map(|&b| char::from(b)).collect()
+}
+
+// windows strings in the header are single byte, anything else becomes '?'
+fn to_bytes(s: &str, max: usize) -> Vec<u8> {
+    s.chars().take(max).map(|c| if c.is_ascii() && c != '\0' { c as u8 } else { b'?' }).collect()
+}
+
+//
+
+pub enum FntVersion {
+    V2,
+    V3,
+}
+
+pub struct FntOptions {
+    // version 2 uses 16 bit offsets and so holds at most 64k, version 3 lifts the limit
+    pub version: FntVersion,
+}
+
+impl Default for FntOptions {
+    fn default() -> Self {
+        Self {
+            version: FntVersion::V2,
+        }
+    }
+}
+
+//
+
+// a single FNT resource, as a .FNT file or taken out of a .FON
+pub fn parse_font(data: &[u8]) -> Result<Font, Error> {
+    let word = |at| u16_at(data, at).ok_or_else(|| invalid("truncated header"));
+    let dword = |at| u32_at(data, at).ok_or_else(|| invalid("truncated header"));
+
+    if data.starts_with(b"MZ") {
+        return Err(invalid("this is a .fon container, not a single font"));
+    }
+
+    let (header, entry) = match word(0)? {
+        0x200 => (V2_HEADER, 4),
+        0x300 => (V3_HEADER, 6),
+        _ => return Err(invalid("only versions 2 and 3 are supported")),
+    };
+    if data.len() < header {
+        return Err(invalid("truncated header"));
+    }
+
+    if word(66)? & 1 != 0 {
+        return Err(invalid("vector fonts are not supported"));
+    }
+
+    let points = i32::from(word(68)?);
+    let res_y = i32::from(word(70)?);
+    let res_x = i32::from(word(72)?);
+    let ascent = usize::from(word(74)?);
+    let internal_leading = usize::from(word(76)?);
+    let italic = data[80] != 0;
+    let weight = word(83)?;
+    let charset = data[85];
+    let height = usize::from(word(88)?);
+    let (first, last, default) = (data[95], data[96], data[97]);
+    let face = dword(105)? as usize;
+
+    if last < first {
+        return Err(invalid("last character comes before the first"));
+    }
+    if ascent > height {
+        return Err(invalid("ascent larger than the character height"));
+    }
+
+    let count = usize::from(last - first) + 1;
+    if data.len() < header + count * entry {
+        return Err(invalid("truncated character table"));
+    }
+
+    let table_entry = |i: usize| -> Result<(usize, usize), Error> {
+        let at = header + i * entry;
+        let offset = match entry {
+            4 => u32::from(word(at + 2)?),
+            _ => dword(at + 2)?,
+        };
+        Ok((usize::from(word(at)?), offset as usize))
+    };
+
+    let descent = height - ascent;
+    let decipoints = points * 10;
+
+    let (registry, encoding) = match CHARSETS.iter().find(|&&(cs, _, _)| cs == charset) {
+        Some(&(_, registry, encoding)) => (Some(String::from(registry)), Some(String::from(encoding))),
+        None => (None, None),
+    };
+    let to_unicode = match (&registry, &encoding) {
+        (Some(registry), Some(encoding)) => Charset::from_registry(registry, encoding),
+        _ => None,
+    };
+
+    // characters missing from the range point at the default character's bitmap
+    let default_entry = if usize::from(default) < count { Some(table_entry(usize::from(default))?) } else { None };
+
+    let mut glyphs = Vec::new();
+    for i in 0..count {
+        let (width, offset) = table_entry(i)?;
+        if i != usize::from(default) && Some((width, offset)) == default_entry {
+            continue;
+        }
+
+        let columns = width.div_ceil(8);
+        let bytes = data.get(offset..offset + columns * height).ok_or_else(|| invalid("character bitmap out of bounds"))?;
+
+        let (bitmap, bounding_box) = if width == 0 || height == 0 {
+            (Bitmap::new(0, 0), BoundingBox::new(0, 0, 0, 0))
+        } else {
+            // bytes are stored column by column, each column 8 pixels wide and the full height
+            let mut bitmap = Bitmap::new(width, height);
+            for x in 0..width {
+                for y in 0..height {
+                    if bytes[(x / 8) * height + y] & (0x80 >> (x % 8)) != 0 {
+                        bitmap.set(x, y, true);
+                    }
+                }
+            }
+            (bitmap, BoundingBox::new(width as u32, height as u32, 0, -(descent as i32)))
+        };
+
+        let code = u32::from(first) + i as u32;
+        let name = match to_unicode.and_then(|cs| cs.to_unicode(code)) {
+            Some(c) => unicode_glyph_name(c as u32),
+            None => format!("char{}", code),
+        };
+
+        let scalable_width = if decipoints > 0 && res_x > 0 {
+            let sw = width as f64 * 720_000.0 / (f64::from(decipoints) * f64::from(res_x));
+            Some(XYPair::new(sw.round() as i32, 0))
+        } else {
+            None
+        };
+
+        glyphs.push(Glyph {
+            name,
+            encoding: Encoding::Standard(code),
+            bounding_box,
+            bitmap,
+            metrics: MetricsSet::Normal,
+
+            scalable_width,
+            device_width: Some(XYPair::new(width as i32, 0)),
+            scalable_width_alt: None,
+            device_width_alt: None,
+            vector: None,
+
+            source: None,
+        });
+    }
+
+    let widths: Vec<u32> = glyphs.iter().filter_map(|g| g.device_width).map(|d| d.x as u32).collect();
+    let max_width = widths.iter().cloned().max().unwrap_or(0);
+    let average = match widths.len() {
+        0 => 0,
+        n => (widths.iter().sum::<u32>() * 10) as usize / n,
+    };
+
+    let family = if face > 0 && face < data.len() { Some(asciz(&data[face..])) } else { None };
+
+    let mut xlfd = Xlfd::empty();
+    xlfd.family_name = family.filter(|f| !f.is_empty());
+    xlfd.weight_name = Some(String::from(weight_name(weight)));
+    xlfd.slant = Some(String::from(if italic { "I" } else { "R" }));
+    xlfd.setwidth_name = Some(String::from("Normal"));
+    // the em is the cell less the internal leading
+    xlfd.pixel_size = Some(match height - internal_leading.min(height) {
+        0 => height as i32,
+        em => em as i32,
+    });
+    xlfd.point_size = Some(decipoints);
+    xlfd.resolution_x = Some(res_x);
+    xlfd.resolution_y = Some(res_y);
+    xlfd.spacing = Some(String::from(if widths.iter().all(|&w| w == max_width) { "C" } else { "P" }));
+    xlfd.average_width = Some(average as i32);
+    xlfd.charset_registry = registry;
+    xlfd.charset_encoding = encoding;
+
+    let mut properties = vec![
+        Property::new(ids::FONT_ASCENT, &PropertyValue::Int(ascent as i32)),
+        Property::new(ids::FONT_DESCENT, &PropertyValue::Int(descent as i32)),
+    ];
+    if usize::from(default) < count {
+        properties.push(Property::new(ids::DEFAULT_CHAR, &PropertyValue::Int(i32::from(first) + i32::from(default))));
+    }
+    let copyright = asciz(&data[6..66]);
+    if !copyright.trim().is_empty() {
+        properties.push(Property::new(ids::COPYRIGHT, &PropertyValue::Str(String::from(copyright.trim()))));
+    }
+
+    Ok(Font {
+        bdf_version: String::from("2.1"),
+        name: xlfd.to_string(),
+        size: FontSize {
+            point_size: points as u32,
+            x_dpi: res_x as u32,
+            y_dpi: res_y as u32,
+        },
+        bounding_box: BoundingBox {
+            width: max_width,
+            height: height as u32,
+            x_offset: 0,
+            y_offset: -(descent as i32),
+        },
+        metrics: MetricsSet::Normal,
+
+        comments: Vec::new(),
+        properties,
+        glyphs,
+
+        content_version: None,
+        scalable_width: None,
+        device_width: None,
+        scalable_width_alt: None,
+        device_width_alt: None,
+        vector: None,
+
+        xlfd,
+
+        source: None,
+    })
+}
+
+pub fn read_font<R: Read>(mut reader: R) -> Result<Font, Error> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    parse_font(&data)
+}
+
+// every font resource in an NE executable, usually one per size
+pub fn parse_fon(data: &[u8]) -> Result<Vec<Font>, Error> {
+    let word = |at| u16_at(data, at).ok_or_else(|| invalid_fon("truncated"));
+
+    if !data.starts_with(b"MZ") {
+        return Err(invalid_fon("missing MZ header"));
+    }
+
+    let ne = u32_at(data, 0x3c).ok_or_else(|| invalid_fon("truncated"))? as usize;
+    match data.get(ne..ne + 2) {
+        Some(b"NE") => (),
+        Some(b"PE") => return Err(invalid_fon("only NE containers are supported")),
+        _ => return Err(invalid_fon("missing NE header")),
+    }
+
+    let table = ne + usize::from(word(ne + 0x24)?);
+    let shift = word(table)?;
+    if shift > 15 {
+        return Err(invalid_fon("bad resource alignment"));
+    }
+
+    let mut fonts = Vec::new();
+    let mut at = table + 2;
+    loop {
+        let kind = word(at)?;
+        if kind == 0 {
+            break;
+        }
+
+        let count = usize::from(word(at + 2)?);
+        at += 8;
+
+        for _ in 0..count {
+            let offset = usize::from(word(at)?) << shift;
+            let length = usize::from(word(at + 2)?) << shift;
+            at += 12;
+
+            if kind == RT_FONT {
+                let resource = data.get(offset..offset + length).ok_or_else(|| invalid_fon("resource out of bounds"))?;
+                fonts.push(parse_font(resource)?);
+            }
+        }
+    }
+
+    if fonts.is_empty() {
+        return Err(invalid_fon("no font resources"));
+    }
+
+    Ok(fonts)
+}
+
+pub fn read_fon<R: Read>(mut reader: R) -> Result<Vec<Font>, Error> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    parse_fon(&data)
+}
+
+//
+
+// the dfCharSet for the font's charset, unicode fonts are written as windows ansi
+fn fnt_charset(font: &Font) -> u8 {
+    if font.xlfd.is_unicode() {
+        return 0;
+    }
+
+    let registry = font.xlfd.charset_registry.as_deref().unwrap_or("");
+    let encoding = font.xlfd.charset_encoding.as_deref().unwrap_or("");
+
+    match CHARSETS.iter().find(|(_, r, e)| r.eq_ignore_ascii_case(registry) && e.eq_ignore_ascii_case(encoding)) {
+        Some(&(charset, _, _)) => charset,
+        None if registry.eq_ignore_ascii_case("ISO8859") && encoding == "1" => 0,
+        None => DEFAULT_CHARSET,
+    }
+}
+
+// the characters to write by their single byte code, glyphs of unicode fonts outside of windows ansi are left out
+fn encoded_glyphs(font: &Font) -> Result<Vec<(u8, &Glyph)>, Error> {
+    let unicode = font.xlfd.is_unicode();
+
+    let mut chars = Vec::new();
+    for glyph in &font.glyphs {
+        let code = match glyph.encoding {
+            Encoding::Standard(code) => code,
+            _ => continue,
+        };
+
+        let code = if unicode { ansi_code(code) } else { u8::try_from(code).ok() };
+        match code {
+            Some(code) => chars.push((code, glyph)),
+            None if unicode => continue,
+            None => return Err(Error::GlyphValidation(glyph.name.clone(), "encoding too large for a fnt character")),
+        }
+    }
+
+    chars.sort_by_key(|&(code, _)| code);
+    chars.dedup_by_key(|&mut (code, _)| code);
+
+    if chars.is_empty() {
+        return Err(Error::FontValidation("fnt needs at least one encoded glyph"));
+    }
+
+    Ok(chars)
+}
+
+fn ansi_code(code: u32) -> Option<u8> {
+    if code < 0x80 {
+        return Some(code as u8);
+    }
+
+    (0x80..=0xff).find(|&b| Charset::Cp1252.to_unicode(b).map(|c| c as u32) == Some(code)).map(|b| b as u8)
+}
+
+// a character's bitmap column by column, fnt has no bearings so the ink has to lie within the advance
+fn char_columns(font: &Font, glyph: &Glyph, ascent: usize, height: usize) -> Result<(usize, Vec<u8>), Error> {
+    let width = glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0);
+    let width = match usize::try_from(width) {
+        Ok(width) if width <= 0xffff => width,
+        _ => return Err(Error::GlyphValidation(glyph.name.clone(), "advance too large or negative for a fnt character")),
+    };
+
+    let bbx = &glyph.bounding_box;
+    let top = ascent as i64 - (i64::from(bbx.y_offset) + i64::from(bbx.height));
+
+    let mut columns = vec![0u8; width.div_ceil(8) * height];
+    for y in 0..glyph.bitmap.height() {
+        for x in 0..glyph.bitmap.width() {
+            if glyph.bitmap.get(x, y) != Some(true) {
+                continue;
+            }
+
+            let (cx, cy) = (i64::from(bbx.x_offset) + x as i64, top + y as i64);
+            if cx < 0 || cy < 0 || cx >= width as i64 || cy >= height as i64 {
+                return Err(Error::GlyphValidation(glyph.name.clone(), "ink lies outside the advance and font height, fnt has no bearings"));
+            }
+
+            columns[(cx as usize / 8) * height + cy as usize] |= 0x80 >> (cx % 8);
+        }
+    }
+
+    Ok((width, columns))
+}
+
+fn fnt_data(font: &Font, options: &FntOptions) -> Result<Vec<u8>, Error> {
+    font.validate()?;
+
+    let (ascent, descent) = font.ascent_descent();
+    if ascent < 0 || descent < 0 || ascent + descent == 0 || ascent + descent > 0xffff {
+        return Err(Error::FontValidation("fnt needs a positive ascent and descent"));
+    }
+    let (ascent, height) = (ascent as usize, (ascent + descent) as usize);
+
+    let charset = fnt_charset(font);
+    let glyphs = encoded_glyphs(font)?;
+    let first = glyphs[0].0;
+    let last = glyphs[glyphs.len() - 1].0;
+
+    let mut chars = Vec::new();
+    for &(code, glyph) in &glyphs {
+        let (width, columns) = char_columns(font, glyph, ascent, height)?;
+        chars.push((code, width, columns));
+    }
+
+    let find = |code: u8| chars.iter().position(|&(c, _, _)| c == code);
+
+    let default_char = font.int_property(ids::DEFAULT_CHAR)
+                           .and_then(|code| if font.xlfd.is_unicode() { ansi_code(code as u32) } else { u8::try_from(code).ok() })
+                           .filter(|&code| find(code).is_some())
+                           .or_else(|| find(b' ').map(|_| b' '))
+                           .unwrap_or(first);
+    let break_char = find(b' ').map(|_| b' ').unwrap_or(first);
+
+    let widths: Vec<usize> = chars.iter().map(|&(_, width, _)| width).collect();
+    let max_width = widths.iter().cloned().max().unwrap_or(0);
+    let fixed = widths.iter().all(|&w| w == max_width);
+    // the average is meant to be the width of 'x'
+    let average = match find(b'x') {
+        Some(i) if charset == 0 || charset == DEFAULT_CHARSET => widths[i],
+        _ => (widths.iter().sum::<usize>() as f64 / widths.len() as f64).round() as usize,
+    };
+
+    let (header, entry) = match options.version {
+        FntVersion::V2 => (V2_HEADER, 4),
+        FntVersion::V3 => (V3_HEADER, 6),
+    };
+    let count = usize::from(last - first) + 1;
+
+    // bitmaps follow the character table, with a blank one for the closing absolute space entry
+    let bits_offset = header + (count + 1) * entry;
+    let mut bits = Vec::new();
+    let mut offsets = Vec::new();
+    for (_, _, columns) in &chars {
+        offsets.push(bits_offset + bits.len());
+        bits.extend_from_slice(columns);
+    }
+    let space_offset = bits_offset + bits.len();
+    bits.resize(bits.len() + average.div_ceil(8) * height, 0);
+
+    let face_offset = bits_offset + bits.len();
+    let family = match &font.xlfd.family_name {
+        Some(family) if !family.is_empty() => family.as_str(),
+        _ => font.name.as_str(),
+    };
+    let mut face = to_bytes(family, 31);
+    face.push(0);
+
+    let size = face_offset + face.len();
+    if let FntVersion::V2 = options.version {
+        if space_offset > 0xffff {
+            return Err(Error::FontValidation("font too large for fnt version 2, use version 3"));
+        }
+    }
+
+    let pixel_size = font.xlfd.pixel_size.filter(|&p| p > 0).map(|p| p as usize).unwrap_or(height);
+    let width_bytes = (widths.iter().map(|w| w.div_ceil(8)).sum::<usize>() + average.div_ceil(8)).next_multiple_of(2);
+
+    let mut out = Vec::with_capacity(size);
+    let word = |out: &mut Vec<u8>, v: usize| out.extend_from_slice(&(v.min(0xffff) as u16).to_le_bytes());
+    let dword = |out: &mut Vec<u8>, v: usize| out.extend_from_slice(&(v as u32).to_le_bytes());
+
+    word(&mut out, match options.version { FntVersion::V2 => 0x200, FntVersion::V3 => 0x300 });
+    dword(&mut out, size);
+    let mut copyright = to_bytes(font.str_property(ids::COPYRIGHT).unwrap_or(""), 60);
+    copyright.resize(60, 0);
+    out.extend_from_slice(&copyright);
+    word(&mut out, 0); // raster
+    word(&mut out, font.size.point_size as usize);
+    word(&mut out, font.size.y_dpi as usize);
+    word(&mut out, font.size.x_dpi as usize);
+    word(&mut out, ascent);
+    word(&mut out, height.saturating_sub(pixel_size));
+    word(&mut out, 0); // external leading
+    out.push(u8::from(font.xlfd.is_italic()));
+    out.push(0); // underline
+    out.push(0); // strike out
+    word(&mut out, usize::from(font.xlfd.weight_class()));
+    out.push(charset);
+    word(&mut out, if fixed { max_width } else { 0 });
+    word(&mut out, height);
+    out.push(u8::from(!fixed)); // the low bit marks variable pitch
+    word(&mut out, average);
+    word(&mut out, max_width);
+    out.push(first);
+    out.push(last);
+    out.push(default_char - first);
+    out.push(break_char.wrapping_sub(first));
+    word(&mut out, width_bytes);
+    dword(&mut out, 0); // device name
+    dword(&mut out, face_offset);
+    dword(&mut out, 0); // bits pointer
+    dword(&mut out, bits_offset);
+    out.push(0);
+    if let FntVersion::V3 = options.version {
+        dword(&mut out, if fixed { 0x11 } else { 0x12 }); // DFF_FIXED or DFF_PROPORTIONAL, DFF_1COLOR
+        word(&mut out, 0);
+        word(&mut out, 0);
+        word(&mut out, 0);
+        dword(&mut out, 0);
+        out.extend_from_slice(&[0; 16]);
+    }
+
+    let default_index = find(default_char).unwrap_or(0);
+    let mut entries: Vec<(usize, usize)> = vec![(widths[default_index], offsets[default_index]); count];
+    for (i, &(code, width, _)) in chars.iter().enumerate() {
+        entries[usize::from(code - first)] = (width, offsets[i]);
+    }
+    entries.push((average, space_offset));
+
+    for (width, offset) in entries {
+        word(&mut out, width);
+        match options.version {
+            FntVersion::V2 => word(&mut out, offset),
+            FntVersion::V3 => dword(&mut out, offset),
+        }
+    }
+
+    out.extend_from_slice(&bits);
+    out.extend_from_slice(&face);
+
+    Ok(out)
+}
+
+pub fn write_font<W: Write>(mut writer: W, font: &Font, options: &FntOptions) -> Result<W, Error> {
+    writer.write_all(&fnt_data(font, options)?)?;
+    writer.flush()?;
+
+    Ok(writer)
+}
+
+//
+
+// a DOS stub that prints a message when run, the NE header follows it
+fn mz_stub() -> Vec<u8> {
+    const CODE: [u8; 14] = [
+        0xba, 0x0e, 0x00, // mov dx, 0xe
+        0x0e,             // push cs
+        0x1f,             // pop ds
+        0xb4, 0x09,       // mov ah, 0x9
+        0xcd, 0x21,       // int 0x21
+        0xb8, 0x01, 0x4c, // mov ax, 0x4c01
+        0xcd, 0x21,       // int 0x21
+    ];
+
+    let mut stub = vec![0u8; 0x40];
+    stub[0..2].copy_from_slice(b"MZ");
+    stub[8..10].copy_from_slice(&4u16.to_le_bytes()); // header paragraphs
+    stub[10..12].copy_from_slice(&0x10u16.to_le_bytes());
+    stub[12..14].copy_from_slice(&0xffffu16.to_le_bytes());
+    stub[16..18].copy_from_slice(&0x100u16.to_le_bytes()); // sp
+    stub[24..26].copy_from_slice(&0x40u16.to_le_bytes()); // relocations
+    stub.extend_from_slice(&CODE);
+    stub.extend_from_slice(b"This is not a program!\r\nIt is a windows font library.\r\n$");
+    stub.resize(stub.len().next_multiple_of(16), 0);
+
+    let len = stub.len();
+    let pages = len.div_ceil(512);
+    stub[2..4].copy_from_slice(&((len - (pages - 1) * 512) as u16).to_le_bytes());
+    stub[4..6].copy_from_slice(&(pages as u16).to_le_bytes());
+    stub[0x3c..0x40].copy_from_slice(&(len as u32).to_le_bytes());
+
+    stub
+}
+
+// several sizes of a font as an NE font library, the resources are a font directory and the fonts
+pub fn write_fon<W: Write>(mut writer: W, fonts: &[Font], options: &FntOptions) -> Result<W, Error> {
+    if fonts.is_empty() || fonts.len() > 0x7ff {
+        return Err(Error::FontValidation("a fon holds between one and 2047 fonts"));
+    }
+
+    let fnts = fonts.iter().map(|f| fnt_data(f, options)).collect::<Result<Vec<_>, Error>>()?;
+
+    let first = &fonts[0];
+    let family = match &first.xlfd.family_name {
+        Some(family) if !family.is_empty() => family.clone(),
+        _ => first.name.clone(),
+    };
+    let module: String = family.chars().filter(|c| c.is_ascii_alphanumeric()).take(8).collect();
+    let module = if module.is_empty() { String::from("FONT") } else { module };
+
+    let word = |out: &mut Vec<u8>, v: usize| out.extend_from_slice(&(v as u16).to_le_bytes());
+    let dword = |out: &mut Vec<u8>, v: usize| out.extend_from_slice(&(v as u32).to_le_bytes());
+
+    // FONTDIR, a count then each font's number and directory entry, the header with device and face names
+    let mut fontdir = Vec::new();
+    word(&mut fontdir, fnts.len());
+    for (i, fnt) in fnts.iter().enumerate() {
+        word(&mut fontdir, i + 1);
+        fontdir.extend_from_slice(&fnt[..DIRENTRY_HEADER]);
+        fontdir.push(0);
+        let face = u32_at(fnt, 105).unwrap_or(0) as usize;
+        fontdir.extend_from_slice(&fnt[face..]);
+    }
+
+    let mut resident = vec![module.len() as u8];
+    resident.extend_from_slice(module.as_bytes());
+    resident.extend_from_slice(&[0; 3]);
+
+    let description = format!("FONTRES 100,{},{} : {}",
+                              first.size.x_dpi,
+                              first.size.y_dpi,
+                              String::from_utf8_lossy(&to_bytes(&family, 200)));
+    let mut nonresident = vec![description.len() as u8];
+    nonresident.extend_from_slice(description.as_bytes());
+    nonresident.extend_from_slice(&[0; 3]);
+
+    let entry_table = [0u8; 2];
+
+    let stub = mz_stub();
+
+    // the resource table is the alignment shift, one type block each for the directory and the fonts,
+    //   the closing zero and the directory's name
+    let table_len = 2 + (8 + 12) + (8 + 12 * fnts.len()) + 2 + 8;
+    let table_pad = table_len.next_multiple_of(16) - table_len;
+
+    let resident_at = 0x40 + table_len + table_pad;
+    let entry_at = resident_at + resident.len();
+    let nonresident_at = entry_at + entry_table.len();
+    let ne_len = nonresident_at + nonresident.len();
+    let ne_pad = ne_len.next_multiple_of(16) - ne_len;
+    let data_start = stub.len() + ne_len + ne_pad;
+
+    let mut table = Vec::new();
+    let mut data = Vec::new();
+    word(&mut table, 4);
+
+    let mut resource = |table: &mut Vec<u8>, bytes: &[u8], flags: usize, id: usize| {
+        word(table, (data_start + data.len()) >> 4);
+        data.extend_from_slice(bytes);
+        data.resize(data.len().next_multiple_of(16), 0);
+        word(table, bytes.len().div_ceil(16));
+        word(table, flags);
+        word(table, id);
+        dword(table, 0);
+    };
+
+    word(&mut table, usize::from(RT_FONTDIR));
+    word(&mut table, 1);
+    dword(&mut table, 0);
+    // moveable, pure and preloaded, named by the string at the end of the table
+    resource(&mut table, &fontdir, 0x0c50, table_len - 8);
+
+    word(&mut table, usize::from(RT_FONT));
+    word(&mut table, fnts.len());
+    dword(&mut table, 0);
+    for (i, fnt) in fnts.iter().enumerate() {
+        // moveable, pure and discardable, numbered from 1
+        resource(&mut table, fnt, 0x1c30, 0x8001 + i);
+    }
+
+    word(&mut table, 0);
+    table.push(7);
+    table.extend_from_slice(b"FONTDIR");
+    table.resize(table.len() + table_pad, 0);
+
+    let mut ne = Vec::new();
+    ne.extend_from_slice(b"NE");
+    ne.push(5); // linker version
+    ne.push(10);
+    word(&mut ne, entry_at);
+    word(&mut ne, entry_table.len());
+    dword(&mut ne, 0); // crc
+    word(&mut ne, 0x8308); // library, no data segment
+    word(&mut ne, 0); // automatic data segment
+    word(&mut ne, 0); // heap
+    word(&mut ne, 0); // stack
+    dword(&mut ne, 0); // cs:ip
+    dword(&mut ne, 0); // ss:sp
+    word(&mut ne, 0); // segments
+    word(&mut ne, 0); // module references
+    word(&mut ne, nonresident.len());
+    word(&mut ne, 0x40); // segment table
+    word(&mut ne, 0x40); // resource table
+    word(&mut ne, resident_at);
+    word(&mut ne, entry_at); // module reference table
+    word(&mut ne, entry_at); // imported names
+    dword(&mut ne, stub.len() + nonresident_at);
+    word(&mut ne, 0); // movable entries
+    word(&mut ne, 4); // segment alignment shift
+    word(&mut ne, 0); // resource segments
+    ne.push(2); // windows
+    ne.push(8);
+    word(&mut ne, 0);
+    word(&mut ne, 0);
+    word(&mut ne, 0);
+    word(&mut ne, 0x300); // windows 3.0
+
+    writer.write_all(&stub)?;
+    writer.write_all(&ne)?;
+    writer.write_all(&table)?;
+    writer.write_all(&resident)?;
+    writer.write_all(&entry_table)?;
+    writer.write_all(&nonresident)?;
+    writer.write_all(&vec![0; ne_pad])?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+
+    Ok(writer)
+}
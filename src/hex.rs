@@ -0,0 +1,273 @@
+use std::{
+    collections::BTreeMap,
+    io::{
+        BufRead,
+        Write,
+    },
+};
+
+use crate::{
+    ids,
+    unicode_glyph_name,
+    Bitmap,
+    Diagnostic,
+    Encoding,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+// GNU Unifont cells, 8 or 16 pixels wide and 16 high with 2 rows below the baseline
+const HEIGHT: usize = 16;
+const DESCENT: i32 = 2;
+
+fn code_point(s: &str) -> Option<u32> {
+    if s.is_empty() || s.len() > 6 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(s, 16).ok().filter(|&u| u <= 0x10ffff)
+}
+
+fn build_font(cells: BTreeMap<u32, Bitmap>) -> Font {
+    let glyphs = cells.into_iter()
+                      .map(|(code, bitmap)| Glyph::from_cell(unicode_glyph_name(code), Encoding::Standard(code), bitmap, DESCENT))
+                      .collect();
+
+    Font::from_cells(glyphs, HEIGHT as u32, DESCENT, true)
+}
+
+// adds a cell read at `line`, a code point may only be defined once
+fn insert_cell(cells: &mut BTreeMap<u32, Bitmap>, code: u32, bitmap: Bitmap, line: usize, text: &str) -> Result<(), Diagnostic> {
+    if cells.insert(code, bitmap).is_some() {
+        return Err(Diagnostic::new(line, text, Error::DuplicateCodepoint(code)));
+    }
+
+    Ok(())
+}
+
+fn read_lines<R: BufRead>(reader: R) -> impl Iterator<Item = Result<(usize, String), Diagnostic>> {
+    reader.lines()
+          .enumerate()
+          .map(|(n, line)| line.map(|l| (n + 1, l)).map_err(|e| Diagnostic::new(n + 1, "", Error::Io(e))))
+}
+
+//
+
+// one glyph per line, `code:digits` with 32 digits for 8 pixel wide cells and 64 for 16 pixel wide ones
+pub fn read_font<R: BufRead>(reader: R) -> Result<Font, Diagnostic> {
+    let mut cells = BTreeMap::new();
+
+    for line in read_lines(reader) {
+        let (n, text) = line?;
+        let content = text.trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        let (code, digits) = content.split_once(':')
+                                    .ok_or_else(|| Diagnostic::new(n, &text, Error::ParseError("code:digits")))?;
+
+        let code = code_point(code).ok_or_else(|| Diagnostic::new(n, &text, Error::ParseError("hex code point")))?;
+
+        let width = match digits.len() {
+            32 => 8,
+            64 => 16,
+            _ => return Err(Diagnostic::new(n, &text, Error::ParseError("32 or 64 hex digits"))),
+        };
+
+        let mut bitmap = Bitmap::new(width, HEIGHT);
+        for (i, c) in digits.chars().enumerate() {
+            let nibble = c.to_digit(16).ok_or_else(|| Diagnostic::new(n, &text, Error::ParseError("32 or 64 hex digits")))?;
+
+            let (x, y) = ((i * 4) % width, (i * 4) / width);
+            for bit in 0..4 {
+                if nibble & (0x8 >> bit) != 0 {
+                    bitmap.set(x + bit, y, true);
+                }
+            }
+        }
+
+        insert_cell(&mut cells, code, bitmap, n, &text)?;
+    }
+
+    Ok(build_font(cells))
+}
+
+pub fn parse_font(input: &str) -> Result<Font, Diagnostic> {
+    read_font(input.as_bytes())
+}
+
+// a glyph is `code:` followed by its 16 rows, one per line with '-' for paper and '#' for ink,
+//   the first row may share the line with the code, glyphs are separated by blank lines
+pub fn read_hexdraw<R: BufRead>(reader: R) -> Result<Font, Diagnostic> {
+    struct Pending {
+        line: usize,
+        text: String,
+        code: u32,
+        rows: Vec<Vec<bool>>,
+    }
+
+    fn finish(cells: &mut BTreeMap<u32, Bitmap>, pending: Pending) -> Result<(), Diagnostic> {
+        let width = pending.rows.first().map(|r| r.len()).unwrap_or(0);
+        if pending.rows.len() != HEIGHT || (width != 8 && width != 16) || pending.rows.iter().any(|r| r.len() != width) {
+            return Err(Diagnostic::new(pending.line, &pending.text, Error::ParseError("16 rows of 8 or 16 pixels")));
+        }
+
+        let mut bitmap = Bitmap::new(width, HEIGHT);
+        for (y, row) in pending.rows.iter().enumerate() {
+            for (x, &on) in row.iter().enumerate() {
+                bitmap.set(x, y, on);
+            }
+        }
+
+        insert_cell(cells, pending.code, bitmap, pending.line, &pending.text)
+    }
+
+    fn row(n: usize, text: &str, s: &str) -> Result<Vec<bool>, Diagnostic> {
+        s.chars()
+         .map(|c| match c {
+             '-' | '.' => Ok(false),
+             '#' => Ok(true),
+             _ => Err(Diagnostic::new(n, text, Error::ParseError("'-' or '#'"))),
+         })
+         .collect()
+    }
+
+    let mut cells = BTreeMap::new();
+    let mut pending: Option<Pending> = None;
+
+    for line in read_lines(reader) {
+        let (n, text) = line?;
+        let content = text.trim();
+
+        if content.is_empty() {
+            if let Some(p) = pending.take() {
+                finish(&mut cells, p)?;
+            }
+            continue;
+        }
+
+        let header = content.split_once(':').filter(|_| !text.starts_with(char::is_whitespace));
+        match (header, &mut pending) {
+            (Some((code, rest)), _) => {
+                if let Some(p) = pending.take() {
+                    finish(&mut cells, p)?;
+                }
+
+                let code = code_point(code).ok_or_else(|| Diagnostic::new(n, &text, Error::ParseError("hex code point")))?;
+
+                let rest = rest.trim();
+                let rows = if rest.is_empty() { Vec::new() } else { vec![row(n, &text, rest)?] };
+
+                pending = Some(Pending { line: n, text: text.clone(), code, rows });
+            }
+            (None, Some(p)) => p.rows.push(row(n, &text, content)?),
+            (None, None) => return Err(Diagnostic::new(n, &text, Error::ParseError("code:"))),
+        }
+    }
+
+    if let Some(p) = pending.take() {
+        finish(&mut cells, p)?;
+    }
+
+    Ok(build_font(cells))
+}
+
+pub fn parse_hexdraw(input: &str) -> Result<Font, Diagnostic> {
+    read_hexdraw(input.as_bytes())
+}
+
+//
+
+// draws a glyph into an 8 or 16 pixel wide cell, the narrow one when both advance and ink fit in it
+fn cell(font: &Font, descent: i32, glyph: &Glyph) -> Result<Bitmap, Error> {
+    let advance = glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0);
+    if advance > 16 {
+        return Err(Error::GlyphValidation(glyph.name.clone(), "advance is wider than a 16x16 cell"));
+    }
+
+    let bbx = &glyph.bounding_box;
+    let top = i64::from(HEIGHT as i32 - descent) - (i64::from(bbx.y_offset) + i64::from(bbx.height));
+
+    let ink: Vec<(i64, i64)> = (0..glyph.bitmap.height())
+        .flat_map(|y| (0..glyph.bitmap.width()).map(move |x| (x, y)))
+        .filter(|&(x, y)| glyph.bitmap.get(x, y) == Some(true))
+        .map(|(x, y)| (i64::from(bbx.x_offset) + x as i64, top + y as i64))
+        .collect();
+
+    let width = if advance <= 8 && ink.iter().all(|&(x, _)| x < 8) { 8 } else { 16 };
+
+    let mut bitmap = Bitmap::new(width, HEIGHT);
+    for (x, y) in ink {
+        if x < 0 || y < 0 || x >= width as i64 || y >= HEIGHT as i64 {
+            return Err(Error::GlyphValidation(glyph.name.clone(), "ink lies outside the 16x16 cell"));
+        }
+
+        bitmap.set(x as usize, y as usize, true);
+    }
+
+    Ok(bitmap)
+}
+
+// the cells of all encoded glyphs in code point order, the font's descent is kept at the bottom of the cell
+fn cells(font: &Font) -> Result<Vec<(u32, Bitmap)>, Error> {
+    if !font.xlfd.is_unicode() {
+        return Err(Error::FontValidation("hex needs an ISO10646 font"));
+    }
+
+    let descent = font.int_property(ids::FONT_DESCENT).unwrap_or(-font.bounding_box.y_offset);
+
+    let mut cells = Vec::new();
+    for glyph in &font.glyphs {
+        let code = match glyph.encoding {
+            Encoding::Standard(code) => code,
+            _ => continue,
+        };
+
+        cells.push((code, cell(font, descent, glyph)?));
+    }
+
+    cells.sort_by_key(|&(code, _)| code);
+    if cells.windows(2).any(|w| w[0].0 == w[1].0) {
+        return Err(Error::FontValidation("two glyphs share a code point"));
+    }
+
+    Ok(cells)
+}
+
+pub fn write_font<W: Write>(mut writer: W, font: &Font) -> Result<W, Error> {
+    for (code, bitmap) in cells(font)? {
+        write!(writer, "{:04X}:", code)?;
+        for row in bitmap.rows() {
+            for byte in row.to_bytes() {
+                write!(writer, "{:02X}", byte)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+
+    Ok(writer)
+}
+
+pub fn write_hexdraw<W: Write>(mut writer: W, font: &Font) -> Result<W, Error> {
+    for (i, (code, bitmap)) in cells(font)?.into_iter().enumerate() {
+        if i > 0 {
+            writeln!(writer)?;
+        }
+
+        write!(writer, "{:04X}:", code)?;
+        for row in bitmap.rows() {
+            let pixels: String = row.iter().map(|on| if on { '#' } else { '-' }).collect();
+            writeln!(writer, "\t{}", pixels)?;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(writer)
+}
@@ -0,0 +1,555 @@
+use std::ops::Range;
+
+use crate::{
+    render::{
+        self,
+        is_selector,
+        Canvas,
+        Cluster,
+    },
+    stack::Fallback,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    // spaces are widened to fill the line, except on the last line of a paragraph
+    Justify,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LineBreak {
+    // only after whitespace
+    Whitespace,
+    // at the opportunities of the unicode line breaking algorithm, for the common classes
+    Unicode,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Direction {
+    Horizontal,
+    // top to bottom by DWIDTH1 with glyphs placed by VVECTOR, lines are columns from right to left
+    Vertical,
+}
+
+#[derive(Clone, Debug)]
+pub struct LayoutOptions {
+    pub direction: Direction,
+    // lines longer than this are broken, words that do not fit on a line of their own are split,
+    //   in vertical text this is the height of a column
+    pub max_width: Option<i32>,
+    pub align: Align,
+    pub line_break: LineBreak,
+    // pixels added between lines
+    pub line_spacing: i32,
+    // explicit tab stops from the left of the line, further stops every `tab_width` after the last,
+    //   which is eight spaces when not given
+    pub tab_stops: Vec<i32>,
+    pub tab_width: Option<i32>,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            direction: Direction::Horizontal,
+            max_width: None,
+            align: Align::Left,
+            line_break: LineBreak::Unicode,
+            line_spacing: 0,
+            tab_stops: Vec::new(),
+            tab_width: None,
+        }
+    }
+}
+
+// a rectangle relative to the pen on the baseline, y growing down, right and bottom exclusive
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Bounds {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Bounds {
+    pub(crate) fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    pub(crate) fn offset(self, x: i32, y: i32) -> Bounds {
+        Bounds {
+            left: self.left + x,
+            top: self.top + y,
+            right: self.right + x,
+            bottom: self.bottom + y,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Measure {
+    pub advance: i32,
+    // the pixels actually set, none for blank text
+    pub ink: Option<Bounds>,
+    pub ascent: i32,
+    pub descent: i32,
+    pub line_height: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct PositionedGlyph<'a> {
+    pub c: char,
+    pub glyph: &'a Glyph,
+    // the pen position on the baseline, from the top left of the layout, origin 0 in vertical text too
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Line<'a> {
+    pub glyphs: Vec<PositionedGlyph<'a>>,
+    // the bytes of the text on this line, the break itself not included
+    pub range: Range<usize>,
+    // y of the baseline, or in vertical text x of the line through origin 1 of every glyph
+    pub baseline: i32,
+    // from the start of the line to the end of its last non-space character, along the line
+    pub width: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Layout<'a> {
+    pub lines: Vec<Line<'a>>,
+    // the wrapping width, or the widest line without one, the other way round in vertical text
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Layout<'_> {
+    pub fn ink(&self) -> Option<Bounds> {
+        self.lines.iter()
+                  .flat_map(|line| line.glyphs.iter())
+                  .filter_map(|g| glyph_ink(g.glyph).map(|b| b.offset(g.x, g.y)))
+                  .reduce(Bounds::union)
+    }
+
+    // draws the glyphs with the top left of the layout at (x, y)
+    pub fn render<C: Canvas>(&self, x: i32, y: i32, canvas: &mut C) {
+        for g in self.lines.iter().flat_map(|line| line.glyphs.iter()) {
+            render::draw_glyph(g.glyph, x + g.x, y + g.y, canvas);
+        }
+    }
+}
+
+//
+
+// the set pixels of a glyph relative to the pen
+pub(crate) fn glyph_ink(glyph: &Glyph) -> Option<Bounds> {
+    let bitmap = &glyph.bitmap;
+    let bbx = &glyph.bounding_box;
+    let top = -(bbx.y_offset + bitmap.height() as i32);
+
+    let mut bounds: Option<Bounds> = None;
+    for y in 0..bitmap.height() {
+        for x in 0..bitmap.width() {
+            if bitmap.get(x, y) == Some(true) {
+                let (x, y) = (bbx.x_offset + x as i32, top + y as i32);
+                let pixel = Bounds { left: x, top: y, right: x + 1, bottom: y + 1 };
+                bounds = Some(bounds.map_or(pixel, |b| b.union(pixel)));
+            }
+        }
+    }
+
+    bounds
+}
+
+fn is_space(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+fn is_mandatory_break(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+pub(crate) fn is_combining(c: char) -> bool {
+    matches!(c as u32, 0x300..=0x36f | 0x483..=0x489 | 0x591..=0x5bd | 0x610..=0x61a | 0x64b..=0x65f |
+                       0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x20d0..=0x20ff | 0x3099..=0x309a | 0xfe20..=0xfe2f)
+}
+
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32, 0x2e80..=0x2fff | 0x3040..=0x30ff | 0x3100..=0x31ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff |
+                       0xa000..=0xa4cf | 0xac00..=0xd7af | 0xf900..=0xfaff | 0xff01..=0xff60 | 0x20000..=0x3ffff)
+}
+
+// the line breaking classes that matter for the opportunities below
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum Class {
+    Space,
+    ZeroWidth,
+    Glue,
+    Combining,
+    // closing punctuation and what may not start a line
+    Close,
+    Open,
+    Hyphen,
+    Numeric,
+    Ideographic,
+    Alphabetic,
+}
+
+fn class(c: char) -> Class {
+    match c {
+        '\u{200b}' => Class::ZeroWidth,
+        '\u{a0}' | '\u{2007}' | '\u{202f}' | '\u{2060}' | '\u{feff}' => Class::Glue,
+        ')' | ']' | '}' | ',' | '.' | ':' | ';' | '!' | '?' | '%' |
+        '\u{3001}' | '\u{3002}' | '\u{3009}' | '\u{300b}' | '\u{300d}' | '\u{300f}' | '\u{3011}' | '\u{30fc}' |
+        '\u{ff01}' | '\u{ff09}' | '\u{ff0c}' | '\u{ff0e}' | '\u{ff1a}' | '\u{ff1b}' | '\u{ff1f}' | '\u{ff3d}' | '\u{ff5d}' => Class::Close,
+        '(' | '[' | '{' |
+        '\u{3008}' | '\u{300a}' | '\u{300c}' | '\u{300e}' | '\u{3010}' | '\u{ff08}' | '\u{ff3b}' | '\u{ff5b}' => Class::Open,
+        '-' | '\u{ad}' | '\u{2010}' | '\u{2012}' | '\u{2013}' => Class::Hyphen,
+        '0'..='9' => Class::Numeric,
+        c if is_space(c) => Class::Space,
+        c if is_combining(c) => Class::Combining,
+        c if is_ideographic(c) => Class::Ideographic,
+        _ => Class::Alphabetic,
+    }
+}
+
+// whether a line may break before each character of a paragraph
+fn break_opportunities(chars: &[char], line_break: LineBreak) -> Vec<bool> {
+    let classes: Vec<Class> = chars.iter().map(|&c| class(c)).collect();
+
+    let mut breaks = vec![false; chars.len()];
+    // combining marks take the class of their base, and the class before a run of spaces is remembered
+    let mut prev = None;
+    let mut before_spaces = None;
+
+    for (i, &next) in classes.iter().enumerate() {
+        if let Some(prev) = prev {
+            breaks[i] = match line_break {
+                LineBreak::Whitespace => prev == Class::Space && next != Class::Space,
+                LineBreak::Unicode => may_break(prev, next, before_spaces.unwrap_or(prev)),
+            };
+        }
+
+        if next != Class::Combining || prev.is_none() || prev == Some(Class::Space) {
+            prev = Some(next);
+        }
+        if next != Class::Space {
+            before_spaces = prev;
+        }
+    }
+
+    breaks
+}
+
+fn may_break(prev: Class, next: Class, before_spaces: Class) -> bool {
+    use Class::*;
+
+    if matches!(next, Space | Combining | Glue | Close) {
+        return false;
+    }
+
+    match prev {
+        ZeroWidth => true,
+        Glue => false,
+        _ if before_spaces == Open => false,
+        Space => true,
+        // keeps a minus sign with its number
+        Hyphen => next != Numeric,
+        _ => prev == Ideographic || next == Ideographic,
+    }
+}
+
+//
+
+struct Shaper<'a, 'o> {
+    fonts: &'o Fallback<'a>,
+    options: &'o LayoutOptions,
+    tab_width: i32,
+}
+
+impl<'a, 'o> Shaper<'a, 'o> {
+    fn new(fonts: &'o Fallback<'a>, options: &'o LayoutOptions) -> Self {
+        let (font, glyph) = fonts.glyph(' ');
+        let space = font.advance(glyph).0;
+
+        Self {
+            fonts,
+            tab_width: options.tab_width.unwrap_or(space * 8),
+            options,
+        }
+    }
+
+    fn glyph(&self, c: char) -> (&'a Font, Option<&'a Glyph>) {
+        self.fonts.glyph(c)
+    }
+
+    // the glyph of a combining mark, marks no font has are left out rather than drawn as DEFAULT_CHAR
+    fn mark(&self, c: char) -> Option<&'a Glyph> {
+        self.fonts.find(c).filter(|_| is_combining(c)).map(|(_, glyph)| glyph)
+    }
+
+    // how far the pen moves along the line over `c` when it stands at `x`
+    fn advance(&self, c: char, x: i32) -> i32 {
+        // marks sit on the character before them
+        if is_combining(c) || is_selector(c) {
+            return 0;
+        }
+
+        if c == '\t' {
+            if let Some(&stop) = self.options.tab_stops.iter().find(|&&stop| stop > x) {
+                return stop - x;
+            }
+            if self.tab_width > 0 {
+                let last = self.options.tab_stops.last().copied().filter(|&last| last <= x).unwrap_or(0);
+                return (x - last) / self.tab_width * self.tab_width + self.tab_width + last - x;
+            }
+        }
+
+        let (font, glyph) = self.glyph(c);
+        match self.options.direction {
+            Direction::Horizontal => font.advance(glyph).0,
+            Direction::Vertical => -font.vertical_advance(glyph).1,
+        }
+    }
+
+    // the end of the line that starts at `start`, greedily taking as much as fits
+    fn fit(&self, chars: &[char], breaks: &[bool], start: usize) -> usize {
+        let max_width = match self.options.max_width {
+            Some(max_width) => max_width,
+            None => return chars.len(),
+        };
+
+        let mut x = 0;
+        let mut last_break = None;
+        for i in start..chars.len() {
+            if i > start && breaks[i] {
+                last_break = Some(i);
+            }
+
+            let advance = self.advance(chars[i], x);
+            // whitespace may hang past the end of the line
+            if i > start && !is_space(chars[i]) && x + advance > max_width {
+                return last_break.unwrap_or(i);
+            }
+            x += advance;
+        }
+
+        chars.len()
+    }
+}
+
+// line height from FONT_ASCENT and FONT_DESCENT, or the font bounding box without them
+pub fn line_height(font: &Font) -> i32 {
+    let (ascent, descent) = font.ascent_descent();
+    ascent + descent
+}
+
+// the size of `text` set on a single line, tabs every eight spaces
+pub fn measure(font: &Font, text: &str) -> Result<Measure, Error> {
+    measure_fallback(&Fallback::new(font, text), text)
+}
+
+pub(crate) fn measure_fallback(fonts: &Fallback<'_>, text: &str) -> Result<Measure, Error> {
+    let options = LayoutOptions::default();
+    let (ascent, descent) = fonts.ascent_descent();
+    let shaper = Shaper::new(fonts, &options);
+
+    let mut x = 0;
+    let mut ink: Option<Bounds> = None;
+    let mut base = (0, Cluster::new(None));
+    for c in text.chars() {
+        let (glyph, offset) = if is_combining(c) || is_selector(c) {
+            match shaper.mark(c) {
+                Some(mark) => (Some(mark), base.1.place(mark)),
+                None => continue,
+            }
+        } else {
+            let glyph = shaper.glyph(c).1.filter(|_| c != '\t');
+            base = (x, Cluster::new(glyph));
+            (glyph, (0, 0))
+        };
+
+        if let Some(bounds) = glyph.and_then(glyph_ink) {
+            let bounds = bounds.offset(base.0 + offset.0, offset.1);
+            ink = Some(ink.map_or(bounds, |b| b.union(bounds)));
+        }
+        x += shaper.advance(c, x);
+    }
+
+    Ok(Measure {
+        advance: x,
+        ink,
+        ascent,
+        descent,
+        line_height: ascent + descent,
+    })
+}
+
+// breaks `text` into lines and places every glyph, mandatory breaks always end a line
+pub fn layout<'a>(font: &'a Font, text: &str, options: &LayoutOptions) -> Result<Layout<'a>, Error> {
+    layout_fallback(&Fallback::new(font, text), text, options)
+}
+
+pub(crate) fn layout_fallback<'a>(fonts: &Fallback<'a>, text: &str, options: &LayoutOptions) -> Result<Layout<'a>, Error> {
+    let shaper = Shaper::new(fonts, options);
+    let (ascent, descent) = shaper.fonts.ascent_descent();
+    let line_height = ascent + descent;
+
+    // paragraphs as the byte offset they start at and their characters with byte offsets
+    let mut paragraphs: Vec<(usize, Vec<(usize, char)>)> = vec![(0, Vec::new())];
+    let mut iter = text.char_indices().peekable();
+    while let Some((i, c)) = iter.next() {
+        if is_mandatory_break(c) {
+            let mut end = i + c.len_utf8();
+            if c == '\r' && iter.next_if(|&(_, c)| c == '\n').is_some() {
+                end += 1;
+            }
+            paragraphs.push((end, Vec::new()));
+        } else if let Some(paragraph) = paragraphs.last_mut() {
+            paragraph.1.push((i, c));
+        }
+    }
+
+    // lines as (characters with byte offsets, byte range, whether the paragraph ends there)
+    let mut lines = Vec::new();
+    for (offset, paragraph) in &paragraphs {
+        let chars: Vec<char> = paragraph.iter().map(|&(_, c)| c).collect();
+        let breaks = break_opportunities(&chars, options.line_break);
+        let byte = |i: usize| match paragraph.get(i) {
+            Some(&(b, _)) => b,
+            None => paragraph.last().map(|&(b, c)| b + c.len_utf8()).unwrap_or(*offset),
+        };
+
+        let mut start = 0;
+        loop {
+            let end = shaper.fit(&chars, &breaks, start);
+            lines.push((&paragraph[start..end], byte(start)..byte(end), end == chars.len()));
+
+            start = end;
+            if start >= chars.len() {
+                break;
+            }
+        }
+    }
+
+    // pen positions from the left of each line, and the width up to the last non-space
+    let mut placed = Vec::new();
+    for (chars, range, last) in lines {
+        let mut x = 0;
+        let mut width = 0;
+        let mut positions = Vec::with_capacity(chars.len());
+        for &(_, c) in chars {
+            positions.push(x);
+            x += shaper.advance(c, x);
+            if !is_space(c) {
+                width = x;
+            }
+        }
+        placed.push((chars, positions, range, last, width));
+    }
+
+    let box_width = options.max_width.unwrap_or_else(|| placed.iter().map(|p| p.4).max().unwrap_or(0));
+
+    // vertical columns are as wide as the font's advance and stacked from the right
+    let count = placed.len() as i32;
+    let column = shaper.fonts.fonts()
+                             .iter()
+                             .map(|font| font.device_width.map(|d| d.x).unwrap_or(font.bounding_box.width as i32))
+                             .max()
+                             .unwrap_or(0);
+    let centre = shaper.fonts.primary().vertical_origin(None).0;
+    let across = match options.direction {
+        Direction::Horizontal => line_height,
+        Direction::Vertical => column,
+    };
+
+    let mut out = Vec::new();
+    for (n, (chars, positions, range, last, width)) in placed.into_iter().enumerate() {
+        let baseline = match options.direction {
+            Direction::Horizontal => ascent + n as i32 * (line_height + options.line_spacing),
+            Direction::Vertical => (count - 1 - n as i32) * (column + options.line_spacing) + centre,
+        };
+        let slack = (box_width - width).max(0);
+
+        // interior spaces are the ones before the last non-space character, after the last tab
+        let content = chars.iter().rposition(|&(_, c)| !is_space(c)).map(|i| i + 1).unwrap_or(0);
+        let first = chars[..content].iter().rposition(|&(_, c)| c == '\t').map(|i| i + 1).unwrap_or(0);
+        let spaces: Vec<usize> = (first..content).filter(|&i| chars[i].1 == ' ').collect();
+
+        let (shift, justify) = match options.align {
+            Align::Left => (0, false),
+            Align::Center => (slack / 2, false),
+            Align::Right => (slack, false),
+            Align::Justify => (0, !last && !spaces.is_empty()),
+        };
+
+        let mut glyphs = Vec::new();
+        let mut extra = 0;
+        let mut base = ((0, 0), Cluster::new(None));
+        for (i, (&(_, c), &x)) in chars.iter().zip(&positions).enumerate() {
+            if is_combining(c) || is_selector(c) {
+                if let Some(mark) = shaper.mark(c) {
+                    let (dx, dy) = base.1.place(mark);
+                    glyphs.push(PositionedGlyph { c, glyph: mark, x: (base.0).0 + dx, y: (base.0).1 + dy });
+                }
+                continue;
+            }
+
+            let (font, glyph) = shaper.glyph(c);
+            let glyph = glyph.filter(|_| c != '\t' && !is_space(c));
+            let along = x + shift + extra;
+            let (x, y) = match options.direction {
+                Direction::Horizontal => (along, baseline),
+                Direction::Vertical => {
+                    let (vx, vy) = font.vertical_origin(glyph);
+                    (baseline - vx, along + vy)
+                }
+            };
+
+            if let Some(glyph) = glyph {
+                glyphs.push(PositionedGlyph { c, glyph, x, y });
+            }
+            base = ((x, y), Cluster::new(glyph));
+
+            if justify {
+                if let Ok(k) = spaces.binary_search(&i) {
+                    // the slack spread evenly, the first spaces taking the remainder
+                    let share = slack / spaces.len() as i32;
+                    extra += share + i32::from((k as i32) < slack % spaces.len() as i32);
+                }
+            }
+        }
+
+        out.push(Line {
+            glyphs,
+            range,
+            baseline,
+            width: if justify { box_width } else { width },
+        });
+    }
+
+    let depth = count * across + (count - 1).max(0) * options.line_spacing;
+    let (width, height) = match options.direction {
+        Direction::Horizontal => (box_width, depth),
+        Direction::Vertical => (depth, box_width),
+    };
+
+    Ok(Layout {
+        lines: out,
+        width,
+        height,
+    })
+}
@@ -3,8 +3,16 @@ use std::{
         Deref,
         DerefMut,
     },
-    convert::TryFrom,
     fmt,
+    hash::{
+        Hash,
+        Hasher,
+    },
+    io::{
+        self,
+        BufRead,
+        Write,
+    },
     str::{
         FromStr,
         SplitWhitespace
@@ -13,12 +21,38 @@ use std::{
 
 use bit_vec::BitVec;
 
-//
+mod lossless;
+
+use lossless::{
+    Slot,
+    SourceLine,
+};
+
+pub use lossless::SourceMap;
+
+pub mod charset;
+use charset::{
+    Charset,
+    Unmapped,
+};
 
-// TODO xlfd
-//        verify XLFD
-//        parse from string
-//        separate into its own module
+pub mod xlfd;
+pub use xlfd::Xlfd;
+
+pub mod pcf;
+pub mod psf;
+pub mod hex;
+pub mod otb;
+pub mod fnt;
+pub mod png;
+pub mod atlas;
+pub mod bmfont;
+pub mod source;
+pub mod render;
+pub mod layout;
+pub mod stack;
+#[cfg(feature = "embedded-graphics")]
+pub mod eg;
 
 //
 
@@ -41,6 +75,14 @@ mod ids {
 
     pub const STARTPROPERTIES: &str = "STARTPROPERTIES";
     pub const ENDPROPERTIES: &str = "ENDPROPERTIES";
+    pub const DEFAULT_CHAR: &str = "DEFAULT_CHAR";
+    pub const FONT_ASCENT: &str = "FONT_ASCENT";
+    pub const FONT_DESCENT: &str = "FONT_DESCENT";
+    pub const COPYRIGHT: &str = "COPYRIGHT";
+    pub const X_HEIGHT: &str = "X_HEIGHT";
+    pub const CAP_HEIGHT: &str = "CAP_HEIGHT";
+    pub const UNDERLINE_POSITION: &str = "UNDERLINE_POSITION";
+    pub const UNDERLINE_THICKNESS: &str = "UNDERLINE_THICKNESS";
 
     pub const STARTCHAR: &str = "STARTCHAR";
     pub const ENCODING: &str = "ENCODING";
@@ -71,17 +113,172 @@ mod ids {
 #[derive(Debug)]
 pub enum Error {
     MissingValue(String),
+    MissingEntry(String),
     UnexpectedEntry(String),
+    CountMismatch(&'static str, usize, usize),
+    InvalidValue(String, Box<Error>),
     MissingBoundingBox,
     InvalidCodepoint(u32),
+    DuplicateCodepoint(u32),
     ParseError(&'static str),
-    SpecialEncoding,
+    UnsupportedCharset(String),
+    InvalidFormat(&'static str, &'static str),
+    Io(io::Error),
 
     FontValidation(&'static str),
     XlfdValidation(&'static str),
-    GlyphValidation(char, &'static str),
+    XlfdMismatch(&'static str),
+    GlyphValidation(String, &'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match self {
+            MissingValue(id) => write!(f, "missing value for {}", id),
+            MissingEntry(id) => write!(f, "missing {}", id),
+            UnexpectedEntry(id) => write!(f, "unexpected {}", id),
+            CountMismatch(id, declared, found) => write!(f, "{} declares {} but {} found", id, declared, found),
+            InvalidValue(id, _) => write!(f, "invalid value for {}", id),
+            MissingBoundingBox => write!(f, "bitmap without a bounding box"),
+            InvalidCodepoint(u) => write!(f, "invalid codepoint {}", u),
+            DuplicateCodepoint(u) => write!(f, "codepoint {} defined more than once", u),
+            ParseError(desired) => write!(f, "expected {}", desired),
+            UnsupportedCharset(name) => write!(f, "unsupported charset {}", name),
+            InvalidFormat(format, msg) => write!(f, "invalid {} file: {}", format, msg),
+            Io(_) => write!(f, "i/o error"),
+
+            FontValidation(msg) => write!(f, "invalid font: {}", msg),
+            XlfdValidation(msg) => write!(f, "invalid xlfd: {}", msg),
+            XlfdMismatch(id) => write!(f, "font name disagrees with {}", id),
+            GlyphValidation(name, msg) => write!(f, "invalid glyph {}: {}", name, msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::InvalidValue(_, err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+//
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// an error pinned to where it was found in the input
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub text: String,
+    pub error: Error,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, text: &str, error: Error) -> Self {
+        use Error::*;
+
+        let text = text.trim_end_matches(['\r', '\n']);
+
+        let content = text.trim();
+        let start = text.len() - text.trim_start().len();
+        let value = content.find(char::is_whitespace)
+                           .map(|n| (start + n, content[n..].trim()))
+                           .map(|(n, v)| (n + text[n..].find(v).unwrap_or(0), v.len()));
+
+        let (at, len) = match &error {
+            MissingValue(id) | MissingEntry(id) | UnexpectedEntry(id) => match text.find(id.as_str()) {
+                Some(n) => (n, id.len()),
+                None    => (start, content.len()),
+            },
+            InvalidValue(..) | ParseError(_) | InvalidCodepoint(_) => {
+                value.unwrap_or((start, content.len()))
+            }
+            _ => (start, content.len()),
+        };
+
+        Self {
+            severity: Severity::Error,
+            line,
+            column: text[..at].chars().count() + 1,
+            token: String::from(&text[at..(at + len)]),
+            text: String::from(text),
+            error,
+        }
+    }
+
+    pub fn warning(line: usize, text: &str, error: Error) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::new(line, text, error)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.severity, self.error)?;
+
+        let num = self.line.to_string();
+        let pad = " ".repeat(num.len());
+
+        if self.text.is_empty() {
+            return write!(f, "{}--> line {}", pad, self.line);
+        }
+
+        writeln!(f, "{}--> line {}, column {}", pad, self.line, self.column)?;
+        writeln!(f, "{} |", pad)?;
+        writeln!(f, "{} | {}", num, self.text)?;
+        write!(f,
+               "{} | {}{}",
+               pad,
+               " ".repeat(self.column - 1),
+               "^".repeat(self.token.chars().count().max(1)))?;
+
+        if let Some(cause) = std::error::Error::source(&self.error) {
+            write!(f, " {}", cause)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
 }
 
+// a problem recovered from when not parsing strictly
+pub type Warning = Diagnostic;
+
 //
 
 pub struct ForBdf<'a, T: ?Sized>(&'a T);
@@ -93,7 +290,7 @@ pub trait BdfValue {
         Error::ParseError(Self::desired())
     }
 
-    fn for_bdf(&self) -> ForBdf<Self> {
+    fn for_bdf(&self) -> ForBdf<'_, Self> {
         ForBdf(self)
     }
 }
@@ -103,7 +300,7 @@ pub trait BdfBlock {
         Ok(())
     }
 
-    fn for_bdf(&self) -> Result<ForBdf<Self>, Error> {
+    fn for_bdf(&self) -> Result<ForBdf<'_, Self>, Error> {
         self.validate()?;
         Ok(ForBdf(self))
     }
@@ -111,7 +308,11 @@ pub trait BdfBlock {
 
 //
 
-fn split_to_parts(s: &str, n: usize) -> Result<SplitWhitespace, usize> {
+fn keyword_line(id: &str, value: impl fmt::Display) -> (Slot, String) {
+    (Slot::Keyword(String::from(id)), format!("{} {}", id, value))
+}
+
+fn split_to_parts(s: &str, n: usize) -> Result<SplitWhitespace<'_>, usize> {
     let parts = s.split_whitespace();
     let parts_ct = parts.clone().count();
     if parts_ct == n {
@@ -123,14 +324,14 @@ fn split_to_parts(s: &str, n: usize) -> Result<SplitWhitespace, usize> {
 
 //
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub struct XYPair {
-    pub x: u32,
-    pub y: u32,
+    pub x: i32,
+    pub y: i32,
 }
 
 impl XYPair {
-    pub fn new(x: u32, y: u32) -> Self {
+    pub fn new(x: i32, y: i32) -> Self {
         Self {
             x,
             y,
@@ -204,7 +405,69 @@ impl<'a> fmt::Display for ForBdf<'a, MetricsSet> {
 
 //
 
-#[derive(Copy, Clone, Debug)]
+// ENCODING n, ENCODING -1 n or ENCODING -1
+//   the numbers are in whatever charset the font's CHARSET_REGISTRY names
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
+pub enum Encoding {
+    Standard(u32),
+    NonStandard(u32),
+    Unencoded,
+}
+
+impl Encoding {
+    pub fn code(&self) -> Option<u32> {
+        match *self {
+            Encoding::Standard(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    // only meaningful for fonts registered as ISO10646
+    pub fn to_char(&self, xlfd: &Xlfd) -> Option<char> {
+        if !xlfd.is_unicode() {
+            return None;
+        }
+
+        self.code().and_then(char::from_u32)
+    }
+}
+
+impl BdfValue for Encoding {
+    fn desired() -> &'static str {
+        "integer or -1 integer"
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+
+        let parse_u32 = |s: &str| s.parse::<u32>().map_err(|_| Self::parse_error());
+
+        match parts[..] {
+            ["-1"] => Ok(Encoding::Unencoded),
+            ["-1", n] => Ok(Encoding::NonStandard(parse_u32(n)?)),
+            [n] => Ok(Encoding::Standard(parse_u32(n)?)),
+            _ => Err(Self::parse_error()),
+        }
+    }
+}
+
+impl<'a> fmt::Display for ForBdf<'a, Encoding> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            Encoding::Standard(n) => write!(f, "{}", n),
+            Encoding::NonStandard(n) => write!(f, "-1 {}", n),
+            Encoding::Unencoded => write!(f, "-1"),
+        }
+    }
+}
+
+//
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub struct BoundingBox {
     pub width: u32,
     pub height: u32,
@@ -262,7 +525,7 @@ impl<'a> fmt::Display for ForBdf<'a, BoundingBox> {
 
 //
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub struct FontSize {
     pub point_size: u32,
     pub x_dpi: u32,
@@ -315,7 +578,7 @@ impl<'a> fmt::Display for ForBdf<'a, FontSize> {
 
 //
 
-#[derive(Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug)]
 pub enum PropertyValue {
     Str(String),
     Int(i32),
@@ -331,9 +594,9 @@ impl FromStr for PropertyValue {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.starts_with('"') {
-            let string = match (&s[1..]).rfind('"') {
-                Some(n) => s[1..n].replace("\"\"", "\""),
+        if let Some(s) = s.strip_prefix('"') {
+            let string = match s.rfind('"') {
+                Some(n) => s[..n].replace("\"\"", "\""),
                 None    => return Err(Self::parse_error()),
             };
             Ok(PropertyValue::Str(string))
@@ -382,15 +645,14 @@ impl FromStr for BitmapRow {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() % 2 != 0 {
+        if !s.is_ascii() || s.len() % 2 != 0 {
             return Err(Self::parse_error());
         }
 
-        let mut buf: Vec<u8> = Vec::new();
-        buf.reserve(s.len() / 2);
+        let mut buf: Vec<u8> = Vec::with_capacity(s.len() / 2);
 
         for i in 0..(s.len() / 2) {
-            buf.push(u8::from_str_radix(&s[i..=(i+1)], 16).or_else(|_| Err(Self::parse_error()))?);
+            buf.push(u8::from_str_radix(&s[(i*2)..(i*2+2)], 16).map_err(|_| Self::parse_error())?);
         }
 
         Ok(Self(BitVec::from_bytes(&buf)))
@@ -446,22 +708,54 @@ impl Bitmap {
     }
 
     pub fn set(&mut self, x: usize, y: usize, to: bool) {
-        if x >= self.width || y >= self.height {
-            return;
-        } else {
+        if x < self.width && y < self.height {
             self.data[y].set(x, to);
         }
     }
+
+    // a row packed into just enough bytes for the width, leftmost pixel in the top bit,
+    //   whatever padding the row was read with
+    pub(crate) fn row_bytes(&self, y: usize) -> Vec<u8> {
+        let mut bytes = vec![0; self.width.div_ceil(8)];
+        for x in 0..self.width {
+            if self.get(x, y) == Some(true) {
+                bytes[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        bytes
+    }
+}
+
+// rows read from a file are padded to whole bytes, only the pixels within the width count
+impl PartialEq for Bitmap {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height &&
+            self.data.iter().zip(&other.data).all(|(a, b)| a.iter().take(self.width).eq(b.iter().take(self.width)))
+    }
+}
+
+impl Eq for Bitmap {}
+
+impl Hash for Bitmap {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.height.hash(state);
+        for row in &self.data {
+            for on in row.iter().take(self.width) {
+                on.hash(state);
+            }
+        }
+    }
 }
 
 impl BdfBlock for Bitmap {}
 
 impl<'a> fmt::Display for ForBdf<'a, Bitmap> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}\n", ids::BITMAP)?;
+        writeln!(f, "{}", ids::BITMAP)?;
 
         for row in self.0.rows() {
-            write!(f, "{}\n", row.for_bdf())?;
+            writeln!(f, "{}", row.for_bdf())?;
         }
 
         Ok(())
@@ -473,7 +767,7 @@ impl<'a> fmt::Display for ForBdf<'a, Bitmap> {
 #[derive(Debug)]
 pub struct Glyph {
     pub name: String,
-    pub codepoint: char,
+    pub encoding: Encoding,
     pub bounding_box: BoundingBox,
     pub bitmap: Bitmap,
 
@@ -484,25 +778,88 @@ pub struct Glyph {
     pub device_width_alt: Option<XYPair>,
 
     pub vector: Option<XYPair>,
+
+    pub source: Option<SourceMap>,
+}
+
+impl Glyph {
+    // a glyph filling a whole cell of a fixed height font, with `descent` rows below the baseline
+    pub(crate) fn from_cell(name: String, encoding: Encoding, bitmap: Bitmap, descent: i32) -> Self {
+        let (width, height) = (bitmap.width() as u32, bitmap.height() as u32);
+        let decipoints = cell_decipoints(height);
+        let swidth = (f64::from(width) * 720_000.0 / (f64::from(decipoints.max(1)) * f64::from(CELL_DPI))).round();
+
+        Self {
+            name,
+            encoding,
+            bounding_box: BoundingBox {
+                width,
+                height,
+                x_offset: 0,
+                y_offset: -descent,
+            },
+            bitmap,
+            metrics: MetricsSet::Normal,
+
+            scalable_width: Some(XYPair::new(swidth as i32, 0)),
+            device_width: Some(XYPair::new(width as i32, 0)),
+            scalable_width_alt: None,
+            device_width_alt: None,
+            vector: None,
+
+            source: None,
+        }
+    }
+
+    fn bdf_lines(&self) -> Vec<(Slot, String)> {
+        let mut lines = Vec::with_capacity(self.bitmap.height() + 8);
+
+        lines.push(keyword_line(ids::STARTCHAR, &self.name));
+        lines.push(keyword_line(ids::ENCODING, self.encoding.for_bdf()));
+        if self.metrics != MetricsSet::Normal {
+            lines.push(keyword_line(ids::METRICSSET, self.metrics.for_bdf()));
+        }
+        if let &Some(pair) = &self.scalable_width {
+            lines.push(keyword_line(ids::SWIDTH, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.device_width {
+            lines.push(keyword_line(ids::DWIDTH, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.scalable_width_alt {
+            lines.push(keyword_line(ids::SWIDTH1, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.device_width_alt {
+            lines.push(keyword_line(ids::DWIDTH1, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.vector {
+            lines.push(keyword_line(ids::VVECTOR, pair.for_bdf()));
+        }
+        lines.push(keyword_line(ids::BBX, self.bounding_box.for_bdf()));
+        lines.push((Slot::Keyword(String::from(ids::BITMAP)), String::from(ids::BITMAP)));
+        for (i, row) in self.bitmap.rows().iter().enumerate() {
+            lines.push((Slot::Row(i), row.for_bdf().to_string()));
+        }
+        lines.push((Slot::Keyword(String::from(ids::ENDCHAR)), String::from(ids::ENDCHAR)));
+
+        lines
+    }
 }
 
 impl BdfBlock for Glyph {
     fn validate(&self) -> Result<(), Error> {
         self.bitmap.validate()?;
 
-        let codepoint = self.codepoint;
-
         match self.metrics {
             MetricsSet::Normal => {
                 if !(self.scalable_width_alt.is_none() &&
                      self.device_width_alt.is_none()) {
-                    return Err(Error::GlyphValidation(codepoint, "glyph with normal metrics cannot have alternate widths"));
+                    return Err(Error::GlyphValidation(self.name.clone(), "glyph with normal metrics cannot have alternate widths"));
                 }
             }
             _ => {
                 if !(self.scalable_width_alt.is_some() &&
                      self.device_width_alt.is_some()) {
-                    return Err(Error::GlyphValidation(codepoint, "glyph with alternate metrics must have alternate widths"));
+                    return Err(Error::GlyphValidation(self.name.clone(), "glyph with alternate metrics must have alternate widths"));
                 }
             }
         }
@@ -513,37 +870,17 @@ impl BdfBlock for Glyph {
 
 impl<'a> fmt::Display for ForBdf<'a, Glyph> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let glyph = &self.0;
-
-        write!(f, "{} {}\n", ids::STARTCHAR, glyph.name)?;
-        write!(f, "{} {}\n", ids::ENCODING, glyph.codepoint as u32)?;
-        write!(f, "{} {}\n", ids::BBX, glyph.bounding_box.for_bdf())?;
-        if glyph.metrics != MetricsSet::Normal {
-            write!(f, "{} {}\n", ids::METRICSSET, glyph.metrics.for_bdf())?;
-        }
-        if let &Some(pair) = &glyph.scalable_width {
-            write!(f, "{} {}\n", ids::SWIDTH, pair.for_bdf())?;
+        for (_, line) in self.0.bdf_lines() {
+            writeln!(f, "{}", line)?;
         }
-        if let &Some(pair) = &glyph.device_width {
-            write!(f, "{} {}\n", ids::DWIDTH, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &glyph.scalable_width_alt {
-            write!(f, "{} {}\n", ids::SWIDTH1, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &glyph.device_width_alt {
-            write!(f, "{} {}\n", ids::DWIDTH1, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &glyph.vector {
-            write!(f, "{} {}\n", ids::VVECTOR, pair.for_bdf())?;
-        }
-        write!(f, "{}", glyph.bitmap.for_bdf().unwrap())?;
-        write!(f, "{}\n", ids::ENDCHAR)
+
+        Ok(())
     }
 }
 
 //
 
-#[derive(Clone, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug)]
 pub struct Property {
     pub name: String,
     pub value: PropertyValue,
@@ -562,188 +899,332 @@ impl BdfBlock for Property {}
 
 impl<'a> fmt::Display for ForBdf<'a, Property> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {}\n", self.0.name, self.0.value.for_bdf())
+        writeln!(f, "{} {}", self.0.name, self.0.value.for_bdf())
     }
 }
 
 //
 
 #[derive(Debug)]
-pub struct Xlfd {
-    pub foundry: Option<String>,
-    pub family_name: Option<String>,
-    pub weight_name: Option<String>,
-    pub slant: Option<String>,
-    pub setwidth_name: Option<String>,
-    pub add_style_name: Option<String>,
-    pub pixel_size: Option<i32>,
-    pub point_size: Option<i32>,
-    pub resolution_x: Option<i32>,
-    pub resolution_y: Option<i32>,
-    pub spacing: Option<String>,
-    pub average_width: Option<i32>,
-    pub charset_registry: Option<String>,
-    pub charset_encoding: Option<String>,
+pub struct Font {
+    pub bdf_version: String,
+    pub name: String,
+    pub size: FontSize,
+    pub bounding_box: BoundingBox,
+    pub metrics: MetricsSet,
+
+    pub comments: Vec<String>,
+    pub properties: Vec<Property>,
+    pub glyphs: Vec<Glyph>,
+
+    pub content_version: Option<i32>,
+    pub scalable_width: Option<XYPair>,
+    pub device_width: Option<XYPair>,
+    pub scalable_width_alt: Option<XYPair>,
+    pub device_width_alt: Option<XYPair>,
+    pub vector: Option<XYPair>,
+
+    pub xlfd: Xlfd,
+
+    pub source: Option<SourceMap>,
 }
 
-impl Xlfd {
-    pub fn empty() -> Self {
-        Self {
-            foundry: None,
-            family_name: None,
-            weight_name: None,
-            slant: None,
-            setwidth_name: None,
-            add_style_name: None,
-            pixel_size: None,
-            point_size: None,
-            resolution_x: None,
-            resolution_y: None,
-            spacing: None,
-            average_width: None,
-            charset_registry: None,
-            charset_encoding: None,
-        }
+// the name binary formats give a glyph known only by its code point
+pub(crate) fn unicode_glyph_name(code: u32) -> String {
+    if code <= 0xffff {
+        format!("uni{:04X}", code)
+    } else {
+        format!("u{:X}", code)
     }
 }
 
-impl fmt::Display for Xlfd {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "-")?;
-        if let Some(val) = &self.foundry {
-            write!(f, "{}", val)?;
-        }
+// formats made of bare cells carry no size, they are taken to be at 75 dpi
+const CELL_DPI: i32 = 75;
 
-        write!(f, "-")?;
-        if let Some(val) = &self.family_name {
-            write!(f, "{}", val)?;
-        }
+fn cell_decipoints(height: u32) -> i32 {
+    (f64::from(height) * 722.7 / f64::from(CELL_DPI)).round() as i32
+}
 
-        write!(f, "-")?;
-        if let Some(val) = &self.weight_name {
-            write!(f, "{}", val)?;
-        }
+impl Font {
+    // a font around glyphs made by Glyph::from_cell, all of the same height
+    pub(crate) fn from_cells(glyphs: Vec<Glyph>, height: u32, descent: i32, unicode: bool) -> Self {
+        let widths: Vec<u32> = glyphs.iter().map(|g| g.bounding_box.width).collect();
+        let max_width = widths.iter().cloned().max().unwrap_or(0);
+        let average = match widths.len() {
+            0 => 0,
+            n => (widths.iter().sum::<u32>() * 10) as usize / n,
+        };
+        let decipoints = cell_decipoints(height);
+
+        let mut xlfd = Xlfd::empty();
+        xlfd.weight_name = Some(String::from("Medium"));
+        xlfd.slant = Some(String::from("R"));
+        xlfd.setwidth_name = Some(String::from("Normal"));
+        xlfd.pixel_size = Some(height as i32);
+        xlfd.point_size = Some(decipoints);
+        xlfd.resolution_x = Some(CELL_DPI);
+        xlfd.resolution_y = Some(CELL_DPI);
+        xlfd.spacing = Some(String::from(if widths.iter().all(|&w| w == max_width) { "C" } else { "P" }));
+        xlfd.average_width = Some(average as i32);
+        if unicode {
+            xlfd.charset_registry = Some(String::from("ISO10646"));
+            xlfd.charset_encoding = Some(String::from("1"));
+        }
+
+        let properties = vec![
+            Property::new(ids::FONT_ASCENT, &PropertyValue::Int(height as i32 - descent)),
+            Property::new(ids::FONT_DESCENT, &PropertyValue::Int(descent)),
+        ];
 
-        write!(f, "-")?;
-        if let Some(val) = &self.slant {
-            write!(f, "{}", val)?;
-        }
+        Self {
+            bdf_version: String::from("2.1"),
+            name: xlfd.to_string(),
+            size: FontSize {
+                point_size: ((decipoints + 5) / 10) as u32,
+                x_dpi: CELL_DPI as u32,
+                y_dpi: CELL_DPI as u32,
+            },
+            bounding_box: BoundingBox {
+                width: max_width,
+                height,
+                x_offset: 0,
+                y_offset: -descent,
+            },
+            metrics: MetricsSet::Normal,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.setwidth_name {
-            write!(f, "{}", val)?;
-        }
+            comments: Vec::new(),
+            properties,
+            glyphs,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.add_style_name {
-            write!(f, "{}", val)?;
-        }
+            content_version: None,
+            scalable_width: None,
+            device_width: None,
+            scalable_width_alt: None,
+            device_width_alt: None,
+            vector: None,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.pixel_size {
-            write!(f, "{}", val)?;
-        }
+            xlfd,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.point_size {
-            write!(f, "{}", val)?;
+            source: None,
         }
+    }
 
-        write!(f, "-")?;
-        if let Some(val) = &self.resolution_x {
-            write!(f, "{}", val)?;
-        }
+    pub fn from_header(header: FontHeader, glyphs: Vec<Glyph>) -> Self {
+        Self {
+            bdf_version: header.bdf_version,
+            name: header.name,
+            size: header.size,
+            bounding_box: header.bounding_box,
+            metrics: header.metrics,
+
+            comments: header.comments,
+            properties: header.properties,
+            glyphs,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.resolution_y {
-            write!(f, "{}", val)?;
-        }
+            content_version: header.content_version,
+            scalable_width: header.scalable_width,
+            device_width: header.device_width,
+            scalable_width_alt: header.scalable_width_alt,
+            device_width_alt: header.device_width_alt,
+            vector: header.vector,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.spacing {
-            write!(f, "{}", val)?;
-        }
+            xlfd: header.xlfd,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.average_width {
-            write!(f, "{}", val)?;
+            source: header.source,
         }
+    }
 
-        write!(f, "-")?;
-        if let Some(val) = &self.charset_registry {
-            write!(f, "{}", val)?;
-        }
+    pub fn header(&self) -> FontHeader {
+        FontHeader {
+            bdf_version: self.bdf_version.clone(),
+            name: self.name.clone(),
+            size: self.size,
+            bounding_box: self.bounding_box,
+            metrics: self.metrics,
 
-        write!(f, "-")?;
-        if let Some(val) = &self.charset_encoding {
-            write!(f, "{}", val)?;
+            comments: self.comments.clone(),
+            properties: self.properties.clone(),
+
+            content_version: self.content_version,
+            scalable_width: self.scalable_width,
+            device_width: self.device_width,
+            scalable_width_alt: self.scalable_width_alt,
+            device_width_alt: self.device_width_alt,
+            vector: self.vector,
+
+            xlfd: self.xlfd.clone(),
+
+            source: self.source.clone(),
         }
+    }
 
-        Ok(())
+    // whether FONT is an xlfd name that agrees with the xlfd properties
+    pub fn check_name(&self) -> Result<(), Error> {
+        self.xlfd.check_name(&self.name)
     }
-}
 
-impl BdfBlock for Xlfd {
-    fn validate(&self) -> Result<(), Error> {
-        Ok(())
+    pub(crate) fn int_property(&self, name: &str) -> Option<i32> {
+        self.properties.iter().find(|p| p.name == name).and_then(|p| match p.value {
+            PropertyValue::Int(v) => Some(v),
+            _ => None,
+        })
     }
-}
 
-impl<'a> fmt::Display for ForBdf<'a, Xlfd> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let xlfd = &self.0;
+    pub(crate) fn str_property(&self, name: &str) -> Option<&str> {
+        self.properties.iter().find(|p| p.name == name).and_then(|p| match &p.value {
+            PropertyValue::Str(v) => Some(v.as_str()),
+            _ => None,
+        })
+    }
 
-        if let Some(val) = &xlfd.foundry {
-            write!(f, "{} \"{}\"\n", ids::xlfd::FOUNDRY, val)?;
-        }
-        if let Some(val) = &xlfd.family_name {
-            write!(f, "{} \"{}\"\n", ids::xlfd::FAMILY_NAME, val)?;
-        }
-        if let Some(val) = &xlfd.weight_name {
-            write!(f, "{} \"{}\"\n", ids::xlfd::WEIGHT_NAME, val)?;
-        }
-        if let Some(val) = &xlfd.slant {
-            write!(f, "{} \"{}\"\n", ids::xlfd::SLANT, val)?;
-        }
-        if let Some(val) = &xlfd.setwidth_name {
-            write!(f, "{} \"{}\"\n", ids::xlfd::SETWIDTH_NAME, val)?;
-        }
-        if let Some(val) = &xlfd.add_style_name {
-            write!(f, "{} \"{}\"\n", ids::xlfd::ADD_STYLE_NAME, val)?;
-        }
-        if let Some(val) = &xlfd.pixel_size {
-            write!(f, "{} {}\n", ids::xlfd::PIXEL_SIZE, val)?;
+    // FONT_ASCENT and FONT_DESCENT, or what the bounding box reaches above and below the baseline
+    pub(crate) fn ascent_descent(&self) -> (i32, i32) {
+        let bbx = &self.bounding_box;
+        let ascent = self.int_property(ids::FONT_ASCENT).unwrap_or(bbx.height as i32 + bbx.y_offset);
+        let descent = self.int_property(ids::FONT_DESCENT).unwrap_or(-bbx.y_offset);
+
+        (ascent, descent)
+    }
+
+    // the charset of a font not encoded in unicode
+    pub(crate) fn charset(&self) -> Result<Charset, Error> {
+        Charset::from_xlfd(&self.xlfd).ok_or_else(|| {
+            let registry = self.xlfd.charset_registry.clone().unwrap_or_default();
+            let encoding = self.xlfd.charset_encoding.clone().unwrap_or_default();
+            Error::UnsupportedCharset(format!("{}-{}", registry, encoding))
+        })
+    }
+
+    // the character of every glyph, through the charset for fonts not encoded in unicode
+    pub(crate) fn unicode_chars(&self) -> Result<Vec<Option<char>>, Error> {
+        let charset = if self.xlfd.is_unicode() { None } else { Some(self.charset()?) };
+
+        Ok(self.glyphs.iter().map(|g| {
+            let code = g.encoding.code()?;
+            match &charset {
+                Some(charset) => charset.to_unicode(code),
+                None => char::from_u32(code),
+            }
+        }).collect())
+    }
+
+    // the character of every glyph for looking up text,
+    //   a font whose charset is unknown or missing is taken to be encoded in unicode
+    pub(crate) fn text_chars(&self) -> Vec<Option<char>> {
+        self.unicode_chars().unwrap_or_else(|_| {
+            self.glyphs.iter().map(|g| g.encoding.code().and_then(char::from_u32)).collect()
+        })
+    }
+
+    pub fn glyph_char(&self, glyph: &Glyph) -> Option<char> {
+        glyph.encoding.to_char(&self.xlfd)
+    }
+
+    pub fn glyph_for(&self, c: char) -> Option<&Glyph> {
+        if !self.xlfd.is_unicode() {
+            return None;
         }
-        if let Some(val) = &xlfd.point_size {
-            write!(f, "{} {}\n", ids::xlfd::POINT_SIZE, val)?;
+
+        self.glyphs.iter().find(|g| g.encoding == Encoding::Standard(c as u32))
+    }
+
+    // re-encodes every glyph into ISO10646-1,
+    //   glyphs without a mapping keep their old code as a non-standard encoding and are returned
+    pub fn convert_to_unicode(&mut self) -> Result<Vec<Unmapped>, Error> {
+        if self.xlfd.is_unicode() {
+            return Ok(Vec::new());
         }
-        if let Some(val) = &xlfd.resolution_x {
-            write!(f, "{} {}\n", ids::xlfd::RESOLUTION_X, val)?;
+
+        let charset = self.charset()?;
+
+        let mut unmapped = Vec::new();
+
+        for glyph in &mut self.glyphs {
+            let code = match glyph.encoding {
+                Encoding::Standard(code) => code,
+                _ => continue,
+            };
+
+            glyph.encoding = match charset.to_unicode(code) {
+                Some(c) => Encoding::Standard(c as u32),
+                None => {
+                    unmapped.push(Unmapped { name: glyph.name.clone(), code });
+                    Encoding::NonStandard(code)
+                }
+            };
         }
-        if let Some(val) = &xlfd.resolution_y {
-            write!(f, "{} {}\n", ids::xlfd::RESOLUTION_Y, val)?;
+
+        for prop in &mut self.properties {
+            if prop.name == ids::DEFAULT_CHAR {
+                if let PropertyValue::Int(code) = prop.value {
+                    if let Some(c) = charset.to_unicode(code as u32) {
+                        prop.value = PropertyValue::Int(c as i32);
+                    }
+                }
+            }
         }
-        if let Some(val) = &xlfd.spacing {
-            write!(f, "{} \"{}\"\n", ids::xlfd::SPACING, val)?;
+
+        let old_suffix = format!("-{}-{}",
+                                 self.xlfd.charset_registry.as_deref().unwrap_or(""),
+                                 self.xlfd.charset_encoding.as_deref().unwrap_or(""));
+        if self.name.to_ascii_uppercase().ends_with(&old_suffix.to_ascii_uppercase()) {
+            let at = self.name.len() - old_suffix.len();
+            let lower = self.name[at..] == self.name[at..].to_ascii_lowercase();
+
+            self.name.truncate(at);
+            self.name.push_str(if lower { "-iso10646-1" } else { "-ISO10646-1" });
         }
-        if let Some(val) = &xlfd.average_width {
-            write!(f, "{} {}\n", ids::xlfd::AVERAGE_WIDTH, val)?;
+
+        self.xlfd.charset_registry = Some(String::from("ISO10646"));
+        self.xlfd.charset_encoding = Some(String::from("1"));
+
+        Ok(unmapped)
+    }
+}
+
+impl BdfBlock for Font {
+    fn validate(&self) -> Result<(), Error> {
+        for g in &self.glyphs {
+            g.validate()?;
         }
-        if let Some(val) = &xlfd.charset_registry {
-            write!(f, "{} \"{}\"\n", ids::xlfd::CHARSET_REGISTRY, val)?;
+
+        for p in &self.properties {
+            p.validate()?;
         }
-        if let Some(val) = &xlfd.charset_encoding {
-            write!(f, "{} \"{}\"\n", ids::xlfd::CHARSET_ENCODING, val)?;
+
+        match self.metrics {
+            MetricsSet::Normal => {
+                if !(self.scalable_width_alt.is_none() &&
+                     self.device_width_alt.is_none()) {
+                    return Err(Error::FontValidation("font with normal metrics cannot have alternate widths"));
+                }
+            }
+            _ => {
+                if !(self.scalable_width_alt.is_some() &&
+                     self.device_width_alt.is_some()) {
+                    return Err(Error::FontValidation("font with alternate metrics must have alternate widths"));
+                }
+            }
         }
 
         Ok(())
     }
 }
 
+impl<'a> fmt::Display for ForBdf<'a, Font> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        BdfWriter::new(FmtWriter(f)).write_font(self.0)
+                                    .map(|_| ())
+                                    .map_err(|_| fmt::Error)
+    }
+}
+
 //
 
-#[derive(Debug)]
-pub struct Font {
+// everything in a font before its glyphs
+
+#[derive(Clone, Debug)]
+pub struct FontHeader {
     pub bdf_version: String,
     pub name: String,
     pub size: FontSize,
@@ -752,7 +1233,6 @@ pub struct Font {
 
     pub comments: Vec<String>,
     pub properties: Vec<Property>,
-    pub glyphs: Vec<Glyph>,
 
     pub content_version: Option<i32>,
     pub scalable_width: Option<XYPair>,
@@ -762,20 +1242,74 @@ pub struct Font {
     pub vector: Option<XYPair>,
 
     pub xlfd: Xlfd,
+
+    pub source: Option<SourceMap>,
 }
 
-impl BdfBlock for Font {
-    fn validate(&self) -> Result<(), Error> {
-        for g in &self.glyphs {
-            g.validate()?;
+impl FontHeader {
+    pub fn property_ct(&self) -> usize {
+        self.xlfd.property_ct() + self.properties.len()
+    }
+
+    fn bdf_lines(&self, chars: usize) -> Vec<(Slot, String)> {
+        let mut lines = Vec::new();
+
+        lines.push(keyword_line(ids::STARTFONT, &self.bdf_version));
+        lines.push(keyword_line(ids::FONT, &self.name));
+        for (i, comment) in self.comments.iter().enumerate() {
+            let line = if comment.is_empty() {
+                String::from(ids::COMMENT)
+            } else {
+                format!("{} {}", ids::COMMENT, comment)
+            };
+            lines.push((Slot::Comment(i), line));
+        }
+        if let Some(cv) = &self.content_version {
+            lines.push(keyword_line(ids::CONTENTVERSION, cv));
+        }
+        lines.push(keyword_line(ids::SIZE, self.size.for_bdf()));
+        lines.push(keyword_line(ids::FONTBOUNDINGBOX, self.bounding_box.for_bdf()));
+        if self.metrics != MetricsSet::Normal {
+            lines.push(keyword_line(ids::METRICSSET, self.metrics.for_bdf()));
+        }
+        if let &Some(pair) = &self.scalable_width {
+            lines.push(keyword_line(ids::SWIDTH, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.device_width {
+            lines.push(keyword_line(ids::DWIDTH, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.scalable_width_alt {
+            lines.push(keyword_line(ids::SWIDTH1, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.device_width_alt {
+            lines.push(keyword_line(ids::DWIDTH1, pair.for_bdf()));
+        }
+        if let &Some(pair) = &self.vector {
+            lines.push(keyword_line(ids::VVECTOR, pair.for_bdf()));
+        }
+
+        let property_ct = self.property_ct();
+        if property_ct > 0 {
+            lines.push(keyword_line(ids::STARTPROPERTIES, property_ct));
+            for property in self.xlfd.properties().iter().chain(&self.properties) {
+                lines.push((Slot::Property(property.name.clone()),
+                            format!("{} {}", property.name, property.value.for_bdf())));
+            }
+            lines.push((Slot::Keyword(String::from(ids::ENDPROPERTIES)), String::from(ids::ENDPROPERTIES)));
         }
 
+        lines.push(keyword_line(ids::CHARS, chars));
+
+        lines
+    }
+}
+
+impl BdfBlock for FontHeader {
+    fn validate(&self) -> Result<(), Error> {
         for p in &self.properties {
             p.validate()?;
         }
 
-        self.xlfd.validate()?;
-
         match self.metrics {
             MetricsSet::Normal => {
                 if !(self.scalable_width_alt.is_none() &&
@@ -795,56 +1329,6 @@ impl BdfBlock for Font {
     }
 }
 
-impl<'a> fmt::Display for ForBdf<'a, Font> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let font = &self.0;
-
-        write!(f, "{} {}\n", ids::STARTFONT, font.bdf_version)?;
-        write!(f, "{} {}\n", ids::FONT, font.name)?;
-        for comment in &font.comments {
-            write!(f, "{} {}\n", ids::COMMENT, comment)?;
-        }
-        if let Some(cv) = &font.content_version {
-            write!(f, "{} {}\n", ids::CONTENTVERSION, cv)?;
-        }
-        write!(f, "{} {}\n", ids::SIZE, font.size.for_bdf())?;
-        write!(f, "{} {}\n", ids::FONTBOUNDINGBOX, font.bounding_box.for_bdf())?;
-        if font.metrics != MetricsSet::Normal {
-            write!(f, "{} {}\n", ids::METRICSSET, font.metrics.for_bdf())?;
-        }
-        if let &Some(pair) = &font.scalable_width {
-            write!(f, "{} {}\n", ids::SWIDTH, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &font.device_width {
-            write!(f, "{} {}\n", ids::DWIDTH, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &font.scalable_width_alt {
-            write!(f, "{} {}\n", ids::SWIDTH1, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &font.device_width_alt {
-            write!(f, "{} {}\n", ids::DWIDTH1, pair.for_bdf())?;
-        }
-        if let &Some(pair) = &font.vector {
-            write!(f, "{} {}\n", ids::VVECTOR, pair.for_bdf())?;
-        }
-        if font.properties.len() > 0 {
-            write!(f, "{} {}\n", ids::STARTPROPERTIES, font.properties.len())?;
-            write!(f, "{}", font.xlfd.for_bdf().unwrap())?;
-            for property in &font.properties {
-                write!(f, "{}", property.for_bdf().unwrap())?;
-            }
-            write!(f, "{}\n", ids::ENDPROPERTIES)?;
-        }
-        if font.glyphs.len() > 0 {
-            write!(f, "{} {}\n", ids::CHARS, font.glyphs.len())?;
-            for glyph in &font.glyphs {
-                write!(f, "{}", glyph.for_bdf().unwrap())?;
-            }
-        }
-        write!(f, "{}\n", ids::ENDFONT)
-    }
-}
-
 //
 
 // TODO shell trait? not really necessary i think
@@ -869,7 +1353,7 @@ impl BitmapShell {
         Ok(())
     }
 
-    fn to_bitmap(self) -> Result<Bitmap, Error> {
+    fn into_bitmap(self) -> Result<Bitmap, Error> {
         self.validate()?;
 
         Ok(Bitmap {
@@ -881,9 +1365,9 @@ impl BitmapShell {
 }
 
 #[derive(Debug)]
-struct GlyphShell<'a> {
-    pub name: Option<&'a str>,
-    pub codepoint: Option<char>,
+struct GlyphShell {
+    pub name: Option<String>,
+    pub encoding: Option<Encoding>,
     pub bounding_box: Option<BoundingBox>,
     pub bitmap: BitmapShell,
 
@@ -896,11 +1380,11 @@ struct GlyphShell<'a> {
     pub vector: Option<XYPair>,
 }
 
-impl<'a> GlyphShell<'a> {
+impl GlyphShell {
     fn new() -> Self {
         Self {
             name: None,
-            codepoint: None,
+            encoding: None,
             bounding_box: None,
             bitmap: BitmapShell::new(),
             metrics: None,
@@ -915,29 +1399,28 @@ impl<'a> GlyphShell<'a> {
     fn validate(&self) -> Result<(), Error> {
         use Error::*;
 
-        if self.codepoint.is_none() {
-            return Err(GlyphValidation('\0', "codepoint not found"));
-        }
-
-        let codepoint = self.codepoint.unwrap();
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => return Err(GlyphValidation(String::new(), "name not found")),
+        };
 
-        if self.name.is_none() {
-            return Err(GlyphValidation(codepoint, "name not found"));
+        if self.encoding.is_none() {
+            return Err(GlyphValidation(name, "encoding not found"));
         } else if self.bounding_box.is_none() {
-            return Err(GlyphValidation(codepoint, "bounding box not found"));
+            return Err(GlyphValidation(name.clone(), "bounding box not found"));
         }
 
         match self.metrics {
             None | Some(MetricsSet::Normal) => {
                 if !(self.scalable_width_alt.is_none() &&
                      self.device_width_alt.is_none()) {
-                    return Err(GlyphValidation(codepoint, "glyph with normal metrics cannot have alternate widths"));
+                    return Err(GlyphValidation(name.clone(), "glyph with normal metrics cannot have alternate widths"));
                 }
             }
             Some(_) => {
                 if !(self.scalable_width_alt.is_some() &&
                      self.device_width_alt.is_some()) {
-                    return Err(GlyphValidation(codepoint, "glyph with alternate metrics must have alternate widths"));
+                    return Err(GlyphValidation(name.clone(), "glyph with alternate metrics must have alternate widths"));
                 }
             }
         }
@@ -947,15 +1430,15 @@ impl<'a> GlyphShell<'a> {
         Ok(())
     }
 
-    fn to_glyph(self) -> Result<Glyph, Error> {
+    fn into_glyph(self) -> Result<Glyph, Error> {
         self.validate()?;
 
-        let codepoint = self.codepoint.unwrap();
-        let bitmap = self.bitmap.to_bitmap()?;
+        let encoding = self.encoding.unwrap();
+        let bitmap = self.bitmap.into_bitmap()?;
 
         Ok(Glyph {
-            name: String::from(self.name.unwrap()),
-            codepoint,
+            name: self.name.unwrap(),
+            encoding,
             bounding_box: self.bounding_box.unwrap(),
             bitmap,
             metrics: self.metrics.unwrap_or(MetricsSet::Normal),
@@ -965,22 +1448,24 @@ impl<'a> GlyphShell<'a> {
             scalable_width_alt: self.scalable_width_alt,
             device_width_alt: self.device_width_alt,
             vector: self.vector,
+
+            source: None,
         })
     }
 }
 
 #[derive(Debug)]
-enum PropertyValueShell<'a> {
-    Str(&'a str),
+enum PropertyValueShell {
+    Str(String),
     Int(i32),
 }
 
-impl<'a> PropertyValueShell<'a> {
+impl PropertyValueShell {
     fn validate(&self) -> Result<(), Error> {
         Ok(())
     }
 
-    fn to_property_value(self) -> Result<PropertyValue, Error> {
+    fn into_property_value(self) -> Result<PropertyValue, Error> {
         self.validate()?;
         Ok(match self {
             Self::Str(s) => PropertyValue::Str(s.replace("\"\"", "\"")),
@@ -990,15 +1475,15 @@ impl<'a> PropertyValueShell<'a> {
 }
 
 #[derive(Debug)]
-struct PropertyShell<'a> {
-    pub name: &'a str,
-    pub value: PropertyValueShell<'a>
+struct PropertyShell {
+    pub name: String,
+    pub value: PropertyValueShell,
 }
 
-impl<'a> PropertyShell<'a> {
-    fn new(name: &'a str, value: PropertyValueShell<'a>) -> Self {
+impl PropertyShell {
+    fn new(name: &str, value: PropertyValueShell) -> Self {
         Self {
-            name,
+            name: String::from(name),
             value,
         }
     }
@@ -1007,34 +1492,34 @@ impl<'a> PropertyShell<'a> {
         self.value.validate()
     }
 
-    fn to_property(self) -> Result<Property, Error> {
+    fn into_property(self) -> Result<Property, Error> {
         self.validate()?;
         Ok(Property {
-            name: String::from(self.name),
-            value: self.value.to_property_value()?,
+            name: self.name,
+            value: self.value.into_property_value()?,
         })
     }
 }
 
 #[derive(Debug)]
-struct XlfdShell<'a> {
-    pub foundry: Option<&'a str>,
-    pub family_name: Option<&'a str>,
-    pub weight_name: Option<&'a str>,
-    pub slant: Option<&'a str>,
-    pub setwidth_name: Option<&'a str>,
-    pub add_style_name: Option<&'a str>,
+struct XlfdShell {
+    pub foundry: Option<String>,
+    pub family_name: Option<String>,
+    pub weight_name: Option<String>,
+    pub slant: Option<String>,
+    pub setwidth_name: Option<String>,
+    pub add_style_name: Option<String>,
     pub pixel_size: Option<i32>,
     pub point_size: Option<i32>,
     pub resolution_x: Option<i32>,
     pub resolution_y: Option<i32>,
-    pub spacing: Option<&'a str>,
+    pub spacing: Option<String>,
     pub average_width: Option<i32>,
-    pub charset_registry: Option<&'a str>,
-    pub charset_encoding: Option<&'a str>,
+    pub charset_registry: Option<String>,
+    pub charset_encoding: Option<String>,
 }
 
-impl<'a> XlfdShell<'a> {
+impl XlfdShell {
     fn new() -> Self {
         Self {
             foundry: None,
@@ -1058,38 +1543,36 @@ impl<'a> XlfdShell<'a> {
         Ok(())
     }
 
-    fn to_xlfd(self) -> Result<Xlfd, Error> {
+    fn into_xlfd(self) -> Result<Xlfd, Error> {
         self.validate()?;
         Ok(Xlfd {
-            foundry: self.foundry.map(String::from),
-            family_name: self.family_name.map(String::from),
-            weight_name: self.weight_name.map(String::from),
-            slant: self.slant.map(String::from),
-            setwidth_name: self.setwidth_name.map(String::from),
-            add_style_name: self.add_style_name.map(String::from),
+            foundry: self.foundry,
+            family_name: self.family_name,
+            weight_name: self.weight_name,
+            slant: self.slant,
+            setwidth_name: self.setwidth_name,
+            add_style_name: self.add_style_name,
             pixel_size: self.pixel_size,
             point_size: self.point_size,
             resolution_x: self.resolution_x,
             resolution_y: self.resolution_y,
-            spacing: self.spacing.map(String::from),
+            spacing: self.spacing,
             average_width: self.average_width,
-            charset_registry: self.charset_registry.map(String::from),
-            charset_encoding: self.charset_encoding.map(String::from),
+            charset_registry: self.charset_registry,
+            charset_encoding: self.charset_encoding,
         })
     }
 }
 
 #[derive(Debug)]
-struct FontShell<'a> {
-    pub bdf_version: Option<&'a str>,
-    pub name: Option<&'a str>,
+struct FontShell {
+    pub bdf_version: Option<String>,
+    pub name: Option<String>,
     pub size: Option<FontSize>,
     pub bounding_box: Option<BoundingBox>,
     pub metrics: Option<MetricsSet>,
 
-    pub comments: Vec<&'a str>,
-    pub properties: Vec<PropertyShell<'a>>,
-    pub glyphs: Vec<GlyphShell<'a>>,
+    pub properties: Vec<PropertyShell>,
 
     pub content_version: Option<i32>,
     pub scalable_width: Option<XYPair>,
@@ -1098,10 +1581,10 @@ struct FontShell<'a> {
     pub device_width_alt: Option<XYPair>,
     pub vector: Option<XYPair>,
 
-    pub xlfd: XlfdShell<'a>,
+    pub xlfd: XlfdShell,
 }
 
-impl<'a> FontShell<'a> {
+impl FontShell {
     fn new() -> Self {
         Self {
             bdf_version: None,
@@ -1109,9 +1592,7 @@ impl<'a> FontShell<'a> {
             size: None,
             bounding_box: None,
             metrics: None,
-            comments: Vec::new(),
             properties: Vec::new(),
-            glyphs: Vec::new(),
             content_version: None,
             scalable_width: None,
             device_width: None,
@@ -1150,10 +1631,6 @@ impl<'a> FontShell<'a> {
             }
         }
 
-        for g in &self.glyphs {
-            g.validate()?;
-        }
-
         for p in &self.properties {
             p.validate()?;
         }
@@ -1163,29 +1640,22 @@ impl<'a> FontShell<'a> {
         Ok(())
     }
 
-    fn to_font(self) -> Result<Font, Error> {
+    fn into_header(self, comments: Vec<String>) -> Result<FontHeader, Error> {
         self.validate()?;
 
-        let glyphs = self.glyphs.into_iter()
-                                .map(GlyphShell::to_glyph)
-                                .collect::<Result<Vec<_>, _>>()?;
         let properties = self.properties.into_iter()
-                                        .map(PropertyShell::to_property)
+                                        .map(PropertyShell::into_property)
                                         .collect::<Result<Vec<_>, _>>()?;
-        let comments = self.comments.into_iter()
-                                    .map(String::from)
-                                    .collect();
 
-        Ok(Font {
-            bdf_version: String::from(self.bdf_version.unwrap()),
-            name: String::from(self.name.unwrap()),
+        Ok(FontHeader {
+            bdf_version: self.bdf_version.unwrap(),
+            name: self.name.unwrap(),
             size: self.size.unwrap(),
             bounding_box: self.bounding_box.unwrap(),
             metrics: self.metrics.unwrap_or(MetricsSet::Normal),
 
             comments,
             properties,
-            glyphs,
 
             content_version: self.content_version,
             scalable_width: self.scalable_width,
@@ -1193,113 +1663,367 @@ impl<'a> FontShell<'a> {
             scalable_width_alt: self.scalable_width_alt,
             device_width_alt: self.device_width_alt,
             vector: self.vector,
-            xlfd: self.xlfd.to_xlfd().unwrap(),
+            xlfd: self.xlfd.into_xlfd().unwrap(),
+
+            source: None,
         })
     }
 }
 
 //
 
-pub fn parse_font(input: &str) -> Result<Font, (usize, Error)> {
-    use Error::*;
+fn invalid(line_num: usize, id: &str, err: Error) -> (usize, Error) {
+    (line_num, Error::InvalidValue(String::from(id), Box::new(err)))
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ParseOptions {
+    // fail on the first problem, otherwise recoverable problems become warnings
+    //   and parsing carries on with a best-effort font
+    pub strict: bool,
+    // keep a SourceMap of every block so that writing it back reproduces the input,
+    //   unknown keywords are kept rather than rejected
+    pub lossless: bool,
+}
 
-    #[derive(Eq, PartialEq, Copy, Clone)]
-    enum ParseState {
-        Empty,
-        InFont,
-        InProperties,
-        InChars,
-        InChar,
-        InBitmap,
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strict: true,
+            lossless: false,
+        }
     }
+}
 
-    let mut state = ParseState::Empty;
+enum ParseEvent {
+    Header(Box<FontHeader>),
+    Glyph(Glyph),
+}
 
-    let mut f_shell = FontShell::new();
-    let mut main_bbox: Option<BoundingBox> = None;
-    let mut curr_bbox: Option<BoundingBox> = None;
+// counts in the file only size the first allocation, so a bogus count cannot exhaust memory
+const MAX_RESERVE: usize = 65536;
+
+#[derive(Eq, PartialEq, Copy, Clone)]
+enum ParseState {
+    Empty,
+    InFont,
+    InProperties,
+    InChars,
+    InChar,
+    InBitmap,
+    Done,
+}
 
-    let mut bitmap_len: u32 = 0;
+struct Parser {
+    options: ParseOptions,
+    state: ParseState,
+
+    f_shell: FontShell,
+    g_shell: Option<GlyphShell>,
+    comments: Vec<String>,
+    header_done: bool,
+    chars_ct: usize,
+    glyphs_ct: usize,
+    props_ct: usize,
+    props_seen: usize,
+    warnings: Vec<Warning>,
+
+    main_bbox: Option<BoundingBox>,
+    curr_bbox: Option<BoundingBox>,
+
+    bitmap_len: u32,
+    // rows past the height of the bitmap, and the first of them
+    surplus_rows: usize,
+    surplus_line: usize,
+    surplus_text: String,
+    last_line: usize,
+    last_text: String,
+    trailing: bool,
+
+    newline: &'static str,
+    slot: Slot,
+    f_lines: Vec<SourceLine>,
+    g_lines: Vec<SourceLine>,
+}
+
+impl Parser {
+    fn new(options: ParseOptions) -> Self {
+        Self {
+            options,
+            state: ParseState::Empty,
+            f_shell: FontShell::new(),
+            g_shell: None,
+            comments: Vec::new(),
+            header_done: false,
+            chars_ct: 0,
+            glyphs_ct: 0,
+            props_ct: 0,
+            props_seen: 0,
+            warnings: Vec::new(),
+            main_bbox: None,
+            curr_bbox: None,
+            bitmap_len: 0,
+            surplus_rows: 0,
+            surplus_line: 0,
+            surplus_text: String::new(),
+            last_line: 0,
+            last_text: String::new(),
+            trailing: false,
+            newline: "\n",
+            slot: Slot::Verbatim,
+            f_lines: Vec::new(),
+            g_lines: Vec::new(),
+        }
+    }
+
+    fn parse_line(&mut self, line_num: usize, raw: &[u8]) -> Result<Option<ParseEvent>, Diagnostic> {
+        let long_line = String::from_utf8_lossy(raw);
+
+        if !self.options.lossless {
+            return self.parse_entry(line_num, &long_line).map_err(|e| self.diagnose(e));
+        }
+
+        if line_num == 1 && raw.ends_with(b"\r\n") {
+            self.newline = "\r\n";
+        }
+
+        self.slot = Slot::Verbatim;
+        let event = self.parse_entry(line_num, &long_line).map_err(|e| self.diagnose(e))?;
+
+        let line = SourceLine::new(std::mem::replace(&mut self.slot, Slot::Verbatim), raw);
+        match event {
+            Some(ParseEvent::Header(_)) => self.f_lines.push(line),
+            Some(ParseEvent::Glyph(_)) => self.g_lines.push(line),
+            None => {
+                if !self.header_done {
+                    self.f_lines.push(line);
+                } else if self.state == ParseState::Done {
+                    self.f_lines.append(&mut self.g_lines);
+                    self.f_lines.push(line);
+                } else {
+                    self.g_lines.push(line);
+                }
+            }
+        }
+
+        Ok(self.attach_source(event))
+    }
+
+    fn attach_source(&mut self, event: Option<ParseEvent>) -> Option<ParseEvent> {
+        match event {
+            Some(ParseEvent::Header(mut header)) => {
+                let mut source = SourceMap::new(self.newline, std::mem::take(&mut self.f_lines));
+                source.fill(&header.bdf_lines(self.chars_ct));
+                source.lines.push(SourceLine::new(Slot::Glyphs, &[]));
+
+                header.source = Some(source);
+                Some(ParseEvent::Header(header))
+            }
+            Some(ParseEvent::Glyph(mut glyph)) => {
+                let mut source = SourceMap::new(self.newline, std::mem::take(&mut self.g_lines));
+                source.fill(&glyph.bdf_lines());
+
+                glyph.source = Some(source);
+                Some(ParseEvent::Glyph(glyph))
+            }
+            None => None,
+        }
+    }
+
+    // whatever followed the last glyph
+    fn take_trailing(&mut self) -> Vec<SourceLine> {
+        self.f_lines.append(&mut self.g_lines);
+
+        let mut trailing = SourceMap::new(self.newline, std::mem::take(&mut self.f_lines));
+        trailing.fill(&[(Slot::Keyword(String::from(ids::ENDFONT)), String::from(ids::ENDFONT))]);
+        trailing.lines
+    }
+
+    fn recover(&mut self, line_num: usize, err: Error) -> Result<(), (usize, Error)> {
+        if self.options.strict {
+            Err((line_num, err))
+        } else {
+            self.warn(line_num, err);
+            Ok(())
+        }
+    }
+
+    // problems the strict parser has always let through, only reported when lenient
+    fn tolerate(&mut self, line_num: usize, err: Error) {
+        if !self.options.strict {
+            self.warn(line_num, err);
+        }
+    }
+
+    // rows past the height of the bitmap, all in one warning
+    fn check_surplus_rows(&mut self, height: usize) -> Result<(), (usize, Error)> {
+        if self.surplus_rows > 0 {
+            let err = Error::CountMismatch(ids::BITMAP, height, height + self.surplus_rows);
+            self.surplus_rows = 0;
+            self.recover(self.surplus_line, err)?;
+        }
+        Ok(())
+    }
+
+    fn warn(&mut self, line_num: usize, err: Error) {
+        let warning = Diagnostic::warning(line_num, self.line_text(line_num), err);
+        self.warnings.push(warning);
+    }
+
+    fn diagnose(&self, (line_num, err): (usize, Error)) -> Diagnostic {
+        Diagnostic::new(line_num, self.line_text(line_num), err)
+    }
+
+    // problems are found on the line just read, or on the last one once the input runs out,
+    //   bar surplus bitmap rows which are reported once the bitmap ends
+    fn line_text(&self, line_num: usize) -> &str {
+        if line_num == self.surplus_line {
+            &self.surplus_text
+        } else {
+            &self.last_text
+        }
+    }
+
+    fn unknown_entry(&mut self, line_num: usize, id: &str) -> Result<(), (usize, Error)> {
+        self.slot = Slot::Verbatim;
+
+        if self.options.lossless && self.options.strict {
+            Ok(())
+        } else {
+            self.recover(line_num, Error::UnexpectedEntry(String::from(id)))
+        }
+    }
+
+    fn check_chars_ct(&mut self, line_num: usize) {
+        if self.header_done && self.glyphs_ct != self.chars_ct {
+            self.tolerate(line_num, Error::CountMismatch(ids::CHARS, self.chars_ct, self.glyphs_ct));
+        }
+    }
+
+    fn parse_entry(&mut self, line_num: usize, long_line: &str) -> Result<Option<ParseEvent>, (usize, Error)> {
+        use Error::*;
 
-    let lines = input.trim().split('\n');
-    let lines_ct = lines.clone().count();
-    for (line_num, long_line) in lines.enumerate() {
         if long_line.chars().all(char::is_whitespace) {
-            continue;
+            return Ok(None);
         }
 
-        let line_num = line_num + 1;
+        self.last_line = line_num;
+        self.last_text.clear();
+        self.last_text.push_str(long_line);
+
         let line = long_line.trim();
 
         let (id, rest) = match line.find(char::is_whitespace) {
-            Some(n) => (&line[0..n], Some((&line[n..]).trim())),
+            Some(n) => (&line[0..n], Some(line[n..].trim())),
             None    => (line, None),
         };
 
-        if state == ParseState::InBitmap {
-            if bitmap_len == 0 {
-                state = ParseState::InChar;
-            } else {
-                match (id, rest) {
-                    (val, None) => {
-                        let row = val.parse().or_else(|e| Err((line_num, e)))?;
-
-                        let g_shell = f_shell.glyphs.last_mut().unwrap();
-                        g_shell.bitmap.data.push(row);
+        // anything past ENDFONT is reported once and otherwise ignored when lenient
+        if self.state == ParseState::Done {
+            if !self.trailing {
+                self.trailing = true;
+                self.recover(line_num, UnexpectedEntry(String::from(id)))?;
+            }
+            return Ok(None);
+        }
 
-                        bitmap_len -= 1;
-                        continue;
-                    }
-                    (_, Some(_)) => {
-                        return Err((line_num, BitmapRow::parse_error()));
+        if self.state == ParseState::InBitmap {
+            match (id, rest) {
+                (ids::ENDCHAR, _) | (ids::COMMENT, _) | (_, Some(_)) if self.bitmap_len == 0 => {
+                    let height = self.g_shell.as_ref().unwrap().bitmap.height;
+                    self.check_surplus_rows(height)?;
+                    self.state = ParseState::InChar;
+                }
+                (ids::ENDCHAR, _) => {
+                    let g_shell = self.g_shell.as_mut().unwrap();
+                    let found = g_shell.bitmap.data.len();
+                    let err = CountMismatch(ids::BITMAP, found + self.bitmap_len as usize, found);
+                    self.recover(line_num, err)?;
+
+                    let g_shell = self.g_shell.as_mut().unwrap();
+                    let blank = BitmapRow(BitVec::from_elem(g_shell.bitmap.width, false));
+                    g_shell.bitmap.data.resize(g_shell.bitmap.height, blank);
+
+                    self.bitmap_len = 0;
+                    self.state = ParseState::InChar;
+                }
+                (val, None) if self.bitmap_len > 0 => {
+                    let mut row: BitmapRow = val.parse().map_err(|e| (line_num, e))?;
+
+                    // rows with fewer bytes than the width are taken to be blank past their end
+                    let g_shell = self.g_shell.as_mut().unwrap();
+                    let short = g_shell.bitmap.width.saturating_sub(row.len());
+                    row.grow(short, false);
+                    self.slot = Slot::Row(g_shell.bitmap.data.len());
+                    g_shell.bitmap.data.push(row);
+
+                    self.bitmap_len -= 1;
+                    return Ok(None);
+                }
+                (_, None) => {
+                    if self.surplus_rows == 0 {
+                        self.surplus_line = line_num;
+                        self.surplus_text = String::from(long_line);
                     }
+                    self.surplus_rows += 1;
+                    return Ok(None);
+                }
+                (_, Some(_)) => {
+                    return Err((line_num, BitmapRow::parse_error()));
                 }
             }
         }
 
-        match (state, id, rest) {
-            (_, ids::COMMENT, Some(s)) => {
-                f_shell.comments.push(s);
-                continue;
+        match (self.state, id, rest) {
+            (_, ids::COMMENT, s) => {
+                // comments among the glyphs live in their source maps when lossless
+                if !(self.options.lossless && self.header_done) {
+                    self.slot = Slot::Comment(self.comments.len());
+                    self.comments.push(String::from(s.unwrap_or("")));
+                }
+                return Ok(None);
             },
 
-            (ParseState::InChars, id @ ids::ENDFONT, _) => {
-                if line_num != lines_ct {
-                    return Err((line_num, UnexpectedEntry(String::from(id))));
-                }
-                break;
+            (ParseState::InChars, ids::ENDFONT, _) => {
+                self.check_chars_ct(line_num);
+                self.slot = Slot::Keyword(String::from(id));
+                self.state = ParseState::Done;
+                return Ok(None);
             },
             (_, ids::ENDFONT, _) => return Err((line_num, UnexpectedEntry(String::from(id)))),
 
             (ParseState::InProperties, ids::ENDPROPERTIES, _) => {
-                state = ParseState::InFont;
-                continue;
+                if self.props_seen != self.props_ct {
+                    self.tolerate(line_num, CountMismatch(ids::STARTPROPERTIES, self.props_ct, self.props_seen));
+                }
+                self.slot = Slot::Keyword(String::from(id));
+                self.state = ParseState::InFont;
+                return Ok(None);
             },
             (_, ids::ENDPROPERTIES, _) => return Err((line_num, UnexpectedEntry(String::from(id)))),
 
             (ParseState::InChar, ids::ENDCHAR, _) => {
-                let g_shell = f_shell.glyphs.last_mut().unwrap();
-                match g_shell.validate() {
-                    Ok(()) => {},
-                    Err(err) => return Err((line_num, err)),
-                }
-                state = ParseState::InChars;
-                continue;
+                self.slot = Slot::Keyword(String::from(id));
+                let glyph = self.g_shell.take().unwrap().into_glyph().map_err(|e| (line_num, e))?;
+                self.state = ParseState::InChars;
+                self.glyphs_ct += 1;
+                return Ok(Some(ParseEvent::Glyph(glyph)));
             },
             (ParseState::InChar, ids::BITMAP, _) => {
-                let bbox = match (&main_bbox, &curr_bbox) {
+                let bbox = match (&self.main_bbox, &self.curr_bbox) {
                     (_, Some(bbox)) | (Some(bbox), None) => bbox,
                     (None, None) => return Err((line_num, MissingBoundingBox)),
                 };
 
-                let g_shell = f_shell.glyphs.last_mut().unwrap();
+                let g_shell = self.g_shell.as_mut().unwrap();
                 g_shell.bitmap.width = bbox.width as usize;
                 g_shell.bitmap.height = bbox.height as usize;
-                g_shell.bitmap.data.reserve(bbox.height as usize);
+                g_shell.bitmap.data.reserve((bbox.height as usize).min(MAX_RESERVE));
 
-                bitmap_len = bbox.height;
-                state = ParseState::InBitmap;
-                continue;
+                self.bitmap_len = bbox.height;
+                self.slot = Slot::Keyword(String::from(id));
+                self.state = ParseState::InBitmap;
+                return Ok(None);
             },
             (_, ids::ENDCHAR, _) => return Err((line_num, UnexpectedEntry(String::from(id)))),
 
@@ -1309,79 +2033,87 @@ pub fn parse_font(input: &str) -> Result<Font, (usize, Error)> {
 
         let rest = rest.unwrap();
 
-        match state {
+        self.slot = Slot::Keyword(String::from(id));
+
+        let f_shell = &mut self.f_shell;
+
+        match self.state {
             ParseState::Empty => match id {
                 ids::STARTFONT => {
-                    f_shell.bdf_version = Some(rest);
-                    state = ParseState::InFont;
+                    f_shell.bdf_version = Some(String::from(rest));
+                    self.state = ParseState::InFont;
                 },
                 id => return Err((line_num, UnexpectedEntry(String::from(id)))),
             }
             ParseState::InFont => match id {
                 ids::FONT => {
-                    f_shell.name = Some(rest);
+                    f_shell.name = Some(String::from(rest));
                 },
                 ids::CONTENTVERSION => {
-                    let val = rest.parse().or_else(|_| Err((line_num, ParseError("integer"))))?;
+                    let val = rest.parse().map_err(|_| invalid(line_num, id, ParseError("integer")))?;
                     f_shell.content_version = Some(val);
                 },
                 ids::SIZE => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.size = Some(val);
                 },
                 ids::FONTBOUNDINGBOX => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.bounding_box = Some(val);
-                    main_bbox = Some(val);
+                    self.main_bbox = Some(val);
                 },
                 ids::METRICSSET => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.metrics = Some(val);
                 },
                 ids::SWIDTH => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.scalable_width = Some(val);
                 },
                 ids::DWIDTH => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.device_width = Some(val);
                 },
                 ids::SWIDTH1 => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.scalable_width_alt = Some(val);
                 },
                 ids::DWIDTH1 => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.device_width_alt = Some(val);
                 },
                 ids::VVECTOR => {
-                    let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                    let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                     f_shell.vector = Some(val);
                 },
                 ids::STARTPROPERTIES => {
-                    let val = rest.parse().or_else(|_| Err((line_num, ParseError("integer"))))?;
-                    f_shell.properties.reserve(val);
-                    state = ParseState::InProperties;
+                    let val: usize = rest.parse().map_err(|_| invalid(line_num, id, ParseError("integer")))?;
+                    f_shell.properties.reserve(val.min(MAX_RESERVE));
+                    self.props_ct = val;
+                    self.state = ParseState::InProperties;
                 }
                 ids::CHARS => {
-                    let val = rest.parse().or_else(|_| Err((line_num, ParseError("integer"))))?;
-                    f_shell.glyphs.reserve(val);
-                    state = ParseState::InChars;
+                    self.chars_ct = rest.parse().map_err(|_| invalid(line_num, id, ParseError("integer")))?;
+                    self.state = ParseState::InChars;
+                    return self.take_header().map(|h| Some(ParseEvent::Header(Box::new(h))))
+                                             .map_err(|e| (line_num, e));
                 },
-                id => return Err((line_num, UnexpectedEntry(String::from(id)))),
+                id => self.unknown_entry(line_num, id)?,
             }
             ParseState::InProperties => {
+                self.slot = Slot::Property(String::from(id));
+                self.props_seen += 1;
+
                 let value =
-                    if rest.starts_with('"') {
-                        if rest.ends_with('"') {
-                            PropertyValueShell::Str(&rest[1..(rest.len()-1)])
-                        } else {
-                            return Err((line_num, PropertyValue::parse_error()));
+                    if let Some(quoted) = rest.strip_prefix('"') {
+                        match quoted.strip_suffix('"') {
+                            Some(val) => PropertyValueShell::Str(String::from(val)),
+                            None => return Err(invalid(line_num, id, PropertyValue::parse_error())),
                         }
                     } else {
                         match rest.parse() {
                             Ok(i) => PropertyValueShell::Int(i),
-                            Err(_) => return Err((line_num, PropertyValue::parse_error())),
+                            Err(_) => return Err(invalid(line_num, id, PropertyValue::parse_error())),
                         }
                     };
 
@@ -1400,8 +2132,8 @@ pub fn parse_font(input: &str) -> Result<Font, (usize, Error)> {
                         ids::xlfd::POINT_SIZE |
                         ids::xlfd::RESOLUTION_X |
                         ids::xlfd::RESOLUTION_Y |
-                        ids::xlfd::AVERAGE_WIDTH => return Err((line_num, ParseError("\"string\""))),
-                        id => f_shell.properties.push(PropertyShell::new(id, value)),
+                        ids::xlfd::AVERAGE_WIDTH => return Err(invalid(line_num, id, ParseError("integer"))),
+                        id => f_shell.properties.push(PropertyShell::new(id, PropertyValueShell::Str(val))),
                     }
 
                     PropertyValueShell::Int(val) => match id {
@@ -1418,7 +2150,7 @@ pub fn parse_font(input: &str) -> Result<Font, (usize, Error)> {
                         ids::xlfd::ADD_STYLE_NAME |
                         ids::xlfd::SPACING |
                         ids::xlfd::CHARSET_REGISTRY |
-                        ids::xlfd::CHARSET_ENCODING => return Err((line_num, ParseError("integer"))),
+                        ids::xlfd::CHARSET_ENCODING => return Err(invalid(line_num, id, ParseError("\"string\""))),
                         id => f_shell.properties.push(PropertyShell::new(id, value)),
                     }
                 }
@@ -1426,74 +2158,323 @@ pub fn parse_font(input: &str) -> Result<Font, (usize, Error)> {
             }
             ParseState::InChars => match id {
                 ids::STARTCHAR => {
-                    f_shell.glyphs.push(GlyphShell::new());
-                    let g_shell = f_shell.glyphs.last_mut().unwrap();
-                    g_shell.name = Some(rest);
-                    state = ParseState::InChar;
+                    let mut g_shell = GlyphShell::new();
+                    g_shell.name = Some(String::from(rest));
+                    self.g_shell = Some(g_shell);
+                    self.state = ParseState::InChar;
                 }
                 id => return Err((line_num, UnexpectedEntry(String::from(id)))),
             },
             ParseState::InChar => {
-                let g_shell = f_shell.glyphs.last_mut().unwrap();
+                let g_shell = self.g_shell.as_mut().unwrap();
 
                 match id {
                     ids::ENCODING => {
-                        match rest.find(char::is_whitespace) {
-                            Some(n) => match &rest[0..n].parse::<i64>() {
-                                Ok(-1) => return Err((line_num, SpecialEncoding)),
-                                _ => return Err((line_num, ParseError("-1 integer"))),
-                            },
-                            None => {}
-                        }
-
-                        match rest.parse::<u32>() {
-                            Ok(u) => {
-                                match char::try_from(u) {
-                                    Ok(c) => g_shell.codepoint = Some(c),
-                                    Err(_) => return Err((line_num, InvalidCodepoint(u))),
-                                }
-                            }
-                            Err(_) => return Err((line_num, ParseError("integer"))),
-                        };
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
+                        g_shell.encoding = Some(val);
                     },
                     ids::METRICSSET => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.metrics = Some(val);
                     },
                     ids::SWIDTH => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.scalable_width = Some(val);
                     },
                     ids::DWIDTH => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.device_width = Some(val);
                     },
                     ids::SWIDTH1 => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.scalable_width_alt = Some(val);
                     },
                     ids::DWIDTH1 => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.device_width_alt = Some(val);
                     },
                     ids::VVECTOR => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.vector = Some(val);
                     },
                     ids::BBX => {
-                        let val = rest.parse().or_else(|e| Err((line_num, e)))?;
+                        let val = rest.parse().map_err(|e| invalid(line_num, id, e))?;
                         g_shell.bounding_box = Some(val);
-                        curr_bbox = Some(val);
+                        self.curr_bbox = Some(val);
                     },
-                    id => return Err((line_num, UnexpectedEntry(String::from(id)))),
+                    id => self.unknown_entry(line_num, id)?,
                 }
             }
-            ParseState::InBitmap => {
-                // rest should be None
+            ParseState::InBitmap | ParseState::Done => {
+                // handled above
                 unreachable!();
             }
         }
+
+        Ok(None)
+    }
+
+    fn take_header(&mut self) -> Result<FontHeader, Error> {
+        self.header_done = true;
+
+        let f_shell = std::mem::replace(&mut self.f_shell, FontShell::new());
+        f_shell.into_header(std::mem::take(&mut self.comments))
+    }
+
+    // flushes whatever the input left unfinished
+    fn finish(&mut self) -> Result<Option<ParseEvent>, Diagnostic> {
+        let last_line = self.last_line;
+
+        // a glyph cut off before its ENDCHAR, kept with blank rows for any missing ones when lenient
+        let glyph = match self.g_shell.take() {
+            Some(mut g_shell) => {
+                self.recover(last_line, Error::MissingEntry(String::from(ids::ENDCHAR)))
+                    .map_err(|e| self.diagnose(e))?;
+
+                if self.state == ParseState::InBitmap {
+                    self.check_surplus_rows(g_shell.bitmap.height).map_err(|e| self.diagnose(e))?;
+
+                    let blank = BitmapRow(BitVec::from_elem(g_shell.bitmap.width, false));
+                    g_shell.bitmap.data.resize(g_shell.bitmap.height, blank);
+                    self.bitmap_len = 0;
+                }
+
+                self.glyphs_ct += 1;
+                Some(g_shell.into_glyph().map_err(|e| self.diagnose((last_line, e)))?)
+            }
+            None => None,
+        };
+
+        if self.state != ParseState::Done {
+            self.tolerate(last_line, Error::MissingEntry(String::from(ids::ENDFONT)));
+            self.check_chars_ct(last_line);
+            self.state = ParseState::Done;
+        }
+
+        let event = if !self.header_done {
+            self.take_header().map(|h| Some(ParseEvent::Header(Box::new(h))))
+                              .map_err(|e| self.diagnose((last_line, e)))?
+        } else {
+            glyph.map(ParseEvent::Glyph)
+        };
+
+        if self.options.lossless {
+            Ok(self.attach_source(event))
+        } else {
+            Ok(event)
+        }
+    }
+}
+
+//
+
+pub struct BdfReader<R> {
+    reader: R,
+    parser: Parser,
+    buf: Vec<u8>,
+    line_num: usize,
+}
+
+impl<R: BufRead> BdfReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        Self {
+            reader,
+            parser: Parser::new(options),
+            buf: Vec::new(),
+            line_num: 0,
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<ParseEvent>, Diagnostic> {
+        loop {
+            self.buf.clear();
+
+            let read = self.reader.read_until(b'\n', &mut self.buf)
+                                  .map_err(|e| Diagnostic::new(self.line_num + 1, "", Error::Io(e)))?;
+            if read == 0 {
+                return self.parser.finish();
+            }
+
+            self.line_num += 1;
+
+            if let Some(event) = self.parser.parse_line(self.line_num, &self.buf)? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    pub fn read_header(mut self) -> Result<(FontHeader, Glyphs<R>), Diagnostic> {
+        match self.next_event()? {
+            Some(ParseEvent::Header(header)) => Ok((*header, Glyphs { reader: self, done: false })),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn read_font(self) -> Result<Font, Diagnostic> {
+        self.read_font_with_warnings().map(|(font, _)| font)
+    }
+
+    pub fn read_font_with_warnings(self) -> Result<(Font, Vec<Warning>), Diagnostic> {
+        let (mut header, mut glyphs) = self.read_header()?;
+
+        let mut vec = Vec::with_capacity(glyphs.reader.parser.chars_ct.min(MAX_RESERVE));
+        for glyph in &mut glyphs {
+            vec.push(glyph?);
+        }
+
+        header.comments.append(&mut glyphs.reader.parser.comments);
+        if let Some(source) = &mut header.source {
+            source.lines.append(&mut glyphs.reader.parser.take_trailing());
+        }
+
+        let warnings = std::mem::take(&mut glyphs.reader.parser.warnings);
+
+        Ok((Font::from_header(header, vec), warnings))
+    }
+}
+
+pub struct Glyphs<R> {
+    reader: BdfReader<R>,
+    done: bool,
+}
+
+impl<R> Glyphs<R> {
+    // problems recovered from so far, when not parsing strictly
+    pub fn warnings(&self) -> &[Warning] {
+        &self.reader.parser.warnings
+    }
+}
+
+impl<R: BufRead> Iterator for Glyphs<R> {
+    type Item = Result<Glyph, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.reader.next_event() {
+            Ok(Some(ParseEvent::Glyph(glyph))) => Some(Ok(glyph)),
+            Ok(Some(ParseEvent::Header(_))) => unreachable!(),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+pub fn parse_font(input: &str) -> Result<Font, Diagnostic> {
+    BdfReader::new(input.as_bytes()).read_font()
+}
+
+pub fn parse_font_with(input: &str, options: ParseOptions) -> Result<(Font, Vec<Warning>), Diagnostic> {
+    BdfReader::with_options(input.as_bytes(), options).read_font_with_warnings()
+}
+
+//
+
+pub struct BdfWriter<W> {
+    writer: W,
+    chars_left: Option<usize>,
+    trailing: Option<SourceMap>,
+}
+
+impl<W: Write> BdfWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            chars_left: None,
+            trailing: None,
+        }
+    }
+
+    // a header read losslessly is written back the way it was read, as far as it is unedited
+    pub fn write_header(&mut self, header: &FontHeader, chars: usize) -> Result<(), Error> {
+        if self.chars_left.is_some() {
+            return Err(Error::UnexpectedEntry(String::from(ids::STARTFONT)));
+        }
+
+        header.validate()?;
+
+        let lines = header.bdf_lines(chars);
+        match &header.source {
+            Some(source) => {
+                let (before, after) = source.split_at_glyphs();
+                lossless::write_merged(&mut self.writer, before, source.newline, &lines)?;
+                self.trailing = Some(SourceMap::new(source.newline, after.to_vec()));
+            }
+            None => lossless::write_lines(&mut self.writer, &lines, "\n")?,
+        }
+
+        self.chars_left = Some(chars);
+
+        Ok(())
+    }
+
+    pub fn write_glyph(&mut self, glyph: &Glyph) -> Result<(), Error> {
+        match self.chars_left {
+            Some(n) if n > 0 => self.chars_left = Some(n - 1),
+            _ => return Err(Error::UnexpectedEntry(String::from(ids::STARTCHAR))),
+        }
+
+        glyph.validate()?;
+
+        let lines = glyph.bdf_lines();
+        match &glyph.source {
+            Some(source) => lossless::write_merged(&mut self.writer, &source.lines, source.newline, &lines)?,
+            None => lossless::write_lines(&mut self.writer, &lines, "\n")?,
+        }
+
+        Ok(())
     }
 
-    f_shell.to_font().or_else(|e| Err((lines_ct, e)))
+    pub fn write_font(mut self, font: &Font) -> Result<W, Error> {
+        self.write_header(&font.header(), font.glyphs.len())?;
+        for glyph in &font.glyphs {
+            self.write_glyph(glyph)?;
+        }
+        self.finish()
+    }
+
+    pub fn finish(mut self) -> Result<W, Error> {
+        match self.chars_left {
+            Some(0) => {},
+            Some(_) => return Err(Error::FontValidation("fewer glyphs written than declared by CHARS")),
+            None => return Err(Error::FontValidation("header not written")),
+        }
+
+        let lines = [(Slot::Keyword(String::from(ids::ENDFONT)), String::from(ids::ENDFONT))];
+        match &self.trailing {
+            Some(source) => lossless::write_merged(&mut self.writer, &source.lines, source.newline, &lines)?,
+            None => lossless::write_lines(&mut self.writer, &lines, "\n")?,
+        }
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+// lets the writer back the Display impls
+
+struct FmtWriter<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl<'a, 'b> Write for FmtWriter<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // lines kept verbatim from a lossless read need not be UTF-8, the writer passes each one whole
+        self.0.write_str(&String::from_utf8_lossy(buf)).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
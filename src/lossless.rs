@@ -0,0 +1,138 @@
+use std::io::{
+    self,
+    Write,
+};
+
+//
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Slot {
+    Verbatim,
+    Glyphs,
+    Comment(usize),
+    Keyword(String),
+    Property(String),
+    Row(usize),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SourceLine {
+    pub slot: Slot,
+    pub raw: Vec<u8>,
+    pub canonical: String,
+}
+
+impl SourceLine {
+    pub fn new(slot: Slot, raw: &[u8]) -> Self {
+        Self {
+            slot,
+            raw: raw.to_vec(),
+            canonical: String::new(),
+        }
+    }
+}
+
+//
+
+// the lines a block was read from, in their original order and spelling
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    pub(crate) newline: &'static str,
+    pub(crate) lines: Vec<SourceLine>,
+}
+
+impl SourceMap {
+    pub(crate) fn new(newline: &'static str, lines: Vec<SourceLine>) -> Self {
+        Self {
+            newline,
+            lines,
+        }
+    }
+
+    // pairs every recorded line with what the writer produces for it right now,
+    //   lines the writer has no counterpart for are kept as they are
+    pub(crate) fn fill(&mut self, current: &[(Slot, String)]) {
+        let mut used = vec![false; current.len()];
+
+        for line in &mut self.lines {
+            if line.slot == Slot::Verbatim || line.slot == Slot::Glyphs {
+                continue;
+            }
+
+            let found = current.iter()
+                               .enumerate()
+                               .position(|(i, (slot, _))| !used[i] && *slot == line.slot);
+            match found {
+                Some(i) => {
+                    used[i] = true;
+                    line.canonical = current[i].1.clone();
+                }
+                None => line.slot = Slot::Verbatim,
+            }
+        }
+    }
+
+    pub(crate) fn split_at_glyphs(&self) -> (&[SourceLine], &[SourceLine]) {
+        match self.lines.iter().position(|l| l.slot == Slot::Glyphs) {
+            Some(n) => (&self.lines[..n], &self.lines[(n + 1)..]),
+            None    => (&self.lines, &[]),
+        }
+    }
+}
+
+//
+
+pub(crate) fn write_lines<W: Write>(w: &mut W, current: &[(Slot, String)], newline: &str) -> io::Result<()> {
+    for (_, line) in current {
+        write!(w, "{}{}", line, newline)?;
+    }
+
+    Ok(())
+}
+
+// unedited lines are written raw, edited ones canonically,
+//   and lines new since reading are slotted in where the writer would normally put them
+pub(crate) fn write_merged<W: Write>(w: &mut W,
+                                     source: &[SourceLine],
+                                     newline: &str,
+                                     current: &[(Slot, String)]) -> io::Result<()> {
+    let mut used = vec![false; current.len()];
+    let in_source = |slot: &Slot| source.iter().any(|l| l.slot == *slot);
+
+    for line in source {
+        if line.slot == Slot::Verbatim {
+            w.write_all(&line.raw)?;
+            continue;
+        }
+
+        let found = current.iter()
+                           .enumerate()
+                           .position(|(i, (slot, _))| !used[i] && *slot == line.slot);
+        let at = match found {
+            Some(i) => i,
+            None    => continue,
+        };
+
+        for i in 0..at {
+            if !used[i] && !in_source(&current[i].0) {
+                used[i] = true;
+                write!(w, "{}{}", current[i].1, newline)?;
+            }
+        }
+
+        used[at] = true;
+        if current[at].1 == line.canonical {
+            w.write_all(&line.raw)?;
+        } else {
+            write!(w, "{}{}", current[at].1, newline)?;
+        }
+    }
+
+    for (i, (_, line)) in current.iter().enumerate() {
+        if !used[i] {
+            write!(w, "{}{}", line, newline)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,799 @@
+use std::{
+    convert::TryFrom,
+    io::Write,
+};
+
+use crate::{
+    ids,
+    BdfBlock,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+#[derive(Default)]
+pub struct OtbOptions {
+    // pixel sizes at which the strike should be scaled rather than have the outline rendered,
+    //   listed in EBSC, which is left out when there are none
+    pub scaled_sizes: Vec<u8>,
+}
+
+//
+
+struct Out(Vec<u8>);
+
+impl Out {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn i8(&mut self, v: i8) {
+        self.0.push(v as u8);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn i16(&mut self, v: i16) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.0.extend_from_slice(v);
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+// searchRange, entrySelector and rangeShift of a binary searchable array
+fn search_params(count: usize, size: usize) -> (u16, u16, u16) {
+    let mut selector = 0;
+    while 2usize << selector <= count {
+        selector += 1;
+    }
+
+    let range = (1usize << selector) * size;
+    (range as u16, selector as u16, (count * size - range) as u16)
+}
+
+//
+
+// one glyph of the strike as image format 1, small metrics and byte aligned rows
+struct Sbit {
+    width: u8,
+    height: u8,
+    bearing_x: i8,
+    bearing_y: i8,
+    advance: u8,
+    rows: Vec<u8>,
+}
+
+impl Sbit {
+    fn notdef() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            bearing_x: 0,
+            bearing_y: 0,
+            advance: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    fn from_glyph(font: &Font, glyph: &Glyph) -> Result<Self, Error> {
+        let too_big = |_| Error::GlyphValidation(glyph.name.clone(), "metrics do not fit in an sbit strike");
+
+        let bbx = &glyph.bounding_box;
+        let advance = glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0);
+
+        Ok(Self {
+            width: u8::try_from(bbx.width).map_err(too_big)?,
+            height: u8::try_from(bbx.height).map_err(too_big)?,
+            bearing_x: i8::try_from(bbx.x_offset).map_err(too_big)?,
+            bearing_y: i8::try_from(i64::from(bbx.y_offset) + i64::from(bbx.height)).map_err(too_big)?,
+            advance: u8::try_from(advance).map_err(too_big)?,
+            rows: (0..glyph.bitmap.height()).flat_map(|y| glyph.bitmap.row_bytes(y)).collect(),
+        })
+    }
+
+    fn inked(&self) -> bool {
+        self.width > 0 && self.height > 0
+    }
+
+    fn right(&self) -> i32 {
+        i32::from(self.bearing_x) + i32::from(self.width)
+    }
+
+    fn bottom(&self) -> i32 {
+        i32::from(self.bearing_y) - i32::from(self.height)
+    }
+}
+
+// SbitLineMetrics, also used for the vertical direction as there are no vertical metrics
+#[derive(Copy, Clone)]
+struct LineMetrics {
+    ascender: i32,
+    descender: i32,
+    width_max: i32,
+    min_origin_sb: i32,
+    min_advance_sb: i32,
+    max_before_bl: i32,
+    min_after_bl: i32,
+}
+
+impl LineMetrics {
+    fn new(ascent: i32, descent: i32, sbits: &[Sbit]) -> Self {
+        let inked = || sbits.iter().filter(|s| s.inked());
+
+        Self {
+            ascender: ascent,
+            descender: -descent,
+            width_max: inked().map(|s| i32::from(s.width)).max().unwrap_or(0),
+            min_origin_sb: inked().map(|s| i32::from(s.bearing_x)).min().unwrap_or(0),
+            min_advance_sb: inked().map(|s| i32::from(s.advance) - s.right()).min().unwrap_or(0),
+            max_before_bl: inked().map(|s| i32::from(s.bearing_y)).max().unwrap_or(0),
+            min_after_bl: inked().map(|s| s.bottom()).min().unwrap_or(0),
+        }
+    }
+
+    fn scaled(&self, from: u8, to: u8) -> Self {
+        let scale = |v: i32| (f64::from(v) * f64::from(to) / f64::from(from)).round() as i32;
+
+        Self {
+            ascender: scale(self.ascender),
+            descender: scale(self.descender),
+            width_max: scale(self.width_max),
+            min_origin_sb: scale(self.min_origin_sb),
+            min_advance_sb: scale(self.min_advance_sb),
+            max_before_bl: scale(self.max_before_bl),
+            min_after_bl: scale(self.min_after_bl),
+        }
+    }
+
+    fn write(&self, out: &mut Out) -> Result<(), Error> {
+        let fit = |v: i32| i8::try_from(v).map_err(|_| Error::FontValidation("metrics do not fit in an sbit strike"));
+
+        out.i8(fit(self.ascender)?);
+        out.i8(fit(self.descender)?);
+        out.u8(u8::try_from(self.width_max).map_err(|_| Error::FontValidation("metrics do not fit in an sbit strike"))?);
+        out.i8(1); // caret slope numerator
+        out.i8(0); // caret slope denominator
+        out.i8(0); // caret offset
+        out.i8(fit(self.min_origin_sb)?);
+        out.i8(fit(self.min_advance_sb)?);
+        out.i8(fit(self.max_before_bl)?);
+        out.i8(fit(self.min_after_bl)?);
+        out.i8(0);
+        out.i8(0);
+
+        Ok(())
+    }
+}
+
+//
+
+// everything the tables are built from, glyph 0 is an empty .notdef and the rest follow in code point order
+struct Strike<'a> {
+    font: &'a Font,
+    ppem: u8,
+    scale: i32,
+    ascent: i32,
+    descent: i32,
+    names: Vec<String>,
+    sbits: Vec<Sbit>,
+    // (code point, glyph id) by code point
+    map: Vec<(u32, u16)>,
+}
+
+impl<'a> Strike<'a> {
+    fn new(font: &'a Font) -> Result<Self, Error> {
+        let mut order: Vec<(Option<char>, &Glyph)> = font.unicode_chars()?.into_iter().zip(&font.glyphs).collect();
+        order.sort_by_key(|&(c, _)| c.map_or(u32::MAX, |c| c as u32));
+
+        if order.len() >= usize::from(u16::MAX) {
+            return Err(Error::FontValidation("too many glyphs for an sfnt"));
+        }
+
+        let mut names = vec![String::from(".notdef")];
+        let mut sbits = vec![Sbit::notdef()];
+        let mut map = Vec::new();
+
+        for (i, (c, glyph)) in order.into_iter().enumerate() {
+            let id = i as u16 + 1;
+
+            let valid = glyph.name.len() <= 63 && glyph.name.bytes().all(|b| b.is_ascii_graphic());
+            names.push(if valid { glyph.name.clone() } else { format!("glyph{}", id) });
+            sbits.push(Sbit::from_glyph(font, glyph)?);
+
+            if let Some(c) = c {
+                if map.last().map(|&(last, _)| last) != Some(c as u32) {
+                    map.push((c as u32, id));
+                }
+            }
+        }
+
+        let (ascent, descent) = font.ascent_descent();
+
+        let pixel_size = font.xlfd.pixel_size.filter(|&p| p > 0).unwrap_or_else(|| {
+            match (font.size.point_size * font.size.y_dpi + 36) / 72 {
+                0 => ascent + descent,
+                p => p as i32,
+            }
+        });
+        let ppem = u8::try_from(pixel_size).ok()
+                                           .filter(|&p| p > 0)
+                                           .ok_or(Error::FontValidation("pixel size does not fit in an sbit strike"))?;
+
+        Ok(Self {
+            font,
+            ppem,
+            // font units per pixel, kept so that a strike of 255 pixels still fits in 16 bit units
+            scale: (2048 / i32::from(ppem)).clamp(1, 128),
+            ascent,
+            descent,
+            names,
+            sbits,
+            map,
+        })
+    }
+
+    fn units_per_em(&self) -> u16 {
+        (i32::from(self.ppem) * self.scale) as u16
+    }
+
+    fn units(&self, px: i32) -> Result<i16, Error> {
+        i16::try_from(px * self.scale).map_err(|_| Error::FontValidation("metrics do not fit in font units"))
+    }
+
+    fn inked(&self) -> impl Iterator<Item = &Sbit> {
+        self.sbits.iter().filter(|s| s.inked())
+    }
+
+    fn glyph_top(&self, c: char) -> Option<i32> {
+        let id = self.map.iter().find(|&&(code, _)| code == c as u32)?.1;
+        Some(i32::from(self.sbits[usize::from(id)].bearing_y))
+    }
+
+    fn bold(&self) -> bool {
+        self.font.xlfd.weight_class() >= 700
+    }
+
+    fn slant(&self) -> &str {
+        self.font.xlfd.slant.as_deref().unwrap_or("R")
+    }
+
+    fn fixed_pitch(&self) -> bool {
+        let mut advances = self.sbits[1..].iter().map(|s| s.advance).filter(|&a| a > 0);
+        match advances.next() {
+            Some(first) => advances.all(|a| a == first),
+            None => false,
+        }
+    }
+
+    fn family(&self) -> String {
+        match &self.font.xlfd.family_name {
+            Some(family) if !family.is_empty() => family.clone(),
+            _ => self.font.name.clone(),
+        }
+    }
+
+    // weight and slant as in "Bold Italic", "Regular" for a medium upright font
+    fn style(&self) -> String {
+        let weight = self.font.xlfd.weight_name.as_deref().unwrap_or("");
+        let mut words: Vec<String> = match weight.to_ascii_lowercase().as_str() {
+            "" | "medium" | "regular" | "normal" | "book" => Vec::new(),
+            _ => weight.split(' ').map(capitalize).collect(),
+        };
+
+        match self.slant().to_ascii_uppercase().as_str() {
+            "I" | "RI" => words.push(String::from("Italic")),
+            "O" | "RO" => words.push(String::from("Oblique")),
+            _ => (),
+        }
+
+        if words.is_empty() {
+            String::from("Regular")
+        } else {
+            words.join(" ")
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+//
+
+fn write_eblc(strike: &Strike) -> Result<Out, Error> {
+    let glyph_ct = strike.sbits.len();
+    let metrics = LineMetrics::new(strike.ascent, strike.descent, &strike.sbits);
+
+    let mut out = Out::new();
+    out.u32(0x00020000);
+    out.u32(1);
+
+    // BitmapSize, the index subtable array follows right after it
+    out.u32(8 + 48);
+    out.u32((8 + 8 + 4 * (glyph_ct + 1)) as u32);
+    out.u32(1);
+    out.u32(0);
+    metrics.write(&mut out)?;
+    metrics.write(&mut out)?;
+    out.u16(0);
+    out.u16((glyph_ct - 1) as u16);
+    out.u8(strike.ppem);
+    out.u8(strike.ppem);
+    out.u8(1); // bit depth
+    out.i8(0x01); // horizontal metrics
+
+    // IndexSubTableArray
+    out.u16(0);
+    out.u16((glyph_ct - 1) as u16);
+    out.u32(8);
+
+    // IndexSubTable1 over the whole font, images start right after the EBDT header
+    out.u16(1);
+    out.u16(1);
+    out.u32(4);
+
+    let mut offset = 0;
+    for sbit in &strike.sbits {
+        out.u32(offset);
+        offset += 5 + sbit.rows.len() as u32;
+    }
+    out.u32(offset);
+
+    Ok(out)
+}
+
+fn write_ebdt(strike: &Strike) -> Out {
+    let mut out = Out::new();
+    out.u32(0x00020000);
+
+    for sbit in &strike.sbits {
+        out.u8(sbit.height);
+        out.u8(sbit.width);
+        out.i8(sbit.bearing_x);
+        out.i8(sbit.bearing_y);
+        out.u8(sbit.advance);
+        out.bytes(&sbit.rows);
+    }
+
+    out
+}
+
+fn write_ebsc(strike: &Strike, sizes: &[u8]) -> Result<Out, Error> {
+    let metrics = LineMetrics::new(strike.ascent, strike.descent, &strike.sbits);
+
+    let mut out = Out::new();
+    out.u32(0x00020000);
+    out.u32(sizes.len() as u32);
+
+    for &size in sizes {
+        let scaled = metrics.scaled(strike.ppem, size);
+        scaled.write(&mut out)?;
+        scaled.write(&mut out)?;
+        out.u8(size);
+        out.u8(size);
+        out.u8(strike.ppem);
+        out.u8(strike.ppem);
+    }
+
+    Ok(out)
+}
+
+fn write_cmap(strike: &Strike) -> Result<Out, Error> {
+    // runs of consecutive code points mapped to consecutive glyphs
+    let mut groups: Vec<(u32, u32, u16)> = Vec::new();
+    for &(code, id) in &strike.map {
+        match groups.last_mut() {
+            Some((start, end, first)) if code == *end + 1 && u32::from(id) == u32::from(*first) + (code - *start) => *end = code,
+            _ => groups.push((code, code, id)),
+        }
+    }
+
+    // format 4 for the basic multilingual plane, the last segment is the required one at 0xffff
+    let mut segments: Vec<(u16, u16, u16)> = Vec::new();
+    for &(start, end, first) in &groups {
+        if start > 0xfffe {
+            break;
+        }
+
+        let end = end.min(0xfffe);
+        segments.push((start as u16, end as u16, first.wrapping_sub(start as u16)));
+    }
+    segments.push((0xffff, 0xffff, 1));
+
+    let length = 16 + 8 * segments.len();
+    if length > usize::from(u16::MAX) {
+        return Err(Error::FontValidation("too many code point ranges for a format 4 cmap"));
+    }
+
+    let mut format4 = Out::new();
+    let (range, selector, shift) = search_params(segments.len(), 2);
+    format4.u16(4);
+    format4.u16(length as u16);
+    format4.u16(0);
+    format4.u16((segments.len() * 2) as u16);
+    format4.u16(range);
+    format4.u16(selector);
+    format4.u16(shift);
+    for &(_, end, _) in &segments {
+        format4.u16(end);
+    }
+    format4.u16(0);
+    for &(start, _, _) in &segments {
+        format4.u16(start);
+    }
+    for &(_, _, delta) in &segments {
+        format4.u16(delta);
+    }
+    for _ in &segments {
+        format4.u16(0);
+    }
+
+    // format 12 with everything, only needed past the basic multilingual plane
+    let format12 = match groups.last() {
+        Some(&(_, end, _)) if end > 0xffff => {
+            let mut out = Out::new();
+            out.u16(12);
+            out.u16(0);
+            out.u32((16 + 12 * groups.len()) as u32);
+            out.u32(0);
+            out.u32(groups.len() as u32);
+            for &(start, end, first) in &groups {
+                out.u32(start);
+                out.u32(end);
+                out.u32(u32::from(first));
+            }
+            Some(out)
+        }
+        _ => None,
+    };
+
+    let records: &[(u16, u16, bool)] = match format12 {
+        Some(_) => &[(0, 3, false), (0, 4, true), (3, 1, false), (3, 10, true)],
+        None => &[(0, 3, false), (3, 1, false)],
+    };
+
+    let header = 4 + 8 * records.len() as u32;
+    let mut out = Out::new();
+    out.u16(0);
+    out.u16(records.len() as u16);
+    for &(platform, encoding, long) in records {
+        out.u16(platform);
+        out.u16(encoding);
+        out.u32(if long { header + format4.len() as u32 } else { header });
+    }
+    out.bytes(&format4.0);
+    if let Some(format12) = format12 {
+        out.bytes(&format12.0);
+    }
+
+    Ok(out)
+}
+
+fn write_head(strike: &Strike) -> Result<Out, Error> {
+    let inked: Vec<&Sbit> = strike.inked().collect();
+    let x_min = inked.iter().map(|s| i32::from(s.bearing_x)).min().unwrap_or(0);
+    let y_min = inked.iter().map(|s| s.bottom()).min().unwrap_or(0);
+    let x_max = inked.iter().map(|s| s.right()).max().unwrap_or(0);
+    let y_max = inked.iter().map(|s| i32::from(s.bearing_y)).max().unwrap_or(0);
+
+    let mut out = Out::new();
+    out.u32(0x00010000);
+    out.u32((strike.font.content_version.unwrap_or(1) as u32) << 16);
+    out.u32(0); // checksum adjustment, filled in once the file is complete
+    out.u32(0x5f0f3cf5);
+    out.u16(0x0009); // baseline at y 0, integer scaling
+    out.u16(strike.units_per_em());
+    out.u32(0);
+    out.u32(0); // created
+    out.u32(0);
+    out.u32(0); // modified
+    out.i16(strike.units(x_min)?);
+    out.i16(strike.units(y_min)?);
+    out.i16(strike.units(x_max)?);
+    out.i16(strike.units(y_max)?);
+    out.u16(u16::from(strike.bold()) | u16::from(strike.font.xlfd.is_italic()) << 1);
+    out.u16(u16::from(strike.ppem));
+    out.i16(2);
+    out.i16(0);
+    out.i16(0);
+
+    Ok(out)
+}
+
+fn write_hhea(strike: &Strike) -> Result<Out, Error> {
+    let advance_max = strike.sbits.iter().map(|s| i32::from(s.advance)).max().unwrap_or(0);
+    let min_left = strike.inked().map(|s| i32::from(s.bearing_x)).min().unwrap_or(0);
+    let min_right = strike.inked().map(|s| i32::from(s.advance) - s.right()).min().unwrap_or(0);
+    let max_extent = strike.inked().map(|s| s.right()).max().unwrap_or(0);
+
+    let mut out = Out::new();
+    out.u32(0x00010000);
+    out.i16(strike.units(strike.ascent)?);
+    out.i16(strike.units(-strike.descent)?);
+    out.i16(0);
+    out.u16(strike.units(advance_max)? as u16);
+    out.i16(strike.units(min_left)?);
+    out.i16(strike.units(min_right)?);
+    out.i16(strike.units(max_extent)?);
+    out.i16(1);
+    out.i16(0);
+    out.i16(0);
+    for _ in 0..5 {
+        out.i16(0);
+    }
+    out.u16(strike.sbits.len() as u16);
+
+    Ok(out)
+}
+
+fn write_hmtx(strike: &Strike) -> Result<Out, Error> {
+    let mut out = Out::new();
+    for sbit in &strike.sbits {
+        out.u16(strike.units(i32::from(sbit.advance))? as u16);
+        out.i16(strike.units(i32::from(sbit.bearing_x))?);
+    }
+
+    Ok(out)
+}
+
+fn write_maxp(strike: &Strike) -> Out {
+    let mut out = Out::new();
+    out.u32(0x00005000);
+    out.u16(strike.sbits.len() as u16);
+
+    out
+}
+
+fn write_name(strike: &Strike) -> Out {
+    let font = strike.font;
+    let family = strike.family();
+    let style = strike.style();
+
+    let full = if style == "Regular" { family.clone() } else { format!("{} {}", family, style) };
+    let postscript: String = format!("{}-{}", family, style).chars()
+                                                            .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+                                                            .take(63)
+                                                            .collect();
+    let version = format!("Version {}.000", font.content_version.unwrap_or(1));
+
+    let mut names: Vec<(u16, String)> = Vec::new();
+    if let Some(copyright) = font.str_property(ids::COPYRIGHT) {
+        names.push((0, String::from(copyright)));
+    }
+    names.push((1, family));
+    names.push((2, style));
+    names.push((3, font.name.clone()));
+    names.push((4, full));
+    names.push((5, version));
+    names.push((6, postscript));
+    if let Some(foundry) = font.xlfd.foundry.as_ref().filter(|f| !f.is_empty()) {
+        names.push((8, foundry.clone()));
+    }
+
+    let mut storage = Out::new();
+    let mut out = Out::new();
+    out.u16(0);
+    out.u16(names.len() as u16);
+    out.u16((6 + 12 * names.len()) as u16);
+
+    for (id, name) in names {
+        let start = storage.len();
+        for unit in name.encode_utf16() {
+            storage.u16(unit);
+        }
+
+        out.u16(3); // windows
+        out.u16(1); // unicode bmp
+        out.u16(0x409);
+        out.u16(id);
+        out.u16((storage.len() - start) as u16);
+        out.u16(start as u16);
+    }
+    out.bytes(&storage.0);
+
+    out
+}
+
+fn write_os2(strike: &Strike) -> Result<Out, Error> {
+    let font = strike.font;
+    let em = i32::from(strike.units_per_em());
+
+    let advances: Vec<i32> = strike.sbits.iter().map(|s| i32::from(s.advance)).filter(|&a| a > 0).collect();
+    let average = match advances.len() {
+        0 => 0,
+        n => advances.iter().sum::<i32>() * strike.scale / n as i32,
+    };
+
+    let x_height = font.int_property(ids::X_HEIGHT).or_else(|| strike.glyph_top('x')).unwrap_or(0);
+    let cap_height = font.int_property(ids::CAP_HEIGHT).or_else(|| strike.glyph_top('H')).unwrap_or(0);
+
+    let y_max = strike.inked().map(|s| i32::from(s.bearing_y)).max().unwrap_or(0);
+    let y_min = strike.inked().map(|s| s.bottom()).min().unwrap_or(0);
+
+    let first = strike.map.first().map_or(0, |&(c, _)| c.min(0xffff));
+    let last = strike.map.last().map_or(0, |&(c, _)| c.min(0xffff));
+    let default_char = font.int_property(ids::DEFAULT_CHAR)
+                           .and_then(|code| if font.xlfd.is_unicode() {
+                               char::from_u32(code as u32)
+                           } else {
+                               font.charset().ok()?.to_unicode(code as u32)
+                           })
+                           .map_or(0, |c| (c as u32).min(0xffff));
+
+    let selection = match (strike.bold(), strike.font.xlfd.is_italic()) {
+        (false, false) => 0x0040,
+        (bold, italic) => u16::from(italic) | u16::from(bold) << 5,
+    } | u16::from(matches!(strike.slant().to_ascii_uppercase().as_str(), "O" | "RO")) << 9;
+
+    let mut out = Out::new();
+    out.u16(4);
+    out.i16(average as i16);
+    out.u16(font.xlfd.weight_class());
+    out.u16(font.xlfd.width_class());
+    out.u16(0); // installable
+    // subscript and superscript size and offset in the usual proportions of the em
+    out.i16((em * 65 / 100) as i16);
+    out.i16((em * 70 / 100) as i16);
+    out.i16(0);
+    out.i16((em * 14 / 100) as i16);
+    out.i16((em * 65 / 100) as i16);
+    out.i16((em * 70 / 100) as i16);
+    out.i16(0);
+    out.i16((em * 48 / 100) as i16);
+    out.i16(strike.units(1)?);
+    out.i16(strike.units(if x_height > 0 { x_height / 2 } else { strike.ascent / 3 })?);
+    out.i16(0);
+    out.bytes(&[0; 10]); // panose
+    for _ in 0..4 {
+        out.u32(0); // unicode ranges
+    }
+    out.bytes(b"    ");
+    out.u16(selection);
+    out.u16(first as u16);
+    out.u16(last as u16);
+    out.i16(strike.units(strike.ascent)?);
+    out.i16(strike.units(-strike.descent)?);
+    out.i16(0);
+    out.u16(strike.units(strike.ascent.max(y_max))? as u16);
+    out.u16(strike.units(strike.descent.max(-y_min))? as u16);
+    out.u32(0);
+    out.u32(0); // code pages
+    out.i16(strike.units(x_height)?);
+    out.i16(strike.units(cap_height)?);
+    out.u16(default_char as u16);
+    out.u16(0x20);
+    out.u16(1);
+
+    Ok(out)
+}
+
+const POST_MAX_NAMES: usize = 32768 - 258;
+
+fn write_post(strike: &Strike) -> Result<Out, Error> {
+    let font = strike.font;
+    // X puts UNDERLINE_POSITION below the baseline, post above it
+    let position = font.int_property(ids::UNDERLINE_POSITION).unwrap_or((strike.descent / 2).max(1));
+    let thickness = font.int_property(ids::UNDERLINE_THICKNESS).unwrap_or(1);
+
+    // name indices start at 258 and stop short of 32768, fonts with more glyphs than that leave names out
+    let named = strike.names.len() - 1 <= POST_MAX_NAMES;
+
+    let mut out = Out::new();
+    out.u32(if named { 0x00020000 } else { 0x00030000 });
+    out.u32(0); // italic angle
+    out.i16(strike.units(-position)?);
+    out.i16(strike.units(thickness)?);
+    out.u32(u32::from(strike.fixed_pitch()));
+    for _ in 0..4 {
+        out.u32(0);
+    }
+
+    if !named {
+        return Ok(out);
+    }
+
+    out.u16(strike.names.len() as u16);
+    out.u16(0); // .notdef is the first of the standard macintosh names
+    for i in 1..strike.names.len() {
+        out.u16((258 + i - 1) as u16);
+    }
+    for name in &strike.names[1..] {
+        out.u8(name.len() as u8);
+        out.bytes(name.as_bytes());
+    }
+
+    Ok(out)
+}
+
+//
+
+// an sfnt holding only a bitmap strike, as loaded by FreeType and fontconfig
+pub fn write_font<W: Write>(mut writer: W, font: &Font, options: &OtbOptions) -> Result<W, Error> {
+    font.validate()?;
+
+    let strike = Strike::new(font)?;
+
+    let mut tables: Vec<(&[u8; 4], Out)> = vec![
+        (b"EBDT", write_ebdt(&strike)),
+        (b"EBLC", write_eblc(&strike)?),
+        (b"OS/2", write_os2(&strike)?),
+        (b"cmap", write_cmap(&strike)?),
+        (b"head", write_head(&strike)?),
+        (b"hhea", write_hhea(&strike)?),
+        (b"hmtx", write_hmtx(&strike)?),
+        (b"maxp", write_maxp(&strike)),
+        (b"name", write_name(&strike)),
+        (b"post", write_post(&strike)?),
+    ];
+    if !options.scaled_sizes.is_empty() {
+        tables.push((b"EBSC", write_ebsc(&strike, &options.scaled_sizes)?));
+    }
+    tables.sort_by_key(|&(tag, _)| *tag);
+
+    let mut out = Out::new();
+    let (range, selector, shift) = search_params(tables.len(), 16);
+    out.u32(0x00010000);
+    out.u16(tables.len() as u16);
+    out.u16(range);
+    out.u16(selector);
+    out.u16(shift);
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_at = 0;
+    for (tag, table) in &tables {
+        if *tag == b"head" {
+            head_at = offset;
+        }
+
+        out.bytes(*tag);
+        out.u32(checksum(&table.0));
+        out.u32(offset as u32);
+        out.u32(table.len() as u32);
+        offset += table.len().next_multiple_of(4);
+    }
+
+    for (_, table) in &tables {
+        out.bytes(&table.0);
+        while out.len() % 4 != 0 {
+            out.u8(0);
+        }
+    }
+
+    let adjustment = 0xb1b0afba_u32.wrapping_sub(checksum(&out.0));
+    out.0[head_at + 8..head_at + 12].copy_from_slice(&adjustment.to_be_bytes());
+
+    writer.write_all(&out.0)?;
+    writer.flush()?;
+
+    Ok(writer)
+}
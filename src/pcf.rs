@@ -0,0 +1,1180 @@
+use std::{
+    convert::TryFrom,
+    io::{
+        Read,
+        Write,
+    },
+};
+
+use flate2::read::GzDecoder;
+
+use crate::{
+    ids,
+    BdfBlock,
+    Bitmap,
+    BoundingBox,
+    Encoding,
+    Error,
+    Font,
+    FontSize,
+    Glyph,
+    MetricsSet,
+    Property,
+    PropertyValue,
+    XYPair,
+    Xlfd,
+};
+
+//
+
+pub(crate) const PCF_PROPERTIES: u32 = 1 << 0;
+pub(crate) const PCF_ACCELERATORS: u32 = 1 << 1;
+pub(crate) const PCF_METRICS: u32 = 1 << 2;
+pub(crate) const PCF_BITMAPS: u32 = 1 << 3;
+pub(crate) const PCF_INK_METRICS: u32 = 1 << 4;
+pub(crate) const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+pub(crate) const PCF_SWIDTHS: u32 = 1 << 6;
+pub(crate) const PCF_GLYPH_NAMES: u32 = 1 << 7;
+pub(crate) const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+pub(crate) const PCF_DEFAULT_FORMAT: u32 = 0x000;
+pub(crate) const PCF_ACCEL_W_INKBOUNDS: u32 = 0x100;
+pub(crate) const PCF_COMPRESSED_METRICS: u32 = 0x100;
+pub(crate) const PCF_FORMAT_MASK: u32 = 0xffff_ff00;
+
+pub(crate) const PCF_GLYPH_PAD_MASK: u32 = 3;
+pub(crate) const PCF_BYTE_MASK: u32 = 1 << 2;
+pub(crate) const PCF_BIT_MASK: u32 = 1 << 3;
+pub(crate) const PCF_SCAN_UNIT_MASK: u32 = 3 << 4;
+
+pub(crate) const MAGIC: &[u8; 4] = b"\x01fcp";
+const GZIP_MAGIC: &[u8; 2] = b"\x1f\x8b";
+// well past any real font, so a small .pcf.gz cannot inflate without end
+const MAX_INFLATED: u64 = 32 << 20;
+
+const NO_GLYPH: u16 = 0xffff;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::InvalidFormat("pcf", msg)
+}
+
+//
+
+struct Table {
+    kind: u32,
+    format: u32,
+    size: u32,
+    offset: u32,
+}
+
+// reads the integers of one table, in the byte order its format asks for
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    msb: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            msb: false,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.pos + len > self.data.len() {
+            return Err(invalid("table is truncated"));
+        }
+
+        let bytes = &self.data[self.pos..(self.pos + len)];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let b = self.bytes(2)?;
+        let b = [b[0], b[1]];
+        Ok(if self.msb { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    }
+
+    fn i16(&mut self) -> Result<i16, Error> {
+        self.u16().map(|v| v as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let b = self.bytes(4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.msb { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        self.u32().map(|v| v as i32)
+    }
+
+    fn count(&mut self) -> Result<usize, Error> {
+        let ct = self.u32()? as usize;
+        if ct > self.data.len() {
+            return Err(invalid("count is larger than its table"));
+        }
+
+        Ok(ct)
+    }
+
+    // the format word is always little endian, everything after it follows the format
+    fn format(&mut self, allowed: &[u32]) -> Result<u32, Error> {
+        self.msb = false;
+        let format = self.u32()?;
+        if !allowed.contains(&(format & PCF_FORMAT_MASK)) {
+            return Err(invalid("table has an unexpected format"));
+        }
+
+        self.msb = format & PCF_BYTE_MASK != 0;
+        Ok(format)
+    }
+}
+
+fn string_at(pool: &[u8], offset: usize) -> Result<String, Error> {
+    let rest = pool.get(offset..).ok_or_else(|| invalid("string offset out of range"))?;
+    let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+    Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+//
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Metrics {
+    pub left: i16,
+    pub right: i16,
+    pub width: i16,
+    pub ascent: i16,
+    pub descent: i16,
+    pub attributes: u16,
+}
+
+impl Metrics {
+    fn read(c: &mut Cursor, compressed: bool) -> Result<Self, Error> {
+        if compressed {
+            let mut next = || c.u8().map(|v| i16::from(v) - 0x80);
+            Ok(Self {
+                left: next()?,
+                right: next()?,
+                width: next()?,
+                ascent: next()?,
+                descent: next()?,
+                attributes: 0,
+            })
+        } else {
+            Ok(Self {
+                left: c.i16()?,
+                right: c.i16()?,
+                width: c.i16()?,
+                ascent: c.i16()?,
+                descent: c.i16()?,
+                attributes: c.u16()?,
+            })
+        }
+    }
+
+    pub fn bitmap_width(&self) -> usize {
+        (i32::from(self.right) - i32::from(self.left)).max(0) as usize
+    }
+
+    pub fn bitmap_height(&self) -> usize {
+        (i32::from(self.ascent) + i32::from(self.descent)).max(0) as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Accelerators {
+    pub no_overlap: bool,
+    pub constant_metrics: bool,
+    pub terminal_font: bool,
+    pub constant_width: bool,
+    pub ink_inside: bool,
+    pub ink_metrics: bool,
+    pub draw_right_to_left: bool,
+    pub font_ascent: i32,
+    pub font_descent: i32,
+    pub max_overlap: i32,
+    pub min_bounds: Metrics,
+    pub max_bounds: Metrics,
+    pub ink_min_bounds: Metrics,
+    pub ink_max_bounds: Metrics,
+}
+
+impl Accelerators {
+    fn read(c: &mut Cursor) -> Result<Self, Error> {
+        let format = c.format(&[PCF_DEFAULT_FORMAT, PCF_ACCEL_W_INKBOUNDS])?;
+
+        let mut accel = Self {
+            no_overlap: c.u8()? != 0,
+            constant_metrics: c.u8()? != 0,
+            terminal_font: c.u8()? != 0,
+            constant_width: c.u8()? != 0,
+            ink_inside: c.u8()? != 0,
+            ink_metrics: c.u8()? != 0,
+            draw_right_to_left: c.u8()? != 0,
+            ..Self::default()
+        };
+        c.skip(1)?;
+
+        accel.font_ascent = c.i32()?;
+        accel.font_descent = c.i32()?;
+        accel.max_overlap = c.i32()?;
+        accel.min_bounds = Metrics::read(c, false)?;
+        accel.max_bounds = Metrics::read(c, false)?;
+
+        if format & PCF_FORMAT_MASK == PCF_ACCEL_W_INKBOUNDS {
+            accel.ink_min_bounds = Metrics::read(c, false)?;
+            accel.ink_max_bounds = Metrics::read(c, false)?;
+        } else {
+            accel.ink_min_bounds = accel.min_bounds;
+            accel.ink_max_bounds = accel.max_bounds;
+        }
+
+        Ok(accel)
+    }
+}
+
+//
+
+// rewrites glyph data to one byte per eight pixels, leftmost pixel in the high bit
+pub(crate) fn normalize_bits(data: &mut [u8], format: u32) {
+    let msb_byte = format & PCF_BYTE_MASK != 0;
+    let msb_bit = format & PCF_BIT_MASK != 0;
+    let unit = 1 << ((format & PCF_SCAN_UNIT_MASK) >> 4);
+
+    if msb_byte != msb_bit && unit > 1 {
+        for chunk in data.chunks_exact_mut(unit) {
+            chunk.reverse();
+        }
+    }
+
+    if !msb_bit {
+        for b in data.iter_mut() {
+            *b = b.reverse_bits();
+        }
+    }
+}
+
+pub(crate) fn row_bytes(width: usize, pad: usize) -> usize {
+    width.div_ceil(8).div_ceil(pad) * pad
+}
+
+//
+
+struct Tables<'a> {
+    data: &'a [u8],
+    tables: Vec<Table>,
+}
+
+impl<'a> Tables<'a> {
+    fn read(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 8 || &data[0..4] != MAGIC {
+            return Err(invalid("missing pcf header"));
+        }
+
+        let mut c = Cursor::new(data);
+        c.skip(4)?;
+        let ct = c.count()?;
+
+        let mut tables = Vec::with_capacity(ct);
+        for _ in 0..ct {
+            let table = Table {
+                kind: c.u32()?,
+                format: c.u32()?,
+                size: c.u32()?,
+                offset: c.u32()?,
+            };
+
+            if table.offset as usize + table.size as usize > data.len() {
+                return Err(invalid("table lies outside the file"));
+            }
+
+            tables.push(table);
+        }
+
+        Ok(Self {
+            data,
+            tables,
+        })
+    }
+
+    // the table of a kind, if present and its format word agrees with the table of contents
+    fn get(&self, kind: u32) -> Result<Option<Cursor<'a>>, Error> {
+        let table = match self.tables.iter().find(|t| t.kind == kind) {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        let data = &self.data[(table.offset as usize)..((table.offset + table.size) as usize)];
+        if data.len() < 4 || u32::from_le_bytes([data[0], data[1], data[2], data[3]]) != table.format {
+            return Err(invalid("table format disagrees with the table of contents"));
+        }
+
+        Ok(Some(Cursor::new(data)))
+    }
+
+    fn require(&self, kind: u32, name: &'static str) -> Result<Cursor<'a>, Error> {
+        self.get(kind)?.ok_or_else(|| invalid(name))
+    }
+}
+
+fn read_properties(mut c: Cursor) -> Result<Vec<Property>, Error> {
+    c.format(&[PCF_DEFAULT_FORMAT])?;
+    let ct = c.count()?;
+
+    let mut raw = Vec::with_capacity(ct);
+    for _ in 0..ct {
+        let name = c.u32()? as usize;
+        let is_string = c.u8()? != 0;
+        let value = c.i32()?;
+        raw.push((name, is_string, value));
+    }
+
+    if ct & 3 != 0 {
+        c.skip(4 - (ct & 3))?;
+    }
+
+    let pool_size = c.count()?;
+    let pool = c.bytes(pool_size)?;
+
+    raw.into_iter()
+       .map(|(name, is_string, value)| {
+           let value = if is_string {
+               PropertyValue::Str(string_at(pool, value as usize)?)
+           } else {
+               PropertyValue::Int(value)
+           };
+
+           Ok(Property::new(&string_at(pool, name)?, &value))
+       })
+       .collect()
+}
+
+fn read_metrics(mut c: Cursor) -> Result<Vec<Metrics>, Error> {
+    let format = c.format(&[PCF_DEFAULT_FORMAT, PCF_COMPRESSED_METRICS])?;
+    let compressed = format & PCF_FORMAT_MASK == PCF_COMPRESSED_METRICS;
+
+    let ct = if compressed { c.u16()? as usize } else { c.count()? };
+
+    (0..ct).map(|_| Metrics::read(&mut c, compressed)).collect()
+}
+
+fn read_bitmaps(mut c: Cursor, metrics: &[Metrics]) -> Result<Vec<Bitmap>, Error> {
+    let format = c.format(&[PCF_DEFAULT_FORMAT])?;
+    let ct = c.count()?;
+    if ct != metrics.len() {
+        return Err(invalid("bitmap and metrics counts differ"));
+    }
+
+    let offsets = (0..ct).map(|_| c.u32().map(|o| o as usize))
+                         .collect::<Result<Vec<_>, _>>()?;
+
+    let mut sizes = [0; 4];
+    for size in &mut sizes {
+        *size = c.u32()? as usize;
+    }
+
+    let pad_index = (format & PCF_GLYPH_PAD_MASK) as usize;
+    let pad = 1 << pad_index;
+
+    let mut data = c.bytes(sizes[pad_index])?.to_vec();
+    normalize_bits(&mut data, format);
+
+    metrics.iter().zip(offsets).map(|(m, offset)| {
+        let (width, height) = (m.bitmap_width(), m.bitmap_height());
+        let stride = row_bytes(width, pad);
+
+        if offset + stride * height > data.len() {
+            return Err(invalid("glyph bitmap lies outside the bitmap data"));
+        }
+
+        let mut bitmap = Bitmap::new(width, height);
+        for y in 0..height {
+            let row = &data[(offset + y * stride)..(offset + (y + 1) * stride)];
+            for x in 0..width {
+                if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                    bitmap.set(x, y, true);
+                }
+            }
+        }
+
+        Ok(bitmap)
+    }).collect()
+}
+
+// the encoding of every glyph index, as the lowest code pointing at it
+fn read_encodings(mut c: Cursor, glyph_ct: usize) -> Result<(Vec<Option<u32>>, Option<u32>), Error> {
+    c.format(&[PCF_DEFAULT_FORMAT])?;
+
+    let min_byte2 = c.u16()? as u32;
+    let max_byte2 = c.u16()? as u32;
+    let min_byte1 = c.u16()? as u32;
+    let max_byte1 = c.u16()? as u32;
+    let default_char = c.u16()?;
+
+    if min_byte2 > max_byte2 || min_byte1 > max_byte1 {
+        return Err(invalid("encoding ranges are reversed"));
+    }
+
+    let mut codes = vec![None; glyph_ct];
+    let mut default = None;
+
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            let index = c.u16()?;
+            if index == NO_GLYPH {
+                continue;
+            }
+
+            let code = (byte1 << 8) | byte2;
+            if let Some(slot) = codes.get_mut(index as usize) {
+                slot.get_or_insert(code);
+            }
+            if default_char != NO_GLYPH && code == u32::from(default_char) {
+                default = Some(code);
+            }
+        }
+    }
+
+    Ok((codes, default))
+}
+
+fn read_swidths(mut c: Cursor) -> Result<Vec<i32>, Error> {
+    c.format(&[PCF_DEFAULT_FORMAT])?;
+    let ct = c.count()?;
+
+    (0..ct).map(|_| c.i32()).collect()
+}
+
+fn read_glyph_names(mut c: Cursor) -> Result<Vec<String>, Error> {
+    c.format(&[PCF_DEFAULT_FORMAT])?;
+    let ct = c.count()?;
+
+    let offsets = (0..ct).map(|_| c.u32().map(|o| o as usize))
+                         .collect::<Result<Vec<_>, _>>()?;
+
+    let pool_size = c.count()?;
+    let pool = c.bytes(pool_size)?;
+
+    offsets.into_iter().map(|o| string_at(pool, o)).collect()
+}
+
+//
+
+fn find_int(properties: &[Property], name: &str) -> Option<i32> {
+    properties.iter().find(|p| p.name == name).and_then(|p| match p.value {
+        PropertyValue::Int(v) => Some(v),
+        _ => None,
+    })
+}
+
+pub fn parse_font(data: &[u8]) -> Result<Font, Error> {
+    if data.starts_with(GZIP_MAGIC) {
+        let mut plain = Vec::new();
+        GzDecoder::new(data).take(MAX_INFLATED + 1).read_to_end(&mut plain)?;
+        if plain.len() as u64 > MAX_INFLATED {
+            return Err(invalid("compressed data inflates past the size limit"));
+        }
+        return parse_font(&plain);
+    }
+
+    let tables = Tables::read(data)?;
+
+    let mut properties = read_properties(tables.require(PCF_PROPERTIES, "missing properties table")?)?;
+    let metrics = read_metrics(tables.require(PCF_METRICS, "missing metrics table")?)?;
+    let bitmaps = read_bitmaps(tables.require(PCF_BITMAPS, "missing bitmaps table")?, &metrics)?;
+    let (codes, default_char) = read_encodings(tables.require(PCF_BDF_ENCODINGS, "missing encodings table")?,
+                                               metrics.len())?;
+
+    // ink metrics only describe the bitmaps already read, they are checked but not kept
+    if let Some(c) = tables.get(PCF_INK_METRICS)? {
+        if read_metrics(c)?.len() != metrics.len() {
+            return Err(invalid("ink metrics and metrics counts differ"));
+        }
+    }
+
+    let swidths = match tables.get(PCF_SWIDTHS)? {
+        Some(c) => Some(read_swidths(c)?),
+        None => None,
+    };
+    let names = match tables.get(PCF_GLYPH_NAMES)? {
+        Some(c) => Some(read_glyph_names(c)?),
+        None => None,
+    };
+    let accel_table = match tables.get(PCF_BDF_ACCELERATORS)? {
+        Some(c) => Some(c),
+        None => tables.get(PCF_ACCELERATORS)?,
+    };
+    let accelerators = match accel_table {
+        Some(mut c) => Some(Accelerators::read(&mut c)?),
+        None => None,
+    };
+
+    // FONT is a BDF keyword rather than a property
+    let name = match properties.iter().position(|p| p.name == ids::FONT) {
+        Some(i) => match properties.remove(i).value {
+            PropertyValue::Str(name) => Some(name),
+            PropertyValue::Int(_) => return Err(invalid("FONT property is not a string")),
+        },
+        None => None,
+    };
+
+    if let Some(accel) = &accelerators {
+        let mut add = |name: &str, value: i32| {
+            if find_int(&properties, name).is_none() {
+                properties.push(Property::new(name, &PropertyValue::Int(value)));
+            }
+        };
+
+        add(ids::FONT_ASCENT, accel.font_ascent);
+        add(ids::FONT_DESCENT, accel.font_descent);
+    }
+    if let Some(code) = default_char {
+        if find_int(&properties, ids::DEFAULT_CHAR).is_none() {
+            properties.push(Property::new(ids::DEFAULT_CHAR, &PropertyValue::Int(code as i32)));
+        }
+    }
+
+    let mut xlfd = Xlfd::empty();
+    properties.retain(|p| !xlfd.set_property(p));
+
+    let name = name.unwrap_or_else(|| xlfd.to_string());
+
+    let res_x = xlfd.resolution_x.unwrap_or(75);
+    let res_y = xlfd.resolution_y.unwrap_or(75);
+
+    let (min, max) = match &accelerators {
+        Some(accel) => (accel.min_bounds, accel.max_bounds),
+        None => metrics.iter().fold((Metrics::default(), Metrics::default()), |(min, max), m| {
+            (Metrics { left: min.left.min(m.left), ..min },
+             Metrics {
+                 right: max.right.max(m.right),
+                 ascent: max.ascent.max(m.ascent),
+                 descent: max.descent.max(m.descent),
+                 ..max
+             })
+        }),
+    };
+
+    let bounding_box = BoundingBox {
+        width: (i32::from(max.right) - i32::from(min.left)).max(0) as u32,
+        height: (i32::from(max.ascent) + i32::from(max.descent)).max(0) as u32,
+        x_offset: i32::from(min.left),
+        y_offset: -i32::from(max.descent),
+    };
+
+    // PCF has no SIZE line, fall back on the pixel size when POINT_SIZE is missing
+    let point_size = match xlfd.point_size {
+        Some(decipoints) => (decipoints.saturating_add(5) / 10).max(0) as u32,
+        None => {
+            let pixels = xlfd.pixel_size
+                             .or_else(|| accelerators.map(|a| a.font_ascent.saturating_add(a.font_descent)))
+                             .unwrap_or(bounding_box.height as i32);
+            (f64::from(pixels) * 72.0 / f64::from(res_y.max(1))).round().max(0.0) as u32
+        }
+    };
+
+    let glyphs = metrics.iter().zip(bitmaps).enumerate().map(|(i, (m, bitmap))| {
+        let code = codes[i];
+
+        let scalable_width = match (&swidths, xlfd.point_size) {
+            (Some(swidths), _) => swidths.get(i).copied(),
+            (None, Some(decipoints)) if decipoints > 0 && res_x > 0 => {
+                let sw = f64::from(m.width) * 720_000.0 / (f64::from(decipoints) * f64::from(res_x));
+                Some(sw.round() as i32)
+            }
+            _ => None,
+        };
+
+        let name = match (&names, code) {
+            (Some(names), _) if i < names.len() => names[i].clone(),
+            (_, Some(code)) => format!("char{}", code),
+            _ => format!("glyph{}", i),
+        };
+
+        Glyph {
+            name,
+            encoding: code.map(Encoding::Standard).unwrap_or(Encoding::Unencoded),
+            bounding_box: BoundingBox {
+                width: m.bitmap_width() as u32,
+                height: m.bitmap_height() as u32,
+                x_offset: i32::from(m.left),
+                y_offset: -i32::from(m.descent),
+            },
+            bitmap,
+            metrics: MetricsSet::Normal,
+
+            scalable_width: scalable_width.map(|sw| XYPair::new(sw, 0)),
+            device_width: Some(XYPair::new(i32::from(m.width), 0)),
+            scalable_width_alt: None,
+            device_width_alt: None,
+            vector: None,
+
+            source: None,
+        }
+    }).collect();
+
+    Ok(Font {
+        bdf_version: String::from("2.1"),
+        name,
+        size: FontSize {
+            point_size,
+            x_dpi: res_x as u32,
+            y_dpi: res_y as u32,
+        },
+        bounding_box,
+        metrics: MetricsSet::Normal,
+
+        comments: Vec::new(),
+        properties,
+        glyphs,
+
+        content_version: None,
+        scalable_width: None,
+        device_width: None,
+        scalable_width_alt: None,
+        device_width_alt: None,
+        vector: None,
+
+        xlfd,
+
+        source: None,
+    })
+}
+
+pub fn read_font<R: Read>(mut reader: R) -> Result<Font, Error> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    parse_font(&data)
+}
+
+//
+
+#[derive(Copy, Clone, Debug)]
+pub struct PcfOptions {
+    // bytes every bitmap row is padded to, 1, 2, 4 or 8
+    pub glyph_pad: usize,
+    // bytes swapped as one unit when byte and bit order differ, 1, 2 or 4
+    pub scan_unit: usize,
+    pub msb_bit_first: bool,
+    pub msb_byte_first: bool,
+}
+
+// what bdftopcf does when given no options
+impl Default for PcfOptions {
+    fn default() -> Self {
+        Self {
+            glyph_pad: 4,
+            scan_unit: 1,
+            msb_bit_first: true,
+            msb_byte_first: true,
+        }
+    }
+}
+
+impl PcfOptions {
+    fn format(&self) -> Result<u32, Error> {
+        let pad = match self.glyph_pad {
+            1 => 0,
+            2 => 1,
+            4 => 2,
+            8 => 3,
+            _ => return Err(invalid("glyph pad must be 1, 2, 4 or 8")),
+        };
+        let unit = match self.scan_unit {
+            1 => 0,
+            2 => 1,
+            4 => 2,
+            _ => return Err(invalid("scan unit must be 1, 2 or 4")),
+        };
+        if self.scan_unit > self.glyph_pad {
+            return Err(invalid("scan unit cannot be larger than the glyph pad"));
+        }
+
+        let mut format = pad | (unit << 4);
+        if self.msb_byte_first {
+            format |= PCF_BYTE_MASK;
+        }
+        if self.msb_bit_first {
+            format |= PCF_BIT_MASK;
+        }
+
+        Ok(format)
+    }
+}
+
+// one table being written, starting with its little endian format word
+struct Out {
+    data: Vec<u8>,
+    msb: bool,
+}
+
+impl Out {
+    fn new(format: u32) -> Self {
+        Self {
+            data: format.to_le_bytes().to_vec(),
+            msb: format & PCF_BYTE_MASK != 0,
+        }
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        let b = if self.msb { v.to_be_bytes() } else { v.to_le_bytes() };
+        self.data.extend_from_slice(&b);
+    }
+
+    fn i16(&mut self, v: i16) {
+        self.u16(v as u16);
+    }
+
+    fn u32(&mut self, v: u32) {
+        let b = if self.msb { v.to_be_bytes() } else { v.to_le_bytes() };
+        self.data.extend_from_slice(&b);
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.u32(v as u32);
+    }
+
+    fn count(&mut self, ct: usize) -> Result<(), Error> {
+        let ct = u32::try_from(ct).map_err(|_| invalid("too many entries for a table"))?;
+        self.u32(ct);
+        Ok(())
+    }
+
+    fn metrics(&mut self, m: &Metrics, compressed: bool) {
+        if compressed {
+            for v in [m.left, m.right, m.width, m.ascent, m.descent].iter() {
+                self.u8((v + 0x80) as u8);
+            }
+        } else {
+            self.i16(m.left);
+            self.i16(m.right);
+            self.i16(m.width);
+            self.i16(m.ascent);
+            self.i16(m.descent);
+            self.u16(m.attributes);
+        }
+    }
+}
+
+impl Metrics {
+    fn compressible(&self) -> bool {
+        let fits = |v: i16| (-0x80..0x80).contains(&v);
+        fits(self.left) && fits(self.right) && fits(self.width) && fits(self.ascent) && fits(self.descent)
+    }
+
+    fn from_glyph(font: &Font, glyph: &Glyph) -> Result<Self, Error> {
+        let short = |v: i64| i16::try_from(v).map_err(|_| invalid("glyph metrics do not fit in 16 bits"));
+
+        let bbx = &glyph.bounding_box;
+        let width = glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0);
+
+        Ok(Self {
+            left: short(i64::from(bbx.x_offset))?,
+            right: short(i64::from(bbx.x_offset) + i64::from(bbx.width))?,
+            width: short(i64::from(width))?,
+            ascent: short(i64::from(bbx.y_offset) + i64::from(bbx.height))?,
+            descent: short(-i64::from(bbx.y_offset))?,
+            attributes: 0,
+        })
+    }
+
+    // the metrics of the inked part of the bitmap, blank glyphs get an empty box at the origin
+    fn ink(&self, bitmap: &Bitmap) -> Self {
+        let mut rows = None;
+        let mut cols = None;
+
+        for y in 0..bitmap.height() {
+            for x in 0..bitmap.width() {
+                if bitmap.get(x, y) == Some(true) {
+                    let (top, bottom) = rows.unwrap_or((y, y));
+                    rows = Some((top.min(y), bottom.max(y)));
+                    let (left, right) = cols.unwrap_or((x, x));
+                    cols = Some((left.min(x), right.max(x)));
+                }
+            }
+        }
+
+        match (rows, cols) {
+            (Some((top, bottom)), Some((left, right))) => Self {
+                left: self.left + left as i16,
+                right: self.left + right as i16 + 1,
+                ascent: self.ascent - top as i16,
+                descent: bottom as i16 + 1 - self.ascent,
+                ..*self
+            },
+            _ => Self {
+                right: self.left,
+                ascent: 0,
+                descent: 0,
+                ..*self
+            },
+        }
+    }
+}
+
+fn bounds(metrics: &[Metrics]) -> (Metrics, Metrics) {
+    let mut iter = metrics.iter();
+    let first = match iter.next() {
+        Some(&m) => m,
+        None => return (Metrics::default(), Metrics::default()),
+    };
+
+    iter.fold((first, first), |(min, max), m| {
+        (Metrics {
+            left: min.left.min(m.left),
+            right: min.right.min(m.right),
+            width: min.width.min(m.width),
+            ascent: min.ascent.min(m.ascent),
+            descent: min.descent.min(m.descent),
+            attributes: min.attributes.min(m.attributes),
+        },
+        Metrics {
+            left: max.left.max(m.left),
+            right: max.right.max(m.right),
+            width: max.width.max(m.width),
+            ascent: max.ascent.max(m.ascent),
+            descent: max.descent.max(m.descent),
+            attributes: max.attributes.max(m.attributes),
+        })
+    })
+}
+
+impl Accelerators {
+    // the same derivation the X server font library does
+    fn compute(font: &Font, metrics: &[Metrics], ink: &[Metrics]) -> Self {
+        let (min, max) = bounds(metrics);
+        let (ink_min, ink_max) = bounds(ink);
+
+        let font_ascent = font.int_property(ids::FONT_ASCENT).unwrap_or_else(|| i32::from(max.ascent));
+        let font_descent = font.int_property(ids::FONT_DESCENT).unwrap_or_else(|| i32::from(max.descent));
+
+        let max_overlap = metrics.iter()
+                                 .map(|m| i32::from(m.right) - i32::from(m.width))
+                                 .max()
+                                 .unwrap_or_else(|| i32::from(i16::MIN));
+
+        let constant_metrics = min == max;
+        let terminal_font = constant_metrics &&
+                            max.left == 0 &&
+                            max.right == max.width &&
+                            i32::from(max.ascent) == font_ascent &&
+                            i32::from(max.descent) == font_descent;
+
+        let ink_inside = min.left >= 0 &&
+                         max_overlap <= 0 &&
+                         i32::from(min.ascent) >= -font_descent &&
+                         i32::from(max.ascent) <= font_ascent &&
+                         -i32::from(min.descent) <= font_ascent &&
+                         i32::from(max.descent) <= font_descent;
+
+        Self {
+            no_overlap: max_overlap <= i32::from(min.left),
+            constant_metrics,
+            terminal_font,
+            constant_width: min.width == max.width,
+            ink_inside,
+            ink_metrics: metrics != ink,
+            draw_right_to_left: false,
+            font_ascent,
+            font_descent,
+            max_overlap,
+            min_bounds: min,
+            max_bounds: max,
+            ink_min_bounds: ink_min,
+            ink_max_bounds: ink_max,
+        }
+    }
+
+    fn write(&self, format: u32) -> Out {
+        let with_ink = if self.ink_metrics { PCF_ACCEL_W_INKBOUNDS } else { PCF_DEFAULT_FORMAT };
+        let mut out = Out::new(format | with_ink);
+
+        for flag in [self.no_overlap,
+                     self.constant_metrics,
+                     self.terminal_font,
+                     self.constant_width,
+                     self.ink_inside,
+                     self.ink_metrics,
+                     self.draw_right_to_left].iter() {
+            out.u8(*flag as u8);
+        }
+        out.u8(0);
+
+        out.i32(self.font_ascent);
+        out.i32(self.font_descent);
+        out.i32(self.max_overlap);
+        out.metrics(&self.min_bounds, false);
+        out.metrics(&self.max_bounds, false);
+        if self.ink_metrics {
+            out.metrics(&self.ink_min_bounds, false);
+            out.metrics(&self.ink_max_bounds, false);
+        }
+
+        out
+    }
+}
+
+fn write_properties(font: &Font, format: u32) -> Result<Out, Error> {
+    let mut properties = vec![Property::new(ids::FONT, &PropertyValue::Str(font.name.clone()))];
+    properties.extend(font.xlfd.properties());
+    properties.extend(font.properties.iter().cloned());
+
+    let mut pool = Vec::new();
+    let mut add = |s: &str| {
+        let offset = pool.len() as i32;
+        pool.extend_from_slice(s.as_bytes());
+        pool.push(0);
+        offset
+    };
+
+    let mut out = Out::new(format);
+    out.count(properties.len())?;
+    for p in &properties {
+        out.i32(add(&p.name));
+        match &p.value {
+            PropertyValue::Str(s) => {
+                out.u8(1);
+                out.i32(add(s));
+            }
+            PropertyValue::Int(v) => {
+                out.u8(0);
+                out.i32(*v);
+            }
+        }
+    }
+
+    if properties.len() & 3 != 0 {
+        out.data.resize(out.data.len() + 4 - (properties.len() & 3), 0);
+    }
+
+    out.count(pool.len())?;
+    out.data.extend_from_slice(&pool);
+
+    Ok(out)
+}
+
+fn write_metrics(metrics: &[Metrics], format: u32) -> Result<Out, Error> {
+    let compressed = metrics.iter().all(Metrics::compressible) && metrics.len() <= 0xffff;
+
+    let mut out = Out::new(format | if compressed { PCF_COMPRESSED_METRICS } else { PCF_DEFAULT_FORMAT });
+    if compressed {
+        out.u16(metrics.len() as u16);
+    } else {
+        out.count(metrics.len())?;
+    }
+    for m in metrics {
+        out.metrics(m, compressed);
+    }
+
+    Ok(out)
+}
+
+fn write_bitmaps(font: &Font, metrics: &[Metrics], options: &PcfOptions, format: u32) -> Result<Out, Error> {
+    let mut data = Vec::new();
+    let mut offsets = Vec::with_capacity(metrics.len());
+    let mut sizes = [0; 4];
+
+    for (glyph, m) in font.glyphs.iter().zip(metrics) {
+        let (width, height) = (m.bitmap_width(), m.bitmap_height());
+        let stride = row_bytes(width, options.glyph_pad);
+
+        for (i, size) in sizes.iter_mut().enumerate() {
+            *size += row_bytes(width, 1 << i) * height;
+        }
+
+        offsets.push(data.len());
+        for y in 0..height {
+            let mut row = vec![0u8; stride];
+            for x in 0..width {
+                if glyph.bitmap.get(x, y) == Some(true) {
+                    row[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            data.extend_from_slice(&row);
+        }
+    }
+
+    // the reordering is its own inverse
+    normalize_bits(&mut data, format);
+
+    let mut out = Out::new(format);
+    out.count(metrics.len())?;
+    for offset in offsets {
+        out.count(offset)?;
+    }
+    for size in sizes.iter() {
+        out.count(*size)?;
+    }
+    out.data.extend_from_slice(&data);
+
+    Ok(out)
+}
+
+// glyphs with codes past 16 bits or outside the charset have no place in the table,
+//   like bdftopcf they are kept unencoded
+fn write_encodings(font: &Font, format: u32) -> Result<Out, Error> {
+    let mut indices = std::collections::BTreeMap::new();
+    for (i, glyph) in font.glyphs.iter().enumerate() {
+        let code = match glyph.encoding {
+            Encoding::Standard(code) => code,
+            Encoding::NonStandard(_) | Encoding::Unencoded => continue,
+        };
+
+        if code <= 0xffff && !indices.contains_key(&code) {
+            // 0xffff marks a code without a glyph
+            let index = u16::try_from(i).ok().filter(|&i| i != NO_GLYPH).ok_or_else(|| {
+                Error::GlyphValidation(glyph.name.clone(), "too many glyphs before it to encode in pcf")
+            })?;
+            indices.insert(code, index);
+        }
+    }
+
+    let (mut min_byte1, mut max_byte1, mut min_byte2, mut max_byte2) = (0, 0, 0, 0);
+    if !indices.is_empty() {
+        let byte1 = indices.keys().map(|c| c >> 8);
+        min_byte1 = byte1.clone().min().unwrap();
+        max_byte1 = byte1.max().unwrap();
+        let byte2 = indices.keys().map(|c| c & 0xff);
+        min_byte2 = byte2.clone().min().unwrap();
+        max_byte2 = byte2.max().unwrap();
+    }
+
+    let default_char = font.properties
+                           .iter()
+                           .find(|p| p.name == ids::DEFAULT_CHAR)
+                           .and_then(|p| match p.value {
+                               PropertyValue::Int(v) => u16::try_from(v).ok(),
+                               _ => None,
+                           })
+                           .unwrap_or(NO_GLYPH);
+
+    let mut out = Out::new(format);
+    out.u16(min_byte2 as u16);
+    out.u16(max_byte2 as u16);
+    out.u16(min_byte1 as u16);
+    out.u16(max_byte1 as u16);
+    out.u16(default_char);
+
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            out.u16(*indices.get(&((byte1 << 8) | byte2)).unwrap_or(&NO_GLYPH));
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_swidths(font: &Font, metrics: &[Metrics], format: u32) -> Result<Out, Error> {
+    let decipoints = font.xlfd.point_size.unwrap_or(font.size.point_size as i32 * 10);
+    let res_x = font.xlfd.resolution_x.unwrap_or(font.size.x_dpi as i32);
+
+    let mut out = Out::new(format);
+    out.count(metrics.len())?;
+    for (glyph, m) in font.glyphs.iter().zip(metrics) {
+        let sw = match glyph.scalable_width.or(font.scalable_width) {
+            Some(sw) => sw.x,
+            None if decipoints > 0 && res_x > 0 => {
+                (f64::from(m.width) * 720_000.0 / (f64::from(decipoints) * f64::from(res_x))).round() as i32
+            }
+            None => 0,
+        };
+        out.i32(sw);
+    }
+
+    Ok(out)
+}
+
+fn write_glyph_names(font: &Font, format: u32) -> Result<Out, Error> {
+    let mut pool = Vec::new();
+
+    let mut out = Out::new(format);
+    out.count(font.glyphs.len())?;
+    for glyph in &font.glyphs {
+        out.count(pool.len())?;
+        pool.extend_from_slice(glyph.name.as_bytes());
+        pool.push(0);
+    }
+    out.count(pool.len())?;
+    out.data.extend_from_slice(&pool);
+
+    Ok(out)
+}
+
+pub fn write_font<W: Write>(mut writer: W, font: &Font, options: &PcfOptions) -> Result<W, Error> {
+    font.validate()?;
+
+    let format = options.format()?;
+
+    let metrics = font.glyphs
+                      .iter()
+                      .map(|g| Metrics::from_glyph(font, g))
+                      .collect::<Result<Vec<_>, _>>()?;
+    let ink = font.glyphs
+                  .iter()
+                  .zip(&metrics)
+                  .map(|(g, m)| m.ink(&g.bitmap))
+                  .collect::<Vec<_>>();
+    let accel = Accelerators::compute(font, &metrics, &ink);
+
+    let mut tables = vec![
+        (PCF_PROPERTIES, write_properties(font, format)?),
+        (PCF_ACCELERATORS, accel.write(format)),
+        (PCF_METRICS, write_metrics(&metrics, format)?),
+        (PCF_BITMAPS, write_bitmaps(font, &metrics, options, format)?),
+    ];
+    if accel.ink_metrics {
+        tables.push((PCF_INK_METRICS, write_metrics(&ink, format)?));
+    }
+    tables.push((PCF_BDF_ENCODINGS, write_encodings(font, format)?));
+    tables.push((PCF_SWIDTHS, write_swidths(font, &metrics, format)?));
+    tables.push((PCF_GLYPH_NAMES, write_glyph_names(font, format)?));
+    tables.push((PCF_BDF_ACCELERATORS, accel.write(format)));
+
+    for (_, table) in &mut tables {
+        let padded = table.data.len().div_ceil(4) * 4;
+        table.data.resize(padded, 0);
+    }
+
+    // the table of contents is always little endian
+    let mut header = Out {
+        data: MAGIC.to_vec(),
+        msb: false,
+    };
+    header.count(tables.len())?;
+
+    let mut offset = 8 + 16 * tables.len();
+    for (kind, table) in &tables {
+        let table_format = u32::from_le_bytes([table.data[0], table.data[1], table.data[2], table.data[3]]);
+        header.u32(*kind);
+        header.u32(table_format);
+        header.count(table.data.len())?;
+        header.count(offset)?;
+        offset += table.data.len();
+    }
+
+    writer.write_all(&header.data)?;
+    for (_, table) in &tables {
+        writer.write_all(&table.data)?;
+    }
+    writer.flush()?;
+
+    Ok(writer)
+}
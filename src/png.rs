@@ -0,0 +1,130 @@
+use std::io::Write;
+
+use flate2::{
+    write::ZlibEncoder,
+    Compression,
+    Crc,
+};
+
+use crate::Error;
+
+//
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// 8 bits per channel, pixels are given row by row with the channels of each pixel together
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ColorType {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+}
+
+impl ColorType {
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Gray => 1,
+            ColorType::GrayAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            ColorType::Gray => 0,
+            ColorType::GrayAlpha => 4,
+            ColorType::Rgb => 2,
+            ColorType::Rgba => 6,
+        }
+    }
+}
+
+fn chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), Error> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc.sum().to_be_bytes())?;
+
+    Ok(())
+}
+
+// the sum of the bytes taken as signed differences, the usual heuristic for picking a row filter
+fn filter_cost(row: &[u8]) -> u64 {
+    row.iter().map(|&b| u64::from((b as i8).unsigned_abs())).sum()
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = ((p - i16::from(a)).abs(), (p - i16::from(b)).abs(), (p - i16::from(c)).abs());
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// each row filtered the way that leaves the smallest differences, prefixed by the filter type
+fn filter_rows(pixels: &[u8], stride: usize, bpp: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pixels.len() + pixels.len() / stride.max(1));
+    let blank = vec![0u8; stride];
+    let mut candidates = vec![vec![0u8; stride]; 5];
+
+    for (y, row) in pixels.chunks(stride).enumerate() {
+        let prior = if y == 0 { &blank[..] } else { &pixels[(y - 1) * stride..y * stride] };
+
+        for i in 0..stride {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = prior[i];
+            let c = if i >= bpp { prior[i - bpp] } else { 0 };
+
+            candidates[0][i] = row[i];
+            candidates[1][i] = row[i].wrapping_sub(a);
+            candidates[2][i] = row[i].wrapping_sub(b);
+            candidates[3][i] = row[i].wrapping_sub(((u16::from(a) + u16::from(b)) / 2) as u8);
+            candidates[4][i] = row[i].wrapping_sub(paeth(a, b, c));
+        }
+
+        let best = (0..5).min_by_key(|&f| filter_cost(&candidates[f])).unwrap_or(0);
+        out.push(best as u8);
+        out.extend_from_slice(&candidates[best]);
+    }
+
+    out
+}
+
+pub fn write_png<W: Write>(mut writer: W, width: u32, height: u32, color: ColorType, pixels: &[u8]) -> Result<W, Error> {
+    let stride = width as usize * color.channels();
+    if width == 0 || height == 0 || pixels.len() != stride * height as usize {
+        return Err(Error::InvalidFormat("png", "pixel data does not match the image size"));
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.push(8); // bit depth
+    header.push(color.code());
+    header.push(0); // deflate
+    header.push(0); // adaptive filtering
+    header.push(0); // not interlaced
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&filter_rows(pixels, stride, color.channels()))?;
+    let data = encoder.finish()?;
+
+    writer.write_all(&SIGNATURE)?;
+    chunk(&mut writer, b"IHDR", &header)?;
+    chunk(&mut writer, b"IDAT", &data)?;
+    chunk(&mut writer, b"IEND", &[])?;
+    writer.flush()?;
+
+    Ok(writer)
+}
@@ -0,0 +1,485 @@
+use std::{
+    collections::HashMap,
+    io::{
+        Read,
+        Write,
+    },
+};
+
+use crate::{
+    unicode_glyph_name,
+    Bitmap,
+    Encoding,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_MODEHASSEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_STARTSEQ: u16 = 0xfffe;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_HEADER_SIZE: u32 = 32;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_STARTSEQ: u8 = 0xfe;
+
+fn invalid(msg: &'static str) -> Error {
+    Error::InvalidFormat("psf", msg)
+}
+
+//
+
+// a run of code points, such as a letter and its combining marks, drawn with one glyph
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sequence {
+    pub glyph: String,
+    pub chars: Vec<char>,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PsfVersion {
+    Psf1,
+    Psf2,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PsfOptions {
+    pub version: PsfVersion,
+    // without a unicode table every glyph is stored at the position of its encoding
+    pub unicode_table: bool,
+}
+
+impl Default for PsfOptions {
+    fn default() -> Self {
+        Self {
+            version: PsfVersion::Psf2,
+            unicode_table: true,
+        }
+    }
+}
+
+//
+
+// one glyph as stored in the file, and the code points mapped to it
+struct Entry {
+    rows: Vec<u8>,
+    chars: Vec<char>,
+    sequences: Vec<Vec<char>>,
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn read_psf1(data: &[u8]) -> Result<(usize, usize, Vec<Entry>), Error> {
+    if data.len() < 4 {
+        return Err(invalid("header is truncated"));
+    }
+
+    let mode = data[2];
+    let height = data[3] as usize;
+    let count = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+    if height == 0 {
+        return Err(invalid("glyphs have no height"));
+    }
+
+    let glyph_data = data.get(4..(4 + count * height)).ok_or_else(|| invalid("glyph data is truncated"))?;
+    let mut entries: Vec<Entry> = glyph_data.chunks_exact(height)
+                                            .map(|rows| Entry { rows: rows.to_vec(), chars: Vec::new(), sequences: Vec::new() })
+                                            .collect();
+
+    if mode & (PSF1_MODEHASTAB | PSF1_MODEHASSEQ) != 0 {
+        let table = &data[(4 + count * height)..];
+        let mut values = table.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+
+        for entry in &mut entries {
+            let mut sequence: Option<Vec<char>> = None;
+
+            loop {
+                let value = values.next().ok_or_else(|| invalid("unicode table is truncated"))?;
+                match value {
+                    PSF1_SEPARATOR | PSF1_STARTSEQ => {
+                        if let Some(seq) = sequence.take() {
+                            entry.sequences.push(seq);
+                        }
+                        if value == PSF1_SEPARATOR {
+                            break;
+                        }
+                        sequence = Some(Vec::new());
+                    }
+                    u => {
+                        let c = char::from_u32(u32::from(u)).ok_or(Error::InvalidCodepoint(u32::from(u)))?;
+                        match &mut sequence {
+                            Some(seq) => seq.push(c),
+                            None => entry.chars.push(c),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((8, height, entries))
+}
+
+fn read_psf2(data: &[u8]) -> Result<(usize, usize, Vec<Entry>), Error> {
+    if data.len() < PSF2_HEADER_SIZE as usize {
+        return Err(invalid("header is truncated"));
+    }
+
+    let header_size = u32_at(data, 8) as usize;
+    let flags = u32_at(data, 12);
+    let count = u32_at(data, 16) as usize;
+    let glyph_size = u32_at(data, 20) as usize;
+    let height = u32_at(data, 24) as usize;
+    let width = u32_at(data, 28) as usize;
+
+    if header_size < PSF2_HEADER_SIZE as usize {
+        return Err(invalid("header is too small"));
+    }
+    if width == 0 || height == 0 {
+        return Err(invalid("glyphs have no size"));
+    }
+    if glyph_size != width.div_ceil(8) * height {
+        return Err(invalid("glyph size disagrees with width and height"));
+    }
+
+    let end = count.checked_mul(glyph_size)
+                   .and_then(|len| len.checked_add(header_size))
+                   .filter(|&end| end <= data.len())
+                   .ok_or_else(|| invalid("glyph data is truncated"))?;
+
+    let mut entries: Vec<Entry> = (0..count).map(|i| {
+        let at = header_size + i * glyph_size;
+        Entry { rows: data[at..(at + glyph_size)].to_vec(), chars: Vec::new(), sequences: Vec::new() }
+    }).collect();
+
+    if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+        let mut table = data[end..].split(|&b| b == PSF2_SEPARATOR);
+
+        for entry in &mut entries {
+            let desc = table.next().ok_or_else(|| invalid("unicode table is truncated"))?;
+            let mut parts = desc.split(|&b| b == PSF2_STARTSEQ);
+
+            let decode = |bytes: &[u8]| -> Result<Vec<char>, Error> {
+                std::str::from_utf8(bytes).map(|s| s.chars().collect())
+                                          .map_err(|_| invalid("unicode table is not utf-8"))
+            };
+
+            entry.chars = decode(parts.next().unwrap_or(&[]))?;
+            for seq in parts {
+                entry.sequences.push(decode(seq)?);
+            }
+        }
+    }
+
+    Ok((width, height, entries))
+}
+
+fn glyph_name(c: Option<char>, i: usize) -> String {
+    match c {
+        Some(c) => unicode_glyph_name(c as u32),
+        None => format!("glyph{}", i),
+    }
+}
+
+// PSF keeps no baseline, it goes under the ink of a letter or digit that sits on it,
+//   found by code point or else by its ASCII position, and a quarter of the cell up without one
+fn infer_descent(entries: &[Entry], bitmaps: &[Bitmap], unicode: bool) -> Option<i32> {
+    ['H', 'x', 'I', '0'].iter().find_map(|&c| {
+        let i = if unicode {
+            entries.iter().position(|e| e.chars.contains(&c))?
+        } else {
+            c as usize
+        };
+        let bitmap = bitmaps.get(i)?;
+
+        let bottom = (0..bitmap.height()).rev().find(|&y| (0..bitmap.width()).any(|x| bitmap.get(x, y) == Some(true)))?;
+        Some((bitmap.height() - 1 - bottom) as i32)
+    })
+}
+
+pub fn parse_font(data: &[u8]) -> Result<(Font, Vec<Sequence>), Error> {
+    let (width, height, entries) = if data.starts_with(&PSF1_MAGIC) {
+        read_psf1(data)?
+    } else if data.starts_with(&PSF2_MAGIC) {
+        read_psf2(data)?
+    } else {
+        return Err(invalid("missing psf header"));
+    };
+
+    let unicode = entries.iter().any(|e| !e.chars.is_empty() || !e.sequences.is_empty());
+
+    let stride = width.div_ceil(8);
+
+    let bitmaps: Vec<Bitmap> = entries.iter().map(|entry| {
+        let mut bitmap = Bitmap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if entry.rows[y * stride + x / 8] & (0x80 >> (x % 8)) != 0 {
+                    bitmap.set(x, y, true);
+                }
+            }
+        }
+        bitmap
+    }).collect();
+
+    let descent = infer_descent(&entries, &bitmaps, unicode).unwrap_or((height / 4) as i32);
+
+    let mut glyphs = Vec::new();
+    let mut sequences = Vec::new();
+
+    for (i, (entry, bitmap)) in entries.iter().zip(&bitmaps).enumerate() {
+
+        let mut codes: Vec<Option<char>> = entry.chars.iter().cloned().map(Some).collect();
+        if codes.is_empty() {
+            codes.push(None);
+        }
+
+        let first = glyph_name(codes[0], i);
+        for seq in &entry.sequences {
+            sequences.push(Sequence { glyph: first.clone(), chars: seq.clone() });
+        }
+
+        for c in codes {
+            let encoding = match c {
+                Some(c) => Encoding::Standard(c as u32),
+                None if unicode => Encoding::NonStandard(i as u32),
+                None => Encoding::Standard(i as u32),
+            };
+
+            glyphs.push(Glyph::from_cell(glyph_name(c, i), encoding, bitmap.clone(), descent));
+        }
+    }
+
+    Ok((Font::from_cells(glyphs, height as u32, descent, unicode), sequences))
+}
+
+pub fn read_font<R: Read>(mut reader: R) -> Result<(Font, Vec<Sequence>), Error> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    parse_font(&data)
+}
+
+//
+
+// draws a glyph into the font's cell, every glyph has to advance by exactly the cell width
+fn cell_rows(font: &Font, glyph: &Glyph) -> Result<Vec<u8>, Error> {
+    let cell = &font.bounding_box;
+    let (width, height) = (cell.width as usize, cell.height as usize);
+    let stride = width.div_ceil(8);
+
+    let advance = glyph.device_width.or(font.device_width).map(|d| d.x);
+    if advance != Some(cell.width as i32) {
+        return Err(Error::GlyphValidation(glyph.name.clone(),
+                                          "advance differs from the cell width, a proportional font cannot be stored as psf"));
+    }
+
+    let bbx = &glyph.bounding_box;
+    let left = i64::from(bbx.x_offset) - i64::from(cell.x_offset);
+    let top = (i64::from(cell.y_offset) + i64::from(cell.height)) - (i64::from(bbx.y_offset) + i64::from(bbx.height));
+
+    let mut rows = vec![0u8; stride * height];
+    for y in 0..glyph.bitmap.height() {
+        for x in 0..glyph.bitmap.width() {
+            if glyph.bitmap.get(x, y) != Some(true) {
+                continue;
+            }
+
+            let (cx, cy) = (left + x as i64, top + y as i64);
+            if cx < 0 || cy < 0 || cx >= width as i64 || cy >= height as i64 {
+                return Err(Error::GlyphValidation(glyph.name.clone(), "ink lies outside the font bounding box"));
+            }
+
+            rows[cy as usize * stride + cx as usize / 8] |= 0x80 >> (cx % 8);
+        }
+    }
+
+    Ok(rows)
+}
+
+fn build_entries(font: &Font, sequences: &[Sequence], options: &PsfOptions) -> Result<Vec<Entry>, Error> {
+    let cell = &font.bounding_box;
+    let blank = vec![0u8; (cell.width as usize).div_ceil(8) * cell.height as usize];
+
+    let mut entries: Vec<Entry> = Vec::new();
+
+    if !options.unicode_table {
+        for glyph in &font.glyphs {
+            let code = match glyph.encoding {
+                Encoding::Standard(code) => code as usize,
+                _ => continue,
+            };
+            if code >= 0x10000 {
+                return Err(Error::GlyphValidation(glyph.name.clone(), "encoding too large for a psf position"));
+            }
+
+            if entries.len() <= code {
+                entries.resize_with(code + 1, || Entry { rows: blank.clone(), chars: Vec::new(), sequences: Vec::new() });
+            }
+            entries[code].rows = cell_rows(font, glyph)?;
+        }
+
+        return Ok(entries);
+    }
+
+    if !font.xlfd.is_unicode() {
+        return Err(Error::FontValidation("a psf unicode table needs an ISO10646 font"));
+    }
+
+    // identical cells share one psf glyph
+    let mut by_rows: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut by_name: HashMap<&str, usize> = HashMap::new();
+
+    for glyph in &font.glyphs {
+        let c = font.glyph_char(glyph);
+        if c.is_none() && !sequences.iter().any(|s| s.glyph == glyph.name) {
+            continue;
+        }
+
+        let rows = cell_rows(font, glyph)?;
+        let index = *by_rows.entry(rows.clone()).or_insert_with(|| {
+            entries.push(Entry { rows, chars: Vec::new(), sequences: Vec::new() });
+            entries.len() - 1
+        });
+
+        if let Some(c) = c {
+            if !entries[index].chars.contains(&c) {
+                entries[index].chars.push(c);
+            }
+        }
+        by_name.entry(&glyph.name).or_insert(index);
+    }
+
+    for seq in sequences {
+        let index = by_name.get(seq.glyph.as_str())
+                           .ok_or_else(|| Error::GlyphValidation(seq.glyph.clone(), "sequence names a glyph not in the font"))?;
+        entries[*index].sequences.push(seq.chars.clone());
+    }
+
+    Ok(entries)
+}
+
+fn write_psf1(entries: &mut Vec<Entry>, font: &Font, options: &PsfOptions) -> Result<Vec<u8>, Error> {
+    if font.bounding_box.width != 8 {
+        return Err(Error::FontValidation("psf1 glyphs must be 8 pixels wide"));
+    }
+    if font.bounding_box.height > 255 {
+        return Err(Error::FontValidation("psf1 glyphs cannot be taller than 255 pixels"));
+    }
+    if entries.len() > 512 {
+        return Err(Error::FontValidation("psf1 holds at most 512 glyphs"));
+    }
+
+    let count = if entries.len() > 256 { 512 } else { 256 };
+    let blank = vec![0u8; font.bounding_box.height as usize];
+    entries.resize_with(count, || Entry { rows: blank.clone(), chars: Vec::new(), sequences: Vec::new() });
+
+    let has_seq = entries.iter().any(|e| !e.sequences.is_empty());
+
+    let mut mode = 0;
+    if count == 512 {
+        mode |= PSF1_MODE512;
+    }
+    if options.unicode_table {
+        mode |= PSF1_MODEHASTAB;
+    }
+    if has_seq {
+        mode |= PSF1_MODEHASSEQ;
+    }
+
+    let mut data = vec![PSF1_MAGIC[0], PSF1_MAGIC[1], mode, font.bounding_box.height as u8];
+    for entry in entries.iter() {
+        data.extend_from_slice(&entry.rows);
+    }
+
+    if options.unicode_table {
+        let mut push = |v: u16| data.extend_from_slice(&v.to_le_bytes());
+        let short = |c: char| -> Result<u16, Error> {
+            if (c as u32) < u32::from(PSF1_STARTSEQ) {
+                Ok(c as u16)
+            } else {
+                Err(Error::InvalidCodepoint(c as u32))
+            }
+        };
+
+        for entry in entries.iter() {
+            for &c in &entry.chars {
+                push(short(c)?);
+            }
+            for seq in &entry.sequences {
+                push(PSF1_STARTSEQ);
+                for &c in seq {
+                    push(short(c)?);
+                }
+            }
+            push(PSF1_SEPARATOR);
+        }
+    }
+
+    Ok(data)
+}
+
+fn write_psf2(entries: &[Entry], font: &Font, options: &PsfOptions) -> Result<Vec<u8>, Error> {
+    let (width, height) = (font.bounding_box.width, font.bounding_box.height);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&PSF2_MAGIC);
+    for v in [0,
+              PSF2_HEADER_SIZE,
+              if options.unicode_table { PSF2_HAS_UNICODE_TABLE } else { 0 },
+              entries.len() as u32,
+              width.div_ceil(8) * height,
+              height,
+              width].iter() {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+
+    for entry in entries {
+        data.extend_from_slice(&entry.rows);
+    }
+
+    if options.unicode_table {
+        let mut buf = [0; 4];
+        for entry in entries {
+            for c in &entry.chars {
+                data.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            for seq in &entry.sequences {
+                data.push(PSF2_STARTSEQ);
+                for c in seq {
+                    data.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            data.push(PSF2_SEPARATOR);
+        }
+    }
+
+    Ok(data)
+}
+
+pub fn write_font<W: Write>(mut writer: W, font: &Font, sequences: &[Sequence], options: &PsfOptions) -> Result<W, Error> {
+    if font.bounding_box.width == 0 || font.bounding_box.height == 0 {
+        return Err(Error::FontValidation("psf needs a non-empty font bounding box"));
+    }
+
+    let mut entries = build_entries(font, sequences, options)?;
+
+    let data = match options.version {
+        PsfVersion::Psf1 => write_psf1(&mut entries, font, options)?,
+        PsfVersion::Psf2 => write_psf2(&entries, font, options)?,
+    };
+
+    writer.write_all(&data)?;
+    writer.flush()?;
+
+    Ok(writer)
+}
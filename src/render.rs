@@ -0,0 +1,338 @@
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use crate::{
+    ids,
+    layout::{
+        glyph_ink,
+        is_combining,
+        Bounds,
+        Direction,
+    },
+    stack::Fallback,
+    unicode_glyph_name,
+    Bitmap,
+    BoundingBox,
+    Encoding,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+// something glyphs can be drawn on, pixels outside of it are ignored
+pub trait Canvas {
+    fn plot(&mut self, x: i32, y: i32);
+}
+
+impl Canvas for Bitmap {
+    fn plot(&mut self, x: i32, y: i32) {
+        if x >= 0 && y >= 0 {
+            self.set(x as usize, y as usize, true);
+        }
+    }
+}
+
+// one byte per pixel, ink is written as `ink` over whatever is there
+pub struct GrayCanvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub ink: u8,
+}
+
+impl GrayCanvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            ink: 255,
+        }
+    }
+}
+
+impl Canvas for GrayCanvas {
+    fn plot(&mut self, x: i32, y: i32) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.pixels[y as usize * self.width + x as usize] = self.ink;
+        }
+    }
+}
+
+// red, green, blue and alpha bytes per pixel, starting out transparent
+pub struct RgbaCanvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub color: [u8; 4],
+}
+
+impl RgbaCanvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+            color: [0, 0, 0, 255],
+        }
+    }
+}
+
+impl Canvas for RgbaCanvas {
+    fn plot(&mut self, x: i32, y: i32) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            let i = (y as usize * self.width + x as usize) * 4;
+            self.pixels[i..i + 4].copy_from_slice(&self.color);
+        }
+    }
+}
+
+//
+
+// draws the glyph with the pen at (x, y) on the baseline, y growing down the canvas
+pub(crate) fn draw_glyph<C: Canvas>(glyph: &Glyph, x: i32, y: i32, canvas: &mut C) {
+    let bitmap = &glyph.bitmap;
+    let left = x + glyph.bounding_box.x_offset;
+    let top = y - (glyph.bounding_box.y_offset + bitmap.height() as i32);
+
+    for row in 0..bitmap.height() {
+        for col in 0..bitmap.width() {
+            if bitmap.get(col, row) == Some(true) {
+                canvas.plot(left + col as i32, top + row as i32);
+            }
+        }
+    }
+}
+
+// joiners and variation selectors, which belong to the cluster before them but draw nothing
+pub(crate) fn is_selector(c: char) -> bool {
+    matches!(c as u32, 0x200c..=0x200d | 0xfe00..=0xfe0f | 0xe0100..=0xe01ef)
+}
+
+// the box of the whole bitmap relative to the pen, y growing down
+fn glyph_box(glyph: &Glyph) -> Bounds {
+    let bbx = &glyph.bounding_box;
+    let top = -(bbx.y_offset + glyph.bitmap.height() as i32);
+
+    Bounds {
+        left: bbx.x_offset,
+        top,
+        right: bbx.x_offset + glyph.bitmap.width() as i32,
+        bottom: -bbx.y_offset,
+    }
+}
+
+// a base character and the combining marks over it, each mark is centred on the ink of the base
+//   and stacked above or below the ink drawn so far, all relative to the pen of the base
+pub(crate) struct Cluster {
+    base: Option<Bounds>,
+    ink: Option<Bounds>,
+}
+
+impl Cluster {
+    pub(crate) fn new(base: Option<&Glyph>) -> Self {
+        let base = base.and_then(glyph_ink);
+
+        Self {
+            base,
+            ink: base,
+        }
+    }
+
+    // where to draw `mark` from the pen of the base, marks over bases without ink stay where the font puts them
+    pub(crate) fn place(&mut self, mark: &Glyph) -> (i32, i32) {
+        let (base, ink, mark) = match (self.base, self.ink, glyph_ink(mark)) {
+            (Some(base), Some(ink), Some(mark)) => (base, ink, mark),
+            _ => return (0, 0),
+        };
+
+        let dx = (base.left + base.right - mark.left - mark.right) / 2;
+        // one empty row between a mark and what it sits on, marks across the baseline are only centred
+        let dy = if mark.bottom <= 0 {
+            ink.top - 1 - mark.bottom
+        } else if mark.top >= 0 {
+            ink.bottom + 1 - mark.top
+        } else {
+            0
+        };
+
+        self.ink = Some(ink.union(mark.offset(dx, dy)));
+        (dx, dy)
+    }
+}
+
+// draws `text` with the pen starting at (x, y), on the baseline or as origin 1 in vertical text,
+//   returning where the pen stops, combining marks are drawn over the character before them
+pub(crate) fn draw_text<C: Canvas>(fonts: &Fallback<'_>, text: &str, x: i32, y: i32, direction: Direction, canvas: &mut C) -> (i32, i32) {
+    let mut pen = (x, y);
+    let mut base = (pen, Cluster::new(None));
+
+    for c in text.chars() {
+        if is_combining(c) {
+            // marks no font has are left out rather than drawn as DEFAULT_CHAR
+            if let Some((_, mark)) = fonts.find(c) {
+                let (dx, dy) = base.1.place(mark);
+                draw_glyph(mark, (base.0).0 + dx, (base.0).1 + dy, canvas);
+            }
+            continue;
+        }
+        if is_selector(c) {
+            continue;
+        }
+
+        let (font, glyph) = fonts.glyph(c);
+        let (origin, advance) = match direction {
+            Direction::Horizontal => {
+                let (dx, dy) = font.advance(glyph);
+                (pen, (dx, -dy))
+            }
+            Direction::Vertical => {
+                let (vx, vy) = font.vertical_origin(glyph);
+                let (dx, dy) = font.vertical_advance(glyph);
+                ((pen.0 - vx, pen.1 + vy), (dx, -dy))
+            }
+        };
+
+        if let Some(glyph) = glyph {
+            draw_glyph(glyph, origin.0, origin.1, canvas);
+        }
+        base = (origin, Cluster::new(glyph));
+        pen = (pen.0 + advance.0, pen.1 + advance.1);
+    }
+
+    pen
+}
+
+impl Font {
+    // the glyphs for the characters of `text`, the first glyph wins when several map to the same character
+    pub(crate) fn glyphs_for(&self, text: &str) -> HashMap<char, &Glyph> {
+        let chars: HashSet<char> = text.chars().collect();
+
+        let mut glyphs = HashMap::new();
+        for (c, glyph) in self.text_chars().into_iter().zip(&self.glyphs) {
+            if let Some(c) = c.filter(|c| chars.contains(c)) {
+                glyphs.entry(c).or_insert(glyph);
+            }
+        }
+
+        glyphs
+    }
+
+    // the glyph that stands in for missing characters
+    pub(crate) fn default_glyph(&self) -> Option<&Glyph> {
+        self.default_glyph_index().map(|i| &self.glyphs[i])
+    }
+
+    pub(crate) fn default_glyph_index(&self) -> Option<usize> {
+        let code = self.int_property(ids::DEFAULT_CHAR)?;
+        self.glyphs.iter().position(|g| g.encoding.code() == Some(code as u32))
+    }
+
+    // how far the pen moves after the glyph, DWIDTH of the glyph or else of the font
+    pub(crate) fn advance(&self, glyph: Option<&Glyph>) -> (i32, i32) {
+        glyph.and_then(|g| g.device_width)
+             .or(self.device_width)
+             .map(|d| (d.x, d.y))
+             .unwrap_or((0, 0))
+    }
+
+    // from origin 0 to origin 1 in pixels with y growing up, VVECTOR of the glyph or else of the font,
+    //   without either the middle of the advance at the ascent
+    pub(crate) fn vertical_origin(&self, glyph: Option<&Glyph>) -> (i32, i32) {
+        if let Some(v) = glyph.and_then(|g| g.vector).or(self.vector) {
+            return (v.x, v.y);
+        }
+
+        let width = match glyph {
+            Some(_) => self.advance(glyph).0,
+            None => self.device_width.map(|d| d.x).unwrap_or(self.bounding_box.width as i32),
+        };
+        (width / 2, self.ascent_descent().0)
+    }
+
+    // how far the pen moves in vertical writing with y growing up, so down the column is negative,
+    //   DWIDTH1 of the glyph or else of the font, without either the height of a line
+    pub(crate) fn vertical_advance(&self, glyph: Option<&Glyph>) -> (i32, i32) {
+        match glyph.and_then(|g| g.device_width_alt).or(self.device_width_alt) {
+            Some(d) => (d.x, d.y),
+            None => {
+                let (ascent, descent) = self.ascent_descent();
+                (0, -(ascent + descent))
+            }
+        }
+    }
+
+    // draws `text` with the pen starting at (x, y) on the baseline, returning where the pen stops,
+    //   characters the font lacks are drawn as DEFAULT_CHAR if it has one
+    pub fn render_at<C: Canvas>(&self, text: &str, x: i32, y: i32, canvas: &mut C) -> Result<(i32, i32), Error> {
+        let fonts = Fallback::new(self, text);
+        Ok(draw_text(&fonts, text, x, y, Direction::Horizontal, canvas))
+    }
+
+    // draws `text` from the left edge with the top of the line at the top of the canvas
+    pub fn render<C: Canvas>(&self, text: &str, canvas: &mut C) -> Result<(i32, i32), Error> {
+        let (ascent, _) = self.ascent_descent();
+        self.render_at(text, 0, ascent, canvas)
+    }
+
+    // draws `text` top to bottom with the pen starting at (x, y) as origin 1, returning where the pen stops,
+    //   each glyph is placed by its VVECTOR and the pen moves down by its DWIDTH1
+    pub fn render_vertical_at<C: Canvas>(&self, text: &str, x: i32, y: i32, canvas: &mut C) -> Result<(i32, i32), Error> {
+        let fonts = Fallback::new(self, text);
+        Ok(draw_text(&fonts, text, x, y, Direction::Vertical, canvas))
+    }
+
+    // draws `text` down the left edge of the canvas from its top
+    pub fn render_vertical<C: Canvas>(&self, text: &str, canvas: &mut C) -> Result<(i32, i32), Error> {
+        let (vx, _) = self.vertical_origin(None);
+        self.render_vertical_at(text, vx, 0, canvas)
+    }
+
+    // one glyph for a base character and the combining marks after it, the marks drawn into its bitmap
+    //   where rendering would put them, none when there is no glyph for the base and no DEFAULT_CHAR
+    pub fn compose(&self, cluster: &str) -> Result<Option<Glyph>, Error> {
+        let fonts = Fallback::new(self, cluster);
+        let mut chars = cluster.chars();
+        let base = match chars.next().and_then(|c| fonts.glyph(c).1) {
+            Some(base) => base,
+            None => return Ok(None),
+        };
+
+        let mut placement = Cluster::new(Some(base));
+        let marks: Vec<(&Glyph, (i32, i32))> = chars.filter(|&c| is_combining(c))
+                                                    .filter_map(|c| fonts.find(c))
+                                                    .map(|(_, mark)| (mark, placement.place(mark)))
+                                                    .collect();
+
+        let bounds = marks.iter()
+                          .map(|&(mark, (dx, dy))| glyph_box(mark).offset(dx, dy))
+                          .fold(glyph_box(base), Bounds::union);
+        let mut bitmap = Bitmap::new((bounds.right - bounds.left) as usize, (bounds.bottom - bounds.top) as usize);
+        draw_glyph(base, -bounds.left, -bounds.top, &mut bitmap);
+        for &(mark, (dx, dy)) in &marks {
+            draw_glyph(mark, dx - bounds.left, dy - bounds.top, &mut bitmap);
+        }
+
+        let name: Vec<String> = cluster.chars().filter(|&c| !is_selector(c)).map(|c| unicode_glyph_name(c as u32)).collect();
+
+        Ok(Some(Glyph {
+            name: name.join("_"),
+            encoding: Encoding::Unencoded,
+            bounding_box: BoundingBox::new(bitmap.width() as u32, bitmap.height() as u32, bounds.left, -bounds.bottom),
+            bitmap,
+            metrics: base.metrics,
+
+            scalable_width: base.scalable_width,
+            device_width: base.device_width,
+            scalable_width_alt: base.scalable_width_alt,
+            device_width_alt: base.device_width_alt,
+            vector: base.vector,
+
+            source: None,
+        }))
+    }
+}
@@ -0,0 +1,628 @@
+use std::{
+    convert::TryFrom,
+    io::Write,
+    ops::RangeInclusive,
+};
+
+use crate::{
+    ids,
+    BdfBlock,
+    Bitmap,
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+pub struct SourceOptions {
+    // the identifier the generated arrays and tables are named after
+    pub name: String,
+    // only the characters in this range are written
+    pub range: Option<RangeInclusive<char>>,
+}
+
+impl Default for SourceOptions {
+    fn default() -> Self {
+        Self {
+            name: String::from("font"),
+            range: None,
+        }
+    }
+}
+
+//
+
+struct Entry<'a> {
+    c: char,
+    glyph: &'a Glyph,
+    advance: i32,
+}
+
+impl Entry<'_> {
+    fn width(&self) -> i32 {
+        self.glyph.bitmap.width() as i32
+    }
+
+    fn height(&self) -> i32 {
+        self.glyph.bitmap.height() as i32
+    }
+
+    // from the baseline down to the top of the bitmap, as the C libraries count
+    fn top(&self) -> i32 {
+        -(self.glyph.bounding_box.y_offset + self.height())
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// the glyphs to write in character order, the first glyph wins when several map to the same character
+fn entries<'a>(font: &'a Font, options: &SourceOptions) -> Result<Vec<Entry<'a>>, Error> {
+    font.validate()?;
+
+    if !is_identifier(&options.name) {
+        return Err(Error::FontValidation("source name is not an identifier"));
+    }
+
+    let mut entries: Vec<Entry> = font.unicode_chars()?
+                                      .into_iter()
+                                      .zip(&font.glyphs)
+                                      .filter_map(|(c, glyph)| c.map(|c| (c, glyph)))
+                                      .filter(|(c, _)| options.range.as_ref().map_or(true, |r| r.contains(c)))
+                                      .map(|(c, glyph)| Entry {
+                                          c,
+                                          glyph,
+                                          advance: glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0),
+                                      })
+                                      .collect();
+    entries.sort_by_key(|e| e.c);
+    entries.dedup_by_key(|e| e.c);
+
+    if entries.is_empty() {
+        return Err(Error::FontValidation("no glyphs to write"));
+    }
+
+    Ok(entries)
+}
+
+fn fits<T: TryFrom<i32>>(entry: &Entry, value: i32) -> Result<T, Error> {
+    T::try_from(value).map_err(|_| Error::GlyphValidation(entry.glyph.name.clone(), "metrics do not fit the source format"))
+}
+
+// the pixels one after another, most significant bit first, rows not padded
+fn packed_bits(bitmap: &Bitmap) -> Vec<u8> {
+    let mut out = vec![0u8; (bitmap.width() * bitmap.height()).div_ceil(8)];
+
+    for y in 0..bitmap.height() {
+        for x in 0..bitmap.width() {
+            if bitmap.get(x, y) == Some(true) {
+                let i = y * bitmap.width() + x;
+                out[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+    }
+
+    out
+}
+
+// the character for comments, left out where it could upset a C compiler
+fn describe(c: char) -> String {
+    if c.is_ascii_graphic() && c != '\\' || c == ' ' {
+        format!("0x{:02X} '{}'", c as u32, c)
+    } else {
+        format!("U+{:04X}", c as u32)
+    }
+}
+
+fn write_c_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
+    // C has no empty initializers
+    let bytes = if bytes.is_empty() { &[0][..] } else { bytes };
+
+    for line in bytes.chunks(12) {
+        let values: Vec<String> = line.iter().map(|b| format!("0x{:02X}", b)).collect();
+        writeln!(writer, "  {},", values.join(", "))?;
+    }
+
+    Ok(())
+}
+
+//
+
+// Adafruit GFX, a GFXfont covering every character from the first to the last one
+pub fn write_gfx<W: Write>(mut writer: W, font: &Font, options: &SourceOptions) -> Result<W, Error> {
+    let entries = entries(font, options)?;
+    let name = &options.name;
+
+    let (first, last) = (entries[0].c as u32, entries[entries.len() - 1].c as u32);
+    if last > 0xffff {
+        return Err(Error::FontValidation("gfx fonts stop at U+FFFF"));
+    }
+
+    let (ascent, descent) = font.ascent_descent();
+    let line_height = u8::try_from(ascent + descent).map_err(|_| Error::FontValidation("line height does not fit a gfx font"))?;
+
+    let mut bitmaps = Vec::new();
+    let mut glyphs = Vec::new();
+    let mut next = entries.iter().peekable();
+
+    for code in first..=last {
+        let offset = u16::try_from(bitmaps.len()).map_err(|_| Error::FontValidation("gfx bitmaps exceed 64k"))?;
+
+        match next.next_if(|e| e.c as u32 == code) {
+            Some(e) => {
+                let row = format!("  {{ {:5}, {:3}, {:3}, {:3}, {:4}, {:4} }},   // {}",
+                                  offset, fits::<u8>(e, e.width())?, fits::<u8>(e, e.height())?, fits::<u8>(e, e.advance)?,
+                                  fits::<i8>(e, e.glyph.bounding_box.x_offset)?, fits::<i8>(e, e.top())?, describe(e.c));
+                glyphs.push(row);
+                bitmaps.extend(packed_bits(&e.glyph.bitmap));
+            }
+            None => glyphs.push(format!("  {{ {:5},   0,   0,   0,    0,    0 }},   // U+{:04X} missing", offset, code)),
+        }
+    }
+
+    writeln!(writer, "// {}", font.name)?;
+    writeln!(writer, "// Adafruit GFX font, U+{:04X} to U+{:04X}", first, last)?;
+    writeln!(writer)?;
+
+    writeln!(writer, "const uint8_t {}Bitmaps[] PROGMEM = {{", name)?;
+    write_c_bytes(&mut writer, &bitmaps)?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "const GFXglyph {}Glyphs[] PROGMEM = {{", name)?;
+    for glyph in glyphs {
+        writeln!(writer, "{}", glyph)?;
+    }
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "const GFXfont {} PROGMEM = {{", name)?;
+    writeln!(writer, "  (uint8_t  *){}Bitmaps,", name)?;
+    writeln!(writer, "  (GFXglyph *){}Glyphs,", name)?;
+    writeln!(writer, "  0x{:02X}, 0x{:02X}, {} }};", first, last, line_height)?;
+
+    writer.flush()?;
+
+    Ok(writer)
+}
+
+//
+
+// bits written least significant first, as u8g2 reads them
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, count: u32, value: u32) {
+        for i in 0..count {
+            if self.len % 8 == 0 {
+                self.bytes.push(0);
+            }
+            if value >> i & 1 != 0 {
+                self.bytes[self.len / 8] |= 1 << (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+
+    fn push_signed(&mut self, count: u32, value: i32) {
+        self.push(count, (value + (1 << (count - 1))) as u32);
+    }
+}
+
+// bits for unsigned values up to `max`
+fn unsigned_bits(max: i32) -> u32 {
+    32 - (max.max(0) as u32).leading_zeros()
+}
+
+// bits for signed values stored with half the range added, as u8g2 does
+fn signed_bits(min: i32, max: i32) -> u32 {
+    (1..32).find(|&n| min >= -(1 << (n - 1)) && max < 1 << (n - 1)).unwrap_or(32)
+}
+
+// runs of 0s and 1s, a repeated pair costs a single bit
+struct Rle {
+    bits: BitWriter,
+    bits_0: u32,
+    bits_1: u32,
+    last: Option<(u32, u32)>,
+}
+
+impl Rle {
+    fn pair(&mut self, a: u32, b: u32) {
+        if self.last == Some((a, b)) {
+            self.bits.push(1, 1);
+        } else {
+            if self.last.is_some() {
+                self.bits.push(1, 0);
+            }
+            self.bits.push(self.bits_0, a);
+            self.bits.push(self.bits_1, b);
+        }
+        self.last = Some((a, b));
+    }
+
+    // splits runs longer than the counters hold
+    fn runs(&mut self, mut a: u32, mut b: u32) {
+        let (max_0, max_1) = ((1 << self.bits_0) - 1, (1 << self.bits_1) - 1);
+
+        while a > max_0 {
+            self.pair(max_0, 0);
+            a -= max_0;
+        }
+        while b > max_1 {
+            self.pair(a, max_1);
+            a = 0;
+            b -= max_1;
+        }
+        if a != 0 || b != 0 {
+            self.pair(a, b);
+        }
+    }
+
+    fn encode(&mut self, bitmap: &Bitmap) {
+        let (mut a, mut b) = (0, 0);
+        for y in 0..bitmap.height() {
+            for x in 0..bitmap.width() {
+                if bitmap.get(x, y) == Some(true) {
+                    b += 1;
+                } else {
+                    if b > 0 {
+                        self.runs(a, b);
+                        a = 0;
+                        b = 0;
+                    }
+                    a += 1;
+                }
+            }
+        }
+
+        self.runs(a, b);
+        self.bits.push(1, 0);
+    }
+}
+
+struct U8g2Layout {
+    bits_0: u32,
+    bits_1: u32,
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    advance: u32,
+}
+
+impl U8g2Layout {
+    fn glyph(&self, entry: &Entry) -> Vec<u8> {
+        let mut rle = Rle { bits: BitWriter::default(), bits_0: self.bits_0, bits_1: self.bits_1, last: None };
+
+        // an empty bitmap is written as 0 by 0, which has no pixel data
+        let (width, height) = if entry.width() == 0 || entry.height() == 0 { (0, 0) } else { (entry.width(), entry.height()) };
+
+        rle.bits.push(self.width, width as u32);
+        rle.bits.push(self.height, height as u32);
+        rle.bits.push_signed(self.x, entry.glyph.bounding_box.x_offset);
+        rle.bits.push_signed(self.y, entry.glyph.bounding_box.y_offset);
+        rle.bits.push_signed(self.advance, entry.advance);
+        if width > 0 {
+            rle.encode(&entry.glyph.bitmap);
+        }
+
+        rle.bits.bytes
+    }
+}
+
+fn u8g2_data(font: &Font, entries: &[Entry]) -> Result<Vec<u8>, Error> {
+    if entries.iter().any(|e| e.c as u32 > 0xffff) {
+        return Err(Error::FontValidation("u8g2 fonts stop at U+FFFF"));
+    }
+
+    let range = |f: fn(&Entry) -> i32| {
+        let values = entries.iter().map(f);
+        (values.clone().min().unwrap_or(0), values.max().unwrap_or(0))
+    };
+    let (_, max_width) = range(|e| e.width());
+    let (_, max_height) = range(|e| e.height());
+    let (min_x, max_x) = range(|e| e.glyph.bounding_box.x_offset);
+    let (min_y, max_y) = range(|e| e.glyph.bounding_box.y_offset);
+    let (min_advance, max_advance) = range(|e| e.advance);
+
+    // the run length counter sizes that give the smallest font
+    let mut best: Option<(usize, U8g2Layout)> = None;
+    for bits_0 in 2..=9 {
+        for bits_1 in 2..=7 {
+            let layout = U8g2Layout {
+                bits_0,
+                bits_1,
+                width: unsigned_bits(max_width),
+                height: unsigned_bits(max_height),
+                x: signed_bits(min_x, max_x),
+                y: signed_bits(min_y, max_y),
+                advance: signed_bits(min_advance, max_advance),
+            };
+
+            let size = entries.iter().map(|e| layout.glyph(e).len()).sum();
+            if best.as_ref().map_or(true, |(best, _)| size < *best) {
+                best = Some((size, layout));
+            }
+        }
+    }
+    let layout = best.map(|(_, layout)| layout).ok_or(Error::FontValidation("no glyphs to write"))?;
+
+    let mut glyphs = Vec::new();
+    let (mut upper_a, mut lower_a) = (None, None);
+
+    for entry in entries.iter().filter(|e| (e.c as u32) < 0x100) {
+        if entry.c >= 'A' && upper_a.is_none() {
+            upper_a = Some(glyphs.len());
+        }
+        if entry.c >= 'a' && lower_a.is_none() {
+            lower_a = Some(glyphs.len());
+        }
+
+        let data = layout.glyph(entry);
+        glyphs.push(entry.c as u8);
+        glyphs.push(fits::<u8>(entry, data.len() as i32 + 2)?);
+        glyphs.extend(data);
+    }
+    let upper_a = upper_a.unwrap_or(glyphs.len());
+    let lower_a = lower_a.unwrap_or(glyphs.len());
+    glyphs.extend_from_slice(&[0, 0]);
+
+    // a lookup table of one group holding every glyph up to U+FFFF
+    let unicode = glyphs.len();
+    glyphs.extend_from_slice(&[0, 4, 0xff, 0xff]);
+    for entry in entries.iter().filter(|e| e.c as u32 >= 0x100) {
+        let data = layout.glyph(entry);
+        glyphs.extend_from_slice(&(entry.c as u16).to_be_bytes());
+        glyphs.push(fits::<u8>(entry, data.len() as i32 + 3)?);
+        glyphs.extend(data);
+    }
+    glyphs.extend_from_slice(&[0, 0]);
+
+    let (ascent, descent) = font.ascent_descent();
+    let find = |c: char| entries.iter().find(|e| e.c == c);
+    let ascent_of = |c| find(c).map(|e| e.height() + e.glyph.bounding_box.y_offset);
+    let descent_of = |c| find(c).map(|e| e.glyph.bounding_box.y_offset);
+
+    let ascent_a = ascent_of('A').unwrap_or(ascent);
+    let descent_g = descent_of('g').unwrap_or(-descent);
+    let ascent_paren = ascent_of('(').unwrap_or(ascent_a);
+    let descent_paren = descent_of(')').unwrap_or(descent_g);
+
+    let too_big = |_| Error::FontValidation("metrics do not fit a u8g2 font");
+    let i8_of = |v: i32| i8::try_from(v).map(|v| v as u8).map_err(too_big);
+    let u16_of = |v: usize| u16::try_from(v).map_err(too_big);
+
+    let mut out = Vec::new();
+    out.push(u8::try_from(entries.len()).unwrap_or(0xff));
+    out.push(0); // proportional
+    for bits in [layout.bits_0, layout.bits_1, layout.width, layout.height, layout.x, layout.y, layout.advance] {
+        out.push(bits as u8);
+    }
+    for value in [max_width, max_height, font.bounding_box.x_offset, font.bounding_box.y_offset,
+                  ascent_a, descent_g, ascent_paren, descent_paren] {
+        out.push(i8_of(value)?);
+    }
+    out.extend_from_slice(&u16_of(upper_a)?.to_be_bytes());
+    out.extend_from_slice(&u16_of(lower_a)?.to_be_bytes());
+    out.extend_from_slice(&u16_of(unicode)?.to_be_bytes());
+    out.extend(glyphs);
+
+    Ok(out)
+}
+
+// a u8g2 font array, run length encoded as bdfconv does
+pub fn write_u8g2<W: Write>(mut writer: W, font: &Font, options: &SourceOptions) -> Result<W, Error> {
+    let entries = entries(font, options)?;
+    let data = u8g2_data(font, &entries)?;
+    let name = &options.name;
+
+    writeln!(writer, "/*")?;
+    writeln!(writer, "  Fontname: {}", font.name.replace("*/", "* /"))?;
+    writeln!(writer, "  Glyphs: {}", entries.len())?;
+    writeln!(writer, "  BBX Build Mode: 0")?;
+    writeln!(writer, "*/")?;
+
+    // the string literal's terminating nul is counted in the size
+    writeln!(writer, "const uint8_t {}[{}] U8G2_FONT_SECTION(\"{}\") =", name, data.len() + 1, name)?;
+    for (i, line) in data.chunks(32).enumerate() {
+        // octal escapes always take three digits, so a digit after one can never extend it
+        let text: String = line.iter().map(|&b| match b {
+            b'"' | b'\\' | b'?' => format!("\\{:03o}", b),
+            0x20..=0x7e => char::from(b).to_string(),
+            _ => format!("\\{:03o}", b),
+        }).collect();
+
+        let end = if (i + 1) * 32 >= data.len() { ";" } else { "" };
+        writeln!(writer, "  \"{}\"{}", text, end)?;
+    }
+
+    writer.flush()?;
+
+    Ok(writer)
+}
+
+//
+
+// an LVGL lv_font_t in the plain 1 bit per pixel format lv_font_conv writes
+pub fn write_lvgl<W: Write>(mut writer: W, font: &Font, options: &SourceOptions) -> Result<W, Error> {
+    let entries = entries(font, options)?;
+    let name = &options.name;
+    let guard = name.to_uppercase();
+
+    let mut bitmaps = Vec::new();
+    let mut glyphs = Vec::new();
+    for e in &entries {
+        if bitmaps.len() >= 1 << 20 {
+            return Err(Error::FontValidation("lvgl bitmaps exceed 1M"));
+        }
+        if !(0..256).contains(&e.advance) {
+            return Err(Error::GlyphValidation(e.glyph.name.clone(), "metrics do not fit the source format"));
+        }
+
+        glyphs.push(format!("    {{.bitmap_index = {}, .adv_w = {}, .box_w = {}, .box_h = {}, .ofs_x = {}, .ofs_y = {}}} /* {} */",
+                            bitmaps.len(), e.advance * 16, fits::<u8>(e, e.width())?, fits::<u8>(e, e.height())?,
+                            fits::<i8>(e, e.glyph.bounding_box.x_offset)?, fits::<i8>(e, e.glyph.bounding_box.y_offset)?,
+                            describe(e.c).replace("*/", "* /")));
+        bitmaps.extend(packed_bits(&e.glyph.bitmap));
+    }
+
+    // runs of consecutive characters, glyph ids follow character order after the reserved id 0
+    let mut ranges: Vec<(u32, u32, usize)> = Vec::new();
+    for (i, e) in entries.iter().enumerate() {
+        match ranges.last_mut() {
+            Some((start, length, _)) if *start + *length == e.c as u32 && *length < 0xffff => *length += 1,
+            _ => ranges.push((e.c as u32, 1, i + 1)),
+        }
+    }
+
+    let (ascent, descent) = font.ascent_descent();
+    let underline_position = -font.int_property(ids::UNDERLINE_POSITION).unwrap_or(1);
+    let underline_thickness = font.int_property(ids::UNDERLINE_THICKNESS).unwrap_or(1);
+
+    writeln!(writer, "/*******************************************************************************")?;
+    writeln!(writer, " * Size: {} px", font.xlfd.pixel_size.filter(|&p| p > 0).unwrap_or(ascent + descent))?;
+    writeln!(writer, " * Bpp: 1")?;
+    writeln!(writer, " * Generated from: {}", font.name.replace("*/", "* /"))?;
+    writeln!(writer, " ******************************************************************************/")?;
+    writeln!(writer)?;
+    writeln!(writer, "#ifdef LV_LVGL_H_INCLUDE_SIMPLE")?;
+    writeln!(writer, "#include \"lvgl.h\"")?;
+    writeln!(writer, "#else")?;
+    writeln!(writer, "#include \"lvgl/lvgl.h\"")?;
+    writeln!(writer, "#endif")?;
+    writeln!(writer)?;
+    writeln!(writer, "#ifndef {}", guard)?;
+    writeln!(writer, "#define {} 1", guard)?;
+    writeln!(writer, "#endif")?;
+    writeln!(writer)?;
+    writeln!(writer, "#if {}", guard)?;
+    writeln!(writer)?;
+
+    writeln!(writer, "static LV_ATTRIBUTE_LARGE_CONST const uint8_t glyph_bitmap[] = {{")?;
+    write_c_bytes(&mut writer, &bitmaps)?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "static const lv_font_fmt_txt_glyph_dsc_t glyph_dsc[] = {{")?;
+    writeln!(writer, "    {{.bitmap_index = 0, .adv_w = 0, .box_w = 0, .box_h = 0, .ofs_x = 0, .ofs_y = 0}} /* id = 0 reserved */,")?;
+    writeln!(writer, "{}", glyphs.join(",\n"))?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "static const lv_font_fmt_txt_cmap_t cmaps[] = {{")?;
+    let cmaps: Vec<String> = ranges.iter().map(|(start, length, id)| {
+        format!("    {{\n        .range_start = {}, .range_length = {}, .glyph_id_start = {},\n        \
+                 .unicode_list = NULL, .glyph_id_ofs_list = NULL, .list_length = 0, .type = LV_FONT_FMT_TXT_CMAP_FORMAT0_TINY\n    }}",
+                start, length, id)
+    }).collect();
+    writeln!(writer, "{}", cmaps.join(",\n"))?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "#if LVGL_VERSION_MAJOR == 8")?;
+    writeln!(writer, "static lv_font_fmt_txt_glyph_cache_t cache;")?;
+    writeln!(writer, "#endif")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "static const lv_font_fmt_txt_dsc_t font_dsc = {{")?;
+    writeln!(writer, "    .glyph_bitmap = glyph_bitmap,")?;
+    writeln!(writer, "    .glyph_dsc = glyph_dsc,")?;
+    writeln!(writer, "    .cmaps = cmaps,")?;
+    writeln!(writer, "    .kern_dsc = NULL,")?;
+    writeln!(writer, "    .kern_scale = 0,")?;
+    writeln!(writer, "    .cmap_num = {},", ranges.len())?;
+    writeln!(writer, "    .bpp = 1,")?;
+    writeln!(writer, "    .kern_classes = 0,")?;
+    writeln!(writer, "    .bitmap_format = 0,")?;
+    writeln!(writer, "#if LVGL_VERSION_MAJOR == 8")?;
+    writeln!(writer, "    .cache = &cache")?;
+    writeln!(writer, "#endif")?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "const lv_font_t {} = {{", name)?;
+    writeln!(writer, "    .get_glyph_dsc = lv_font_get_glyph_dsc_fmt_txt,")?;
+    writeln!(writer, "    .get_glyph_bitmap = lv_font_get_bitmap_fmt_txt,")?;
+    writeln!(writer, "    .line_height = {},", ascent + descent)?;
+    writeln!(writer, "    .base_line = {},", descent)?;
+    writeln!(writer, "    .subpx = LV_FONT_SUBPX_NONE,")?;
+    writeln!(writer, "    .underline_position = {},", underline_position)?;
+    writeln!(writer, "    .underline_thickness = {},", underline_thickness)?;
+    writeln!(writer, "    .dsc = &font_dsc")?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+    writeln!(writer, "#endif /*#if {}*/", guard)?;
+
+    writer.flush()?;
+
+    Ok(writer)
+}
+
+//
+
+// a Rust module with the bitmaps and a glyph table sorted by character
+pub fn write_rust<W: Write>(mut writer: W, font: &Font, options: &SourceOptions) -> Result<W, Error> {
+    let entries = entries(font, options)?;
+    let (ascent, descent) = font.ascent_descent();
+
+    let mut bitmaps = Vec::new();
+    let mut glyphs = Vec::new();
+    for e in &entries {
+        glyphs.push(format!("        Glyph {{ c: '{}', offset: {}, width: {}, height: {}, x_offset: {}, y_offset: {}, advance: {} }},",
+                            e.c.escape_default(), bitmaps.len(), fits::<u16>(e, e.width())?, fits::<u16>(e, e.height())?,
+                            fits::<i16>(e, e.glyph.bounding_box.x_offset)?, fits::<i16>(e, e.glyph.bounding_box.y_offset)?,
+                            fits::<i16>(e, e.advance)?));
+        for y in 0..e.glyph.bitmap.height() {
+            bitmaps.extend(e.glyph.bitmap.row_bytes(y));
+        }
+    }
+
+    writeln!(writer, "// {}", font.name)?;
+    writeln!(writer, "pub mod {} {{", options.name)?;
+    writeln!(writer, "    // a bitmap starts at `offset` in BITMAPS, its rows padded to whole bytes with the leftmost pixel in the top bit,")?;
+    writeln!(writer, "    //   the offsets place its bottom left corner relative to the pen on the baseline, y growing up")?;
+    writeln!(writer, "    pub struct Glyph {{")?;
+    writeln!(writer, "        pub c: char,")?;
+    writeln!(writer, "        pub offset: usize,")?;
+    writeln!(writer, "        pub width: u16,")?;
+    writeln!(writer, "        pub height: u16,")?;
+    writeln!(writer, "        pub x_offset: i16,")?;
+    writeln!(writer, "        pub y_offset: i16,")?;
+    writeln!(writer, "        pub advance: i16,")?;
+    writeln!(writer, "    }}")?;
+    writeln!(writer)?;
+    writeln!(writer, "    pub const ASCENT: i32 = {};", ascent)?;
+    writeln!(writer, "    pub const DESCENT: i32 = {};", descent)?;
+    writeln!(writer)?;
+
+    writeln!(writer, "    pub static BITMAPS: [u8; {}] = [", bitmaps.len())?;
+    for line in bitmaps.chunks(12) {
+        let values: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+        writeln!(writer, "        {},", values.join(", "))?;
+    }
+    writeln!(writer, "    ];")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "    pub static GLYPHS: [Glyph; {}] = [", glyphs.len())?;
+    for glyph in glyphs {
+        writeln!(writer, "{}", glyph)?;
+    }
+    writeln!(writer, "    ];")?;
+    writeln!(writer)?;
+
+    writeln!(writer, "    pub fn glyph(c: char) -> Option<&'static Glyph> {{")?;
+    writeln!(writer, "        GLYPHS.binary_search_by_key(&c, |g| g.c).ok().map(|i| &GLYPHS[i])")?;
+    writeln!(writer, "    }}")?;
+    writeln!(writer, "}}")?;
+
+    writer.flush()?;
+
+    Ok(writer)
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use crate::{
+    layout::{
+        self,
+        Direction,
+        Layout,
+        LayoutOptions,
+        Measure,
+    },
+    render::{
+        self,
+        Canvas,
+    },
+    Error,
+    Font,
+    Glyph,
+};
+
+//
+
+// fonts tried in order for every character, the first is the primary font
+pub struct FontStack {
+    fonts: Vec<Font>,
+    // the first font with a glyph for each character, by position in `fonts` and in its glyphs
+    index: HashMap<char, (usize, usize)>,
+    // the first font's stand-in from DEFAULT_CHAR
+    default_glyph: Option<(usize, usize)>,
+}
+
+// the fonts of a stack with the glyphs for one text looked up
+pub(crate) struct Fallback<'a> {
+    fonts: Vec<&'a Font>,
+    glyphs: HashMap<char, (&'a Font, &'a Glyph)>,
+    default_glyph: Option<(&'a Font, &'a Glyph)>,
+}
+
+impl<'a> Fallback<'a> {
+    pub(crate) fn new(font: &'a Font, text: &str) -> Self {
+        let glyphs = font.glyphs_for(&with_space(text))
+                         .into_iter()
+                         .map(|(c, glyph)| (c, (font, glyph)))
+                         .collect();
+        let default_glyph = font.default_glyph().map(|glyph| (font, glyph));
+
+        Self {
+            fonts: vec![font],
+            glyphs,
+            default_glyph,
+        }
+    }
+
+    fn from_stack(stack: &'a FontStack, text: &str) -> Result<Self, Error> {
+        if stack.fonts.is_empty() {
+            return Err(Error::FontValidation("a font stack needs at least one font"));
+        }
+
+        let glyphs = with_space(text).chars().filter_map(|c| stack.glyph_for(c).map(|found| (c, found))).collect();
+        let default_glyph = stack.default_glyph.map(|at| stack.glyph_at(at));
+
+        Ok(Self {
+            fonts: stack.fonts.iter().collect(),
+            glyphs,
+            default_glyph,
+        })
+    }
+
+    pub(crate) fn primary(&self) -> &'a Font {
+        self.fonts[0]
+    }
+
+    pub(crate) fn fonts(&self) -> &[&'a Font] {
+        &self.fonts
+    }
+
+    // the first font with a glyph for `c`, and that glyph
+    pub(crate) fn find(&self, c: char) -> Option<(&'a Font, &'a Glyph)> {
+        self.glyphs.get(&c).copied()
+    }
+
+    // the glyph for `c` and the font it comes from, the primary font without a glyph when no font has one
+    pub(crate) fn glyph(&self, c: char) -> (&'a Font, Option<&'a Glyph>) {
+        match self.glyphs.get(&c).copied().or(self.default_glyph) {
+            Some((font, glyph)) => (font, Some(glyph)),
+            None => (self.primary(), None),
+        }
+    }
+
+    // the line all the fonts fit in, each sitting on the same baseline
+    pub(crate) fn ascent_descent(&self) -> (i32, i32) {
+        self.fonts.iter()
+                  .map(|font| font.ascent_descent())
+                  .reduce(|a, b| (a.0.max(b.0), a.1.max(b.1)))
+                  .unwrap_or((0, 0))
+    }
+}
+
+// the space is always looked up as layout measures tabs by it
+fn with_space(text: &str) -> String {
+    format!("{} ", text)
+}
+
+impl FontStack {
+    pub fn new(fonts: Vec<Font>) -> Self {
+        let mut stack = Self {
+            fonts: Vec::new(),
+            index: HashMap::new(),
+            default_glyph: None,
+        };
+        for font in fonts {
+            stack.push(font);
+        }
+
+        stack
+    }
+
+    // adds a font tried after the ones already in the stack,
+    //   earlier fonts win both for characters and for the stand-in from DEFAULT_CHAR
+    pub fn push(&mut self, font: Font) {
+        let n = self.fonts.len();
+        for (i, c) in font.text_chars().into_iter().enumerate() {
+            if let Some(c) = c {
+                self.index.entry(c).or_insert((n, i));
+            }
+        }
+        if self.default_glyph.is_none() {
+            self.default_glyph = font.default_glyph_index().map(|i| (n, i));
+        }
+        self.fonts.push(font);
+    }
+
+    pub fn fonts(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    fn fallback(&self, text: &str) -> Result<Fallback<'_>, Error> {
+        Fallback::from_stack(self, text)
+    }
+
+    fn glyph_at(&self, (n, i): (usize, usize)) -> (&Font, &Glyph) {
+        (&self.fonts[n], &self.fonts[n].glyphs[i])
+    }
+
+    // the first font with a glyph for `c`, and that glyph
+    pub fn glyph_for(&self, c: char) -> Option<(&Font, &Glyph)> {
+        self.index.get(&c).map(|&at| self.glyph_at(at))
+    }
+
+    pub fn line_height(&self) -> Result<i32, Error> {
+        let (ascent, descent) = self.fallback("")?.ascent_descent();
+        Ok(ascent + descent)
+    }
+
+    pub fn measure(&self, text: &str) -> Result<Measure, Error> {
+        layout::measure_fallback(&self.fallback(text)?, text)
+    }
+
+    pub fn layout(&self, text: &str, options: &LayoutOptions) -> Result<Layout<'_>, Error> {
+        layout::layout_fallback(&self.fallback(text)?, text, options)
+    }
+
+    // draws `text` with the pen starting at (x, y) on the baseline as Font::render_at does,
+    //   each character from the first font that has it
+    pub fn render_at<C: Canvas>(&self, text: &str, x: i32, y: i32, canvas: &mut C) -> Result<(i32, i32), Error> {
+        Ok(render::draw_text(&self.fallback(text)?, text, x, y, Direction::Horizontal, canvas))
+    }
+
+    // draws `text` from the left edge with the top of the tallest font's line at the top of the canvas
+    pub fn render<C: Canvas>(&self, text: &str, canvas: &mut C) -> Result<(i32, i32), Error> {
+        let (ascent, _) = self.fallback("")?.ascent_descent();
+        self.render_at(text, 0, ascent, canvas)
+    }
+
+    // draws `text` top to bottom with the pen starting at (x, y) as origin 1 as Font::render_vertical_at does
+    pub fn render_vertical_at<C: Canvas>(&self, text: &str, x: i32, y: i32, canvas: &mut C) -> Result<(i32, i32), Error> {
+        Ok(render::draw_text(&self.fallback(text)?, text, x, y, Direction::Vertical, canvas))
+    }
+
+    // draws `text` down the left edge of the canvas from its top, centred on the primary font's column
+    pub fn render_vertical<C: Canvas>(&self, text: &str, canvas: &mut C) -> Result<(i32, i32), Error> {
+        let (vx, _) = self.fallback("")?.primary().vertical_origin(None);
+        self.render_vertical_at(text, vx, 0, canvas)
+    }
+}
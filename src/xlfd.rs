@@ -0,0 +1,516 @@
+use std::{
+    fmt,
+    str::FromStr,
+};
+
+use crate::{
+    ids,
+    BdfBlock,
+    Error,
+    Font,
+    ForBdf,
+    Property,
+    PropertyValue,
+};
+
+//
+
+const SLANTS: [&str; 6] = ["R", "I", "O", "RI", "RO", "OT"];
+const SPACINGS: [&str; 3] = ["P", "M", "C"];
+
+//
+
+#[derive(Clone, Debug)]
+pub struct Xlfd {
+    pub foundry: Option<String>,
+    pub family_name: Option<String>,
+    pub weight_name: Option<String>,
+    pub slant: Option<String>,
+    pub setwidth_name: Option<String>,
+    pub add_style_name: Option<String>,
+    pub pixel_size: Option<i32>,
+    pub point_size: Option<i32>,
+    pub resolution_x: Option<i32>,
+    pub resolution_y: Option<i32>,
+    pub spacing: Option<String>,
+    pub average_width: Option<i32>,
+    pub charset_registry: Option<String>,
+    pub charset_encoding: Option<String>,
+}
+
+impl Xlfd {
+    pub fn empty() -> Self {
+        Self {
+            foundry: None,
+            family_name: None,
+            weight_name: None,
+            slant: None,
+            setwidth_name: None,
+            add_style_name: None,
+            pixel_size: None,
+            point_size: None,
+            resolution_x: None,
+            resolution_y: None,
+            spacing: None,
+            average_width: None,
+            charset_registry: None,
+            charset_encoding: None,
+        }
+    }
+}
+
+impl fmt::Display for Xlfd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "-")?;
+        if let Some(val) = &self.foundry {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.family_name {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.weight_name {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.slant {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.setwidth_name {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.add_style_name {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.pixel_size {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.point_size {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.resolution_x {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.resolution_y {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.spacing {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.average_width {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.charset_registry {
+            write!(f, "{}", val)?;
+        }
+
+        write!(f, "-")?;
+        if let Some(val) = &self.charset_encoding {
+            write!(f, "{}", val)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Xlfd {
+    // the xlfd fields that are set, as they appear in a STARTPROPERTIES block
+    pub fn properties(&self) -> Vec<Property> {
+        use PropertyValue::*;
+
+        let mut props = Vec::new();
+
+        let mut push = |name: &str, value: Option<PropertyValue>| {
+            if let Some(value) = value {
+                props.push(Property::new(name, &value));
+            }
+        };
+
+        push(ids::xlfd::FOUNDRY, self.foundry.clone().map(Str));
+        push(ids::xlfd::FAMILY_NAME, self.family_name.clone().map(Str));
+        push(ids::xlfd::WEIGHT_NAME, self.weight_name.clone().map(Str));
+        push(ids::xlfd::SLANT, self.slant.clone().map(Str));
+        push(ids::xlfd::SETWIDTH_NAME, self.setwidth_name.clone().map(Str));
+        push(ids::xlfd::ADD_STYLE_NAME, self.add_style_name.clone().map(Str));
+        push(ids::xlfd::PIXEL_SIZE, self.pixel_size.map(Int));
+        push(ids::xlfd::POINT_SIZE, self.point_size.map(Int));
+        push(ids::xlfd::RESOLUTION_X, self.resolution_x.map(Int));
+        push(ids::xlfd::RESOLUTION_Y, self.resolution_y.map(Int));
+        push(ids::xlfd::SPACING, self.spacing.clone().map(Str));
+        push(ids::xlfd::AVERAGE_WIDTH, self.average_width.map(Int));
+        push(ids::xlfd::CHARSET_REGISTRY, self.charset_registry.clone().map(Str));
+        push(ids::xlfd::CHARSET_ENCODING, self.charset_encoding.clone().map(Str));
+
+        props
+    }
+
+    // number of properties the xlfd contributes to a STARTPROPERTIES block
+    pub fn property_ct(&self) -> usize {
+        self.properties().len()
+    }
+
+    // stores a property that names an xlfd field, false if it is not one or has the wrong type
+    pub fn set_property(&mut self, property: &Property) -> bool {
+        use PropertyValue::*;
+
+        match (property.name.as_str(), &property.value) {
+            (ids::xlfd::FOUNDRY, Str(val)) => self.foundry = Some(val.clone()),
+            (ids::xlfd::FAMILY_NAME, Str(val)) => self.family_name = Some(val.clone()),
+            (ids::xlfd::WEIGHT_NAME, Str(val)) => self.weight_name = Some(val.clone()),
+            (ids::xlfd::SLANT, Str(val)) => self.slant = Some(val.clone()),
+            (ids::xlfd::SETWIDTH_NAME, Str(val)) => self.setwidth_name = Some(val.clone()),
+            (ids::xlfd::ADD_STYLE_NAME, Str(val)) => self.add_style_name = Some(val.clone()),
+            (ids::xlfd::PIXEL_SIZE, &Int(val)) => self.pixel_size = Some(val),
+            (ids::xlfd::POINT_SIZE, &Int(val)) => self.point_size = Some(val),
+            (ids::xlfd::RESOLUTION_X, &Int(val)) => self.resolution_x = Some(val),
+            (ids::xlfd::RESOLUTION_Y, &Int(val)) => self.resolution_y = Some(val),
+            (ids::xlfd::SPACING, Str(val)) => self.spacing = Some(val.clone()),
+            (ids::xlfd::AVERAGE_WIDTH, &Int(val)) => self.average_width = Some(val),
+            (ids::xlfd::CHARSET_REGISTRY, Str(val)) => self.charset_registry = Some(val.clone()),
+            (ids::xlfd::CHARSET_ENCODING, Str(val)) => self.charset_encoding = Some(val.clone()),
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn is_unicode(&self) -> bool {
+        match &self.charset_registry {
+            Some(registry) => registry.eq_ignore_ascii_case("ISO10646"),
+            None => false,
+        }
+    }
+}
+
+impl Xlfd {
+    fn fields(&self) -> [(&'static str, Option<String>); 14] {
+        let int = |v: Option<i32>| v.map(|v| v.to_string());
+
+        [
+            (ids::xlfd::FOUNDRY, self.foundry.clone()),
+            (ids::xlfd::FAMILY_NAME, self.family_name.clone()),
+            (ids::xlfd::WEIGHT_NAME, self.weight_name.clone()),
+            (ids::xlfd::SLANT, self.slant.clone()),
+            (ids::xlfd::SETWIDTH_NAME, self.setwidth_name.clone()),
+            (ids::xlfd::ADD_STYLE_NAME, self.add_style_name.clone()),
+            (ids::xlfd::PIXEL_SIZE, int(self.pixel_size)),
+            (ids::xlfd::POINT_SIZE, int(self.point_size)),
+            (ids::xlfd::RESOLUTION_X, int(self.resolution_x)),
+            (ids::xlfd::RESOLUTION_Y, int(self.resolution_y)),
+            (ids::xlfd::SPACING, self.spacing.clone()),
+            (ids::xlfd::AVERAGE_WIDTH, int(self.average_width)),
+            (ids::xlfd::CHARSET_REGISTRY, self.charset_registry.clone()),
+            (ids::xlfd::CHARSET_ENCODING, self.charset_encoding.clone()),
+        ]
+    }
+
+    // checks that a FONT name agrees with these properties,
+    //   fields left empty on either side are not compared
+    pub fn check_name(&self, name: &str) -> Result<(), Error> {
+        let named: Xlfd = name.parse()?;
+
+        for ((id, ours), (_, theirs)) in self.fields().iter().zip(named.fields().iter()) {
+            if let (Some(ours), Some(theirs)) = (ours, theirs) {
+                if !ours.eq_ignore_ascii_case(theirs) {
+                    return Err(Error::XlfdMismatch(id));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// WEIGHT_NAME, SETWIDTH_NAME and SLANT as other font formats describe a style,
+//   weights in classes of 100 to 900 and widths from 1 (ultra condensed) to 9 (ultra expanded)
+impl Xlfd {
+    pub(crate) fn weight_class(&self) -> u16 {
+        let weight = self.weight_name.as_deref().unwrap_or("").to_ascii_lowercase().replace([' ', '-'], "");
+
+        match weight.as_str() {
+            "thin" | "hairline" => 100,
+            "extralight" | "ultralight" => 200,
+            "light" => 300,
+            "semibold" | "demibold" | "demi" => 600,
+            "bold" => 700,
+            "extrabold" | "ultrabold" => 800,
+            "black" | "heavy" => 900,
+            _ => 400,
+        }
+    }
+
+    pub(crate) fn width_class(&self) -> u16 {
+        let setwidth = self.setwidth_name.as_deref().unwrap_or("").to_ascii_lowercase().replace([' ', '-'], "");
+
+        match setwidth.as_str() {
+            "ultracondensed" => 1,
+            "extracondensed" => 2,
+            "condensed" | "narrow" => 3,
+            "semicondensed" => 4,
+            "semiexpanded" => 6,
+            "expanded" | "wide" => 7,
+            "extraexpanded" => 8,
+            "ultraexpanded" => 9,
+            _ => 5,
+        }
+    }
+
+    pub(crate) fn is_italic(&self) -> bool {
+        let slant = self.slant.as_deref().unwrap_or("R").to_ascii_uppercase();
+        matches!(slant.as_str(), "I" | "O" | "RI" | "RO")
+    }
+}
+
+// the other way round, regular weights are called medium as in most X fonts
+pub(crate) fn weight_name(class: u16) -> &'static str {
+    match class {
+        0 => "Medium",
+        1..=150 => "Thin",
+        151..=250 => "ExtraLight",
+        251..=350 => "Light",
+        351..=550 => "Medium",
+        551..=650 => "SemiBold",
+        651..=750 => "Bold",
+        751..=850 => "ExtraBold",
+        _ => "Black",
+    }
+}
+
+// a lint rather than a requirement, writing a font does not run it as fonts that parse
+//   should save whatever their names look like
+impl BdfBlock for Xlfd {
+    fn validate(&self) -> Result<(), Error> {
+        use Error::XlfdValidation;
+
+        for (_, field) in self.fields().iter() {
+            if let Some(field) = field {
+                if field.contains('-') {
+                    return Err(XlfdValidation("field contains '-'"));
+                }
+            }
+        }
+
+        if let Some(slant) = &self.slant {
+            let slant = slant.to_ascii_uppercase();
+            if !SLANTS.contains(&slant.as_str()) {
+                return Err(XlfdValidation("SLANT must be one of R, I, O, RI, RO or OT"));
+            }
+        }
+
+        if let Some(spacing) = &self.spacing {
+            let spacing = spacing.to_ascii_uppercase();
+            if !SPACINGS.contains(&spacing.as_str()) {
+                return Err(XlfdValidation("SPACING must be one of P, M or C"));
+            }
+        }
+
+        let sizes = [self.pixel_size, self.point_size, self.resolution_x, self.resolution_y];
+        if sizes.iter().any(|v| matches!(v, Some(v) if *v < 0)) {
+            return Err(XlfdValidation("sizes and resolutions cannot be negative"));
+        }
+
+        // point size is in decipoints, 722.7 of them to an inch
+        //   names round the point size and fonts are often cut a size off, 9x18 is 12 points at 100 dpi,
+        //   so allow a tenth or a pixel of slack
+        if let (Some(pixel), Some(point), Some(res_y)) = (self.pixel_size, self.point_size, self.resolution_y) {
+            if pixel != 0 && point != 0 && res_y != 0 {
+                let expected = f64::from(point) * f64::from(res_y) / 722.7;
+                if (f64::from(pixel) - expected).abs() > (expected / 10.0).max(1.0) {
+                    return Err(XlfdValidation("PIXEL_SIZE does not agree with POINT_SIZE and RESOLUTION_Y"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Xlfd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Error::*;
+
+        let rest = match s.strip_prefix('-') {
+            Some(rest) => rest,
+            None => return Err(XlfdValidation("name must start with '-'")),
+        };
+
+        let fields: Vec<&str> = rest.split('-').collect();
+        if fields.len() != 14 {
+            return Err(XlfdValidation("name must have 14 fields"));
+        }
+
+        let string = |i: usize| Some(String::from(fields[i])).filter(|f| !f.is_empty());
+        let int = |i: usize, id: &'static str| match fields[i] {
+            "" => Ok(None),
+            f => f.parse().map(Some)
+                          .map_err(|_| InvalidValue(String::from(id), Box::new(ParseError("integer")))),
+        };
+
+        Ok(Xlfd {
+            foundry: string(0),
+            family_name: string(1),
+            weight_name: string(2),
+            slant: string(3),
+            setwidth_name: string(4),
+            add_style_name: string(5),
+            pixel_size: int(6, ids::xlfd::PIXEL_SIZE)?,
+            point_size: int(7, ids::xlfd::POINT_SIZE)?,
+            resolution_x: int(8, ids::xlfd::RESOLUTION_X)?,
+            resolution_y: int(9, ids::xlfd::RESOLUTION_Y)?,
+            spacing: string(10),
+            average_width: int(11, ids::xlfd::AVERAGE_WIDTH)?,
+            charset_registry: string(12),
+            charset_encoding: string(13),
+        })
+    }
+}
+
+impl<'a> fmt::Display for ForBdf<'a, Xlfd> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for property in self.0.properties() {
+            write!(f, "{}", property.for_bdf().map_err(|_| fmt::Error)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+//
+
+// X11 style name matching, '*' matches any run of characters including '-' and '?' any one character
+impl Xlfd {
+    pub fn matches(&self, pattern: &str) -> bool {
+        glob(pattern, &self.to_string())
+    }
+}
+
+fn glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+//
+
+// indices of the size fields in an xlfd name
+const PIXEL: usize = 6;
+const POINT: usize = 7;
+const RES_X: usize = 8;
+const RES_Y: usize = 9;
+const AVERAGE: usize = 11;
+
+fn requested_pixels(pattern: &[&str], font: &Xlfd) -> Option<f64> {
+    if let Ok(pixel) = pattern[PIXEL].parse::<i32>() {
+        return Some(f64::from(pixel));
+    }
+
+    let point = pattern[POINT].parse::<i32>().ok()?;
+    let res_y = pattern[RES_Y].parse::<i32>()
+                              .ok()
+                              .or(font.resolution_y)
+                              .unwrap_or(75);
+
+    Some(f64::from(point) * f64::from(res_y) / 722.7)
+}
+
+// orders the fonts matching a pattern best first, the way the X server picks among them
+//   fonts matching the pattern outright come first, then fonts that only differ in size,
+//   scalable fonts (PIXEL_SIZE 0) count as an exact size and otherwise the nearest size wins
+pub fn rank_fonts<'a>(fonts: &'a [Font], pattern: &str) -> Vec<&'a Font> {
+    let fields: Option<Vec<&str>> = pattern.strip_prefix('-')
+                                           .map(|rest| rest.split('-').collect())
+                                           .filter(|fields: &Vec<&str>| fields.len() == 14);
+
+    let mut ranked: Vec<(bool, f64, &Font)> = Vec::new();
+
+    for font in fonts {
+        let exact = font.xlfd.matches(pattern);
+
+        let fields = match &fields {
+            Some(fields) => fields,
+            None => {
+                if exact {
+                    ranked.push((false, 0.0, font));
+                }
+                continue;
+            }
+        };
+
+        let named = font.xlfd.fields();
+        let scaled = [PIXEL, POINT, RES_X, RES_Y, AVERAGE];
+
+        let others_match = fields.iter()
+                                 .zip(named.iter())
+                                 .enumerate()
+                                 .filter(|(i, _)| !scaled.contains(i))
+                                 .all(|(_, (p, (_, f)))| glob(p, f.as_deref().unwrap_or("")));
+        if !exact && !others_match {
+            continue;
+        }
+
+        let distance = match (requested_pixels(fields, &font.xlfd), font.xlfd.pixel_size) {
+            (_, Some(0)) => 0.0,
+            (Some(want), Some(have)) => (want - f64::from(have)).abs(),
+            (Some(_), None) => f64::INFINITY,
+            (None, _) => 0.0,
+        };
+
+        ranked.push((!exact, distance, font));
+    }
+
+    ranked.sort_by(|a, b| {
+        a.0.cmp(&b.0).then(a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+    });
+
+    ranked.into_iter().map(|(_, _, font)| font).collect()
+}
@@ -0,0 +1,400 @@
+use std::io::{
+    BufReader,
+    Cursor,
+};
+
+use bdf_font::{
+    BdfBlock,
+    BdfReader,
+    BdfWriter,
+    Encoding,
+    Error,
+    ParseOptions,
+    Severity,
+    Font,
+    Glyph,
+};
+
+mod common;
+
+use common::{
+    glyph,
+    SMALL,
+};
+
+//
+
+fn pixels(glyph: &Glyph) -> Vec<String> {
+    (0..glyph.bitmap.height()).map(|y| {
+        (0..glyph.bitmap.width()).map(|x| if glyph.bitmap.get(x, y) == Some(true) { '#' } else { '.' }).collect()
+    }).collect()
+}
+
+//
+
+// every pair of hex digits is one byte of the row
+#[test]
+fn bitmap_rows() {
+    let input = SMALL.replacen("BBX 5 5 0 0\nBITMAP\n88\n50\n20\n50\n88\n", "BBX 12 3 0 0\nBITMAP\n8010\n7FE0\nA5A0\n", 1);
+    let font = bdf_font::parse_font(&input).unwrap();
+
+    assert_eq!(pixels(glyph(&font, 'x')), [
+        "#..........#",
+        ".##########.",
+        "#.#..#.##.#.",
+    ]);
+}
+
+// a reader handing out a few bytes at a time sees the same font as the whole input
+#[test]
+fn reader_matches_parse_font() {
+    let whole = bdf_font::parse_font(SMALL).unwrap();
+    let read = BdfReader::new(BufReader::with_capacity(5, SMALL.as_bytes())).read_font().unwrap();
+
+    assert_eq!(read.name, whole.name);
+    assert_eq!(read.glyphs.len(), whole.glyphs.len());
+    for (a, b) in whole.glyphs.iter().zip(&read.glyphs) {
+        assert_eq!(a.encoding, b.encoding);
+        assert_eq!(pixels(a), pixels(b));
+    }
+}
+
+#[test]
+fn header_then_glyphs() {
+    let (header, glyphs) = BdfReader::new(SMALL.as_bytes()).read_header().unwrap();
+
+    assert_eq!(header.name, "-test-small-medium-r-normal--10-100-75-75-c-60-iso10646-1");
+    let bbx = &header.bounding_box;
+    assert_eq!((bbx.width, bbx.height, bbx.x_offset, bbx.y_offset), (6, 10, 0, -2));
+    assert_eq!(header.xlfd.family_name.as_deref(), Some("small"));
+
+    let codes: Vec<Encoding> = glyphs.map(|g| g.unwrap().encoding).collect();
+    assert_eq!(codes, [32, 63, 65, 72, 103, 105, 120, 233].map(Encoding::Standard));
+}
+
+// nothing past the glyph handed out has been read
+#[test]
+fn glyphs_are_read_lazily() {
+    let mut cursor = Cursor::new(SMALL.as_bytes());
+
+    let (_, mut glyphs) = BdfReader::new(&mut cursor).read_header().unwrap();
+    assert_eq!(glyphs.next().unwrap().unwrap().encoding, Encoding::Standard(32));
+    drop(glyphs);
+
+    let end = SMALL.find("ENDCHAR\n").unwrap() + "ENDCHAR\n".len();
+    assert_eq!(cursor.position() as usize, end);
+}
+
+// glyphs before a broken one still come out, then the error with its line, then nothing
+#[test]
+fn glyphs_stop_at_error() {
+    let input = SMALL.replacen("BBX 5 5 0 0", "BBX 5 five 0 0", 1);
+    let (_, mut glyphs) = BdfReader::new(input.as_bytes()).read_header().unwrap();
+
+    for _ in 0..6 {
+        assert!(glyphs.next().unwrap().is_ok());
+    }
+    let err = glyphs.next().unwrap().unwrap_err();
+    assert_eq!(err.line, SMALL.lines().position(|l| l == "BBX 5 5 0 0").unwrap() + 1);
+    assert!(glyphs.next().is_none());
+}
+
+// a count far beyond the glyphs in the file does not size an allocation up front
+#[test]
+fn huge_chars_count() {
+    let input = SMALL.replacen("CHARS 8", "CHARS 1000000000000", 1);
+    assert_eq!(bdf_font::parse_font(&input).unwrap().glyphs.len(), 8);
+}
+
+// the number of lines between `start` and `end`
+fn block_len(text: &str, start: &str, end: &str) -> usize {
+    let lines: Vec<&str> = text.lines().collect();
+    let from = lines.iter().position(|l| l.starts_with(start)).unwrap();
+    let to = lines.iter().position(|l| l.starts_with(end)).unwrap();
+    to - from - 1
+}
+
+// the counts match what is written, the xlfd fields included among the properties
+#[test]
+fn writer_counts() {
+    let font = bdf_font::parse_font(SMALL).unwrap();
+    let out = String::from_utf8(BdfWriter::new(Vec::new()).write_font(&font).unwrap()).unwrap();
+
+    let props = block_len(&out, "STARTPROPERTIES", "ENDPROPERTIES");
+    assert_eq!(props, 17);
+    assert!(out.contains(&format!("STARTPROPERTIES {}\n", props)));
+    assert!(out.contains("CHARS 8\n"));
+    assert_eq!(out.matches("STARTCHAR ").count(), 8);
+
+    let read = bdf_font::parse_font(&out).unwrap();
+    assert_eq!(read.glyphs.len(), font.glyphs.len());
+    for (a, b) in font.glyphs.iter().zip(&read.glyphs) {
+        assert_eq!(pixels(a), pixels(b));
+    }
+}
+
+// glyphs are written one at a time, no more and no fewer than the header declares
+#[test]
+fn writer_streams_glyphs() {
+    let (header, glyphs) = BdfReader::new(SMALL.as_bytes()).read_header().unwrap();
+    let glyphs: Vec<Glyph> = glyphs.map(Result::unwrap).collect();
+
+    let mut writer = BdfWriter::new(Vec::new());
+    writer.write_header(&header, 2).unwrap();
+    writer.write_glyph(&glyphs[0]).unwrap();
+    writer.write_glyph(&glyphs[1]).unwrap();
+    assert!(writer.write_glyph(&glyphs[2]).is_err());
+    let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+    assert!(out.contains("CHARS 2\n"));
+    assert!(out.ends_with("ENDCHAR\nENDFONT\n"));
+    assert_eq!(bdf_font::parse_font(&out).unwrap().glyphs.len(), 2);
+
+    let mut writer = BdfWriter::new(Vec::new());
+    writer.write_header(&header, 2).unwrap();
+    writer.write_glyph(&glyphs[0]).unwrap();
+    assert!(writer.finish().is_err());
+}
+
+// comments where they were, properties out of the usual order, an unknown keyword,
+//   odd spacing and lower case hex, all with CRLF line ends
+fn untidy() -> String {
+    SMALL.replacen("FOUNDRY \"test\"\n", "DEFAULT_CHAR 63\nCOMMENT among the properties\nFOUNDRY \"test\"\n", 1)
+         .replacen("DEFAULT_CHAR 63\nENDPROPERTIES", "ENDPROPERTIES", 1)
+         .replacen("STARTCHAR A\n", "COMMENT before A\nSTARTCHAR A\nATTRIBUTES 0000\n", 1)
+         .replacen("BBX 5 7 1 0", "BBX  3 7   1 0", 1)
+         .replace("F8\n", "f8\n")
+         .replace('\n', "\r\n")
+}
+
+fn read_lossless(input: &str) -> Font {
+    let options = ParseOptions {
+        lossless: true,
+        ..ParseOptions::default()
+    };
+    BdfReader::with_options(input.as_bytes(), options).read_font().unwrap()
+}
+
+#[test]
+fn lossless_round_trip() {
+    let input = untidy();
+    let font = read_lossless(&input);
+
+    let out = BdfWriter::new(Vec::new()).write_font(&font).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), input);
+}
+
+// an edit only changes the lines it touches
+#[test]
+fn lossless_edit() {
+    let input = untidy();
+    let mut font = read_lossless(&input);
+
+    let a = font.glyphs.iter_mut().find(|g| g.name == "A").unwrap();
+    a.bitmap.set(2, 0, false);
+
+    let out = String::from_utf8(BdfWriter::new(Vec::new()).write_font(&font).unwrap()).unwrap();
+    let changed: Vec<(&str, &str)> = input.lines().zip(out.lines()).filter(|(a, b)| a != b).collect();
+    assert_eq!(input.lines().count(), out.lines().count());
+    assert_eq!(changed, [("20", "00")]);
+}
+
+// a latin-1 comment kept verbatim still displays, with the byte replaced
+#[test]
+fn lossless_display_non_utf8() {
+    let mut input = SMALL.replacen("STARTCHAR A\n", "COMMENT caf\u{0}\nSTARTCHAR A\n", 1).into_bytes();
+    let at = input.iter().position(|&b| b == 0).unwrap();
+    input[at] = 0xe9;
+
+    let options = ParseOptions {
+        lossless: true,
+        ..ParseOptions::default()
+    };
+    let font = BdfReader::with_options(&input[..], options).read_font().unwrap();
+
+    let shown = font.for_bdf().unwrap().to_string();
+    assert!(shown.contains("COMMENT caf\u{fffd}\n"));
+}
+
+fn lenient() -> ParseOptions {
+    ParseOptions {
+        strict: false,
+        ..ParseOptions::default()
+    }
+}
+
+fn line_of(text: &str, line: &str) -> usize {
+    text.lines().position(|l| l == line).unwrap() + 1
+}
+
+// strict stops at an unknown keyword, lenient skips it and says where it was
+#[test]
+fn lenient_unknown_keyword() {
+    let input = SMALL.replacen("DWIDTH 6 0\nBBX 5 7 0 0\nBITMAP\n20", "DWIDTH 6 0\nWIBBLE 1\nBBX 5 7 0 0\nBITMAP\n20", 1);
+    let line = line_of(&input, "WIBBLE 1");
+
+    let err = bdf_font::parse_font(&input).unwrap_err();
+    assert_eq!(err.line, line);
+    assert!(matches!(err.error, Error::UnexpectedEntry(ref id) if id == "WIBBLE"));
+
+    let (font, warnings) = bdf_font::parse_font_with(&input, lenient()).unwrap();
+    assert_eq!(font.glyphs.len(), 8);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, line);
+    assert!(matches!(warnings[0].error, Error::UnexpectedEntry(ref id) if id == "WIBBLE"));
+}
+
+// a bitmap with rows missing is filled out with blank ones
+#[test]
+fn lenient_short_bitmap() {
+    let input = SMALL.replacen("BITMAP\n88\n50\n20\n50\n88\n", "BITMAP\n88\n50\n", 1);
+    assert!(bdf_font::parse_font(&input).is_err());
+
+    let (font, warnings) = bdf_font::parse_font_with(&input, lenient()).unwrap();
+    assert_eq!(pixels(glyph(&font, 'x')), [
+        "#...#",
+        ".#.#.",
+        ".....",
+        ".....",
+        ".....",
+    ]);
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(warnings[0].error, Error::CountMismatch("BITMAP", 5, 2)));
+}
+
+// counts that disagree with the file are let through either way, and reported when lenient
+#[test]
+fn lenient_count_mismatch() {
+    let input = SMALL.replacen("CHARS 8", "CHARS 9", 1)
+                     .replacen("STARTPROPERTIES 17", "STARTPROPERTIES 16", 1);
+    assert_eq!(bdf_font::parse_font(&input).unwrap().glyphs.len(), 8);
+
+    let (_, warnings) = bdf_font::parse_font_with(&input, lenient()).unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert!(matches!(warnings[0].error, Error::CountMismatch("STARTPROPERTIES", 16, 17)));
+    assert!(matches!(warnings[1].error, Error::CountMismatch("CHARS", 9, 8)));
+}
+
+// a missing ENDFONT is let through, and a glyph cut off before its ENDCHAR is kept with blank rows
+#[test]
+fn lenient_truncated() {
+    let end = SMALL.find("ENDFONT").unwrap();
+    assert_eq!(bdf_font::parse_font(&SMALL[..end]).unwrap().glyphs.len(), 8);
+
+    let end = SMALL.find("BITMAP\n88\n50").unwrap() + "BITMAP\n88\n50\n".len();
+    let input = &SMALL[..end];
+    assert!(bdf_font::parse_font(input).is_err());
+
+    let (font, warnings) = bdf_font::parse_font_with(input, lenient()).unwrap();
+    assert_eq!(pixels(glyph(&font, 'x')), [
+        "#...#",
+        ".#.#.",
+        ".....",
+        ".....",
+        ".....",
+    ]);
+    assert!(matches!(warnings[0].error, Error::MissingEntry(ref id) if id == "ENDCHAR"));
+    assert!(matches!(warnings[1].error, Error::MissingEntry(ref id) if id == "ENDFONT"));
+    assert!(matches!(warnings[2].error, Error::CountMismatch("CHARS", 8, 7)));
+}
+
+// rows past the height of the bitmap make one warning with how many there were
+#[test]
+fn lenient_surplus_rows() {
+    let input = SMALL.replacen("BITMAP\n88\n50\n20\n50\n88\n", "BITMAP\n88\n50\n20\n50\n88\n00\n00\n00\n", 1);
+    // the first of them, past BITMAP and the five rows
+    let line = line_of(SMALL, "BBX 5 5 0 0") + 7;
+
+    let err = bdf_font::parse_font(&input).unwrap_err();
+    assert_eq!(err.line, line);
+    assert!(matches!(err.error, Error::CountMismatch("BITMAP", 5, 8)));
+
+    let (font, warnings) = bdf_font::parse_font_with(&input, lenient()).unwrap();
+    assert_eq!(pixels(glyph(&font, 'x')).len(), 5);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, line);
+    assert!(matches!(warnings[0].error, Error::CountMismatch("BITMAP", 5, 8)));
+}
+
+// anything after ENDFONT is reported on its own line
+#[test]
+fn content_after_endfont() {
+    let input = format!("{}\nSTARTCHAR extra\nENDCHAR\n", SMALL);
+    let line = line_of(&input, "STARTCHAR extra");
+
+    let err = bdf_font::parse_font(&input).unwrap_err();
+    assert_eq!(err.line, line);
+    assert!(matches!(err.error, Error::UnexpectedEntry(ref id) if id == "STARTCHAR"));
+
+    // one warning for all of it
+    let (font, warnings) = bdf_font::parse_font_with(&input, lenient()).unwrap();
+    assert_eq!(font.glyphs.len(), 8);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, line);
+    assert!(matches!(warnings[0].error, Error::UnexpectedEntry(ref id) if id == "STARTCHAR"));
+}
+
+// the line, the column of the bad value and a caret under it, then the cause
+#[test]
+fn diagnostic_display() {
+    let input = SMALL.replacen("BBX 5 5 0 0", "BBX 5 five 0 0", 1);
+    let err = bdf_font::parse_font(&input).unwrap_err();
+
+    assert_eq!(err.line, 106);
+    assert_eq!(err.column, 5);
+    assert_eq!(err.token, "5 five 0 0");
+    assert!(matches!(err.error, Error::InvalidValue(ref id, _) if id == "BBX"));
+    assert!(std::error::Error::source(&err).is_some());
+
+    assert_eq!(err.to_string(), [
+        "error: invalid value for BBX",
+        "   --> line 106, column 5",
+        "    |",
+        "106 | BBX 5 five 0 0",
+        "    |     ^^^^^^^^^^ expected W:integer H:integer X:integer Y:integer",
+    ].join("\n"));
+}
+
+// a keyword out of place is pointed at itself
+#[test]
+fn diagnostic_column() {
+    let input = SMALL.replacen("STARTCHAR A\n", "STARTCHAR A\n  ENDPROPERTIES\n", 1);
+    let err = bdf_font::parse_font(&input).unwrap_err();
+
+    assert_eq!(err.line, line_of(&input, "  ENDPROPERTIES"));
+    assert_eq!((err.column, err.token.as_str()), (3, "ENDPROPERTIES"));
+    assert!(err.to_string().ends_with("|   ^^^^^^^^^^^^^"));
+}
+
+// warnings say so, and show their line like errors do
+#[test]
+fn diagnostic_warning() {
+    let input = SMALL.replacen("CHARS 8", "CHARS 9", 1);
+    let (_, warnings) = bdf_font::parse_font_with(&input, lenient()).unwrap();
+
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert_eq!(warnings[0].to_string(), [
+        "warning: CHARS declares 9 but 8 found",
+        "   --> line 129, column 1",
+        "    |",
+        "129 | ENDFONT",
+        "    | ^^^^^^^",
+    ].join("\n"));
+}
+
+// glyphs outside the charset are kept, with or without a code of their own, and written back as they were
+#[test]
+fn unencoded_glyphs() {
+    let input = SMALL.replacen("ENCODING 72\n", "ENCODING -1 72\n", 1)
+                     .replacen("ENCODING 103\n", "ENCODING -1\n", 1);
+    let font = bdf_font::parse_font(&input).unwrap();
+
+    let encodings: Vec<Encoding> = font.glyphs.iter().map(|g| g.encoding).collect();
+    assert_eq!(encodings[3..5], [Encoding::NonStandard(72), Encoding::Unencoded]);
+    assert_eq!(encodings[3].code(), None);
+    assert_eq!(encodings[2].to_char(&font.xlfd), Some('A'));
+
+    let out = String::from_utf8(BdfWriter::new(Vec::new()).write_font(&font).unwrap()).unwrap();
+    assert!(out.contains("ENCODING -1 72\n"));
+    assert!(out.contains("ENCODING -1\n"));
+}
@@ -0,0 +1,160 @@
+use bdf_font::{
+    charset::Charset,
+    Encoding,
+    Error,
+    Font,
+    PropertyValue,
+};
+
+mod common;
+
+use common::{
+    fixture,
+    glyph,
+};
+
+//
+
+// a one pixel glyph per (name, code)
+fn legacy_font(name: &str, registry: &str, encoding: &str, default_char: u32, glyphs: &[(&str, i32)]) -> Font {
+    let mut text = format!("\
+STARTFONT 2.1
+FONT {}
+SIZE 10 75 75
+FONTBOUNDINGBOX 6 10 0 -2
+STARTPROPERTIES 5
+CHARSET_REGISTRY \"{}\"
+CHARSET_ENCODING \"{}\"
+FONT_ASCENT 8
+FONT_DESCENT 2
+DEFAULT_CHAR {}
+ENDPROPERTIES
+CHARS {}
+", name, registry, encoding, default_char, glyphs.len());
+
+    for (name, code) in glyphs {
+        text += &format!("STARTCHAR {}\nENCODING {}\nSWIDTH 576 0\nDWIDTH 6 0\nBBX 1 1 0 0\nBITMAP\n80\nENDCHAR\n", name, code);
+    }
+    text += "ENDFONT\n";
+
+    bdf_font::parse_font(&text).unwrap()
+}
+
+fn default_char(font: &Font) -> Option<&PropertyValue> {
+    font.properties.iter().find(|p| p.name == "DEFAULT_CHAR").map(|p| &p.value)
+}
+
+//
+
+#[test]
+fn registries() {
+    assert_eq!(Charset::from_registry("ISO8859", "1"), Some(Charset::Iso8859(1)));
+    assert_eq!(Charset::from_registry("iso8859", "15"), Some(Charset::Iso8859(15)));
+    assert_eq!(Charset::from_registry("ISO8859", "12"), None);
+    assert_eq!(Charset::from_registry("koi8", "r"), Some(Charset::Koi8R));
+    assert_eq!(Charset::from_registry("KOI8", "U"), Some(Charset::Koi8U));
+    assert_eq!(Charset::from_registry("MICROSOFT", "CP1251"), Some(Charset::Cp1251));
+    assert_eq!(Charset::from_registry("JISX0208.1983", "0"), Some(Charset::JisX0208));
+    assert_eq!(Charset::from_registry("JISX0208.1990", "2"), None);
+    assert_eq!(Charset::from_registry("BIG5", "0"), Some(Charset::Big5));
+    assert_eq!(Charset::from_registry("ISO10646", "1"), None);
+    assert_eq!(Charset::from_registry("FOO", "1"), None);
+}
+
+#[test]
+fn single_byte_tables() {
+    let cases = [
+        (Charset::Iso8859(1), 0xe9, Some('é')),
+        (Charset::Iso8859(15), 0xa4, Some('€')),
+        (Charset::Iso8859(5), 0xb0, Some('А')),
+        (Charset::Koi8R, 0x41, Some('A')),
+        (Charset::Koi8R, 0xc1, Some('а')),
+        (Charset::Koi8R, 0xe1, Some('А')),
+        (Charset::Koi8R, 0xa3, Some('ё')),
+        (Charset::Koi8U, 0xa4, Some('є')),
+        (Charset::Cp1251, 0xc0, Some('А')),
+        (Charset::Cp1251, 0xff, Some('я')),
+        (Charset::Cp1251, 0x80, Some('Ђ')),
+        (Charset::Cp1251, 0x98, None),
+        (Charset::Cp1252, 0x80, Some('€')),
+        (Charset::JisX0201, 0x5c, Some('¥')),
+        (Charset::JisX0201, 0xb1, Some('ｱ')),
+        (Charset::Koi8R, 0x100, None),
+    ];
+
+    for (charset, code, expected) in cases {
+        assert_eq!(charset.to_unicode(code), expected, "{:?} {:#x}", charset, code);
+    }
+}
+
+// the 94x94 sets take both the GL and the GR form of a code
+#[test]
+fn double_byte_tables() {
+    let cases = [
+        (Charset::JisX0208, 0x2121, Some('\u{3000}')),
+        (Charset::JisX0208, 0x2422, Some('あ')),
+        (Charset::JisX0208, 0xa4a2, Some('あ')),
+        (Charset::JisX0208, 0x3021, Some('亜')),
+        (Charset::JisX0208, 0x2020, None),
+        (Charset::Gb2312, 0x3021, Some('啊')),
+        (Charset::Ksc5601, 0x3021, Some('가')),
+        (Charset::Big5, 0xa440, Some('一')),
+        (Charset::Big5, 0xa441, Some('乙')),
+        (Charset::Big5, 0xa140, Some('\u{3000}')),
+        (Charset::Big5, 0xa100, None),
+        (Charset::Big5, 0x4140, None),
+    ];
+
+    for (charset, code, expected) in cases {
+        assert_eq!(charset.to_unicode(code), expected, "{:?} {:#x}", charset, code);
+    }
+}
+
+// glyphs, DEFAULT_CHAR and the FONT name move to ISO10646-1, glyphs without a mapping are reported
+#[test]
+fn convert_to_unicode() {
+    let mut font = legacy_font("-test-cyr-medium-r-normal--10-100-75-75-c-60-microsoft-cp1251", "MICROSOFT", "CP1251", 0xc0,
+                               &[("A", 0x41), ("afii10017", 0xc0), ("afii10049", 0xff), ("unused", 0x98), ("extra", -1)]);
+
+    let unmapped = font.convert_to_unicode().unwrap();
+    assert_eq!(unmapped.iter().map(|u| (u.name.as_str(), u.code)).collect::<Vec<_>>(), [("unused", 0x98)]);
+
+    let encodings: Vec<Encoding> = font.glyphs.iter().map(|g| g.encoding).collect();
+    assert_eq!(encodings, [
+        Encoding::Standard(0x41),
+        Encoding::Standard(0x410),
+        Encoding::Standard(0x44f),
+        Encoding::NonStandard(0x98),
+        Encoding::Unencoded,
+    ]);
+
+    assert_eq!(default_char(&font), Some(&PropertyValue::Int(0x410)));
+    assert_eq!(font.name, "-test-cyr-medium-r-normal--10-100-75-75-c-60-iso10646-1");
+    assert!(font.xlfd.is_unicode());
+    assert_eq!(font.glyph_for('я').unwrap().name, "afii10049");
+
+    // a second conversion has nothing left to do
+    assert!(font.convert_to_unicode().unwrap().is_empty());
+}
+
+// upper case names stay upper case
+#[test]
+fn convert_keeps_name_case() {
+    let mut font = legacy_font("-TEST-CYR-MEDIUM-R-NORMAL--10-100-75-75-C-60-KOI8-R", "KOI8", "R", 0x41, &[("a", 0xc1)]);
+
+    assert!(font.convert_to_unicode().unwrap().is_empty());
+    assert_eq!(font.name, "-TEST-CYR-MEDIUM-R-NORMAL--10-100-75-75-C-60-ISO10646-1");
+    assert_eq!(font.glyphs[0].encoding, Encoding::Standard(0x430));
+    assert_eq!(default_char(&font), Some(&PropertyValue::Int(0x41)));
+}
+
+#[test]
+fn convert_unicode_and_unknown() {
+    let mut font = fixture();
+    assert!(font.convert_to_unicode().unwrap().is_empty());
+    assert_eq!(glyph(&font, 'é').name, "eacute");
+
+    let mut font = legacy_font("-test-odd-medium-r-normal--10-100-75-75-c-60-foo-1", "FOO", "1", 0x41, &[("A", 0x41)]);
+    assert!(matches!(font.convert_to_unicode(), Err(Error::UnsupportedCharset(name)) if name == "FOO-1"));
+    assert_eq!(font.glyphs[0].encoding, Encoding::Standard(0x41));
+}
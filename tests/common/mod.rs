@@ -0,0 +1,39 @@
+// helpers shared by the integration tests, each test file uses only some of them
+#![allow(dead_code)]
+
+use bdf_font::{
+    Font,
+    Glyph,
+};
+
+//
+
+pub const SMALL: &str = include_str!("../fixtures/small.bdf");
+
+pub fn fixture() -> Font {
+    bdf_font::parse_font(SMALL).unwrap()
+}
+
+// the fixture under a plain font name without charset properties
+pub fn bare() -> Font {
+    let text = SMALL
+        .replace("FONT -test-small-medium-r-normal--10-100-75-75-c-60-iso10646-1", "FONT small")
+        .replace("STARTPROPERTIES 17", "STARTPROPERTIES 15")
+        .replace("CHARSET_REGISTRY \"ISO10646\"\n", "")
+        .replace("CHARSET_ENCODING \"1\"\n", "");
+    bdf_font::parse_font(&text).unwrap()
+}
+
+pub fn glyph(font: &Font, c: char) -> &Glyph {
+    font.glyphs
+        .iter()
+        .find(|g| g.encoding.code() == Some(c as u32))
+        .unwrap_or_else(|| panic!("no glyph for {:?}", c))
+}
+
+pub fn glyph_mut(font: &mut Font, c: char) -> &mut Glyph {
+    font.glyphs
+        .iter_mut()
+        .find(|g| g.encoding.code() == Some(c as u32))
+        .unwrap_or_else(|| panic!("no glyph for {:?}", c))
+}
@@ -0,0 +1,163 @@
+#![cfg(feature = "embedded-graphics")]
+
+use embedded_graphics::{
+    mock_display::MockDisplay,
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{
+        renderer::TextRenderer,
+        Baseline,
+        Text,
+    },
+};
+
+use bdf_font::{
+    eg::{
+        BdfTextStyle,
+        EgFont,
+        MonoFontData,
+    },
+};
+
+mod common;
+
+use common::{
+    bare,
+    fixture,
+    glyph,
+    SMALL,
+};
+
+//
+
+// the ink of `text` as the display shows it, `#` for pixels drawn on, `.` for drawn off, space for untouched
+fn shown(display: &MockDisplay<BinaryColor>, width: i32, height: i32) -> Vec<String> {
+    (0..height).map(|y| {
+        (0..width).map(|x| match display.get_pixel(Point::new(x, y)) {
+            Some(BinaryColor::On) => '#',
+            Some(BinaryColor::Off) => '.',
+            None => ' ',
+        }).collect::<String>().trim_end().to_string()
+    }).collect()
+}
+
+//
+
+// glyphs sit on the baseline with their own offsets, the pen moves on by each advance
+#[test]
+fn draw_text() {
+    let font = fixture();
+    let prepared = EgFont::new(&font).unwrap();
+    let style = BdfTextStyle::new(&prepared, BinaryColor::On);
+
+    let mut display = MockDisplay::new();
+    let next = Text::with_baseline("Ag", Point::zero(), style.clone(), Baseline::Top).draw(&mut display).unwrap();
+    assert_eq!(next, Point::new(12, 0));
+
+    assert_eq!(shown(&display, 12, 10), [
+        "",
+        "  #",
+        " # #",
+        "#   #  ####",
+        "#   # #   #",
+        "##### #   #",
+        "#   #  ####",
+        "#   #     #",
+        "      #   #",
+        "       ###",
+    ]);
+
+    let metrics = style.measure_string("Ag", Point::new(0, 7), Baseline::Alphabetic);
+    assert_eq!(metrics.bounding_box.top_left, Point::zero());
+    assert_eq!(metrics.bounding_box.size, Size::new(12, 10));
+    assert_eq!(metrics.next_position, Point::new(12, 7));
+}
+
+// a background fills the whole cell, each pixel drawn once
+#[test]
+fn draw_text_with_background() {
+    let font = fixture();
+    let prepared = EgFont::new(&font).unwrap();
+    let mut style = BdfTextStyle::new(&prepared, BinaryColor::On);
+    style.background_color = Some(BinaryColor::Off);
+
+    let mut display = MockDisplay::new();
+    Text::with_baseline("i", Point::zero(), style, Baseline::Top).draw(&mut display).unwrap();
+
+    assert_eq!(shown(&display, 6, 10), [
+        "......",
+        "..#...",
+        "......",
+        ".##...",
+        "..#...",
+        "..#...",
+        "..#...",
+        ".###..",
+        "......",
+        "......",
+    ]);
+}
+
+// characters the font lacks are drawn with DEFAULT_CHAR
+#[test]
+fn draw_missing_char() {
+    let font = fixture();
+    let prepared = EgFont::new(&font).unwrap();
+    let style = BdfTextStyle::new(&prepared, BinaryColor::On);
+
+    let mut missing = MockDisplay::new();
+    Text::with_baseline("Z", Point::zero(), style.clone(), Baseline::Top).draw(&mut missing).unwrap();
+    let mut question = MockDisplay::new();
+    Text::with_baseline("?", Point::zero(), style, Baseline::Top).draw(&mut question).unwrap();
+
+    assert_eq!(shown(&missing, 6, 10), shown(&question, 6, 10));
+    assert_eq!(glyph(&font, '?').name, "question");
+}
+
+// a font without charset properties is drawn by its encodings as they are
+#[test]
+fn draw_without_charset() {
+    let font = bare();
+    let prepared = EgFont::new(&font).unwrap();
+    let mut drawn = MockDisplay::new();
+    Text::with_baseline("Ag", Point::zero(), BdfTextStyle::new(&prepared, BinaryColor::On), Baseline::Top)
+        .draw(&mut drawn)
+        .unwrap();
+
+    let font = fixture();
+    let prepared = EgFont::new(&font).unwrap();
+    let mut expected = MockDisplay::new();
+    Text::with_baseline("Ag", Point::zero(), BdfTextStyle::new(&prepared, BinaryColor::On), Baseline::Top)
+        .draw(&mut expected)
+        .unwrap();
+
+    assert_eq!(shown(&drawn, 12, 10), shown(&expected, 12, 10));
+}
+
+// a monospaced font converted to a MonoFont draws the same as the proportional renderer
+#[test]
+fn mono_font() {
+    let font = fixture();
+    let data = MonoFontData::new(&font).unwrap();
+    let mono = data.mono_font();
+    assert_eq!(mono.character_size, Size::new(6, 10));
+
+    let prepared = EgFont::new(&font).unwrap();
+    for text in ["AHgix", "é?Z"] {
+        let mut expected = MockDisplay::new();
+        Text::with_baseline(text, Point::zero(), BdfTextStyle::new(&prepared, BinaryColor::On), Baseline::Top)
+            .draw(&mut expected)
+            .unwrap();
+
+        let mut drawn = MockDisplay::new();
+        Text::with_baseline(text, Point::zero(), MonoTextStyle::new(&mono, BinaryColor::On), Baseline::Top)
+            .draw(&mut drawn)
+            .unwrap();
+
+        assert_eq!(shown(&drawn, 40, 10), shown(&expected, 40, 10), "{:?}", text);
+    }
+
+    let proportional = SMALL.replacen("DWIDTH 6 0\nBBX 5 7 0 0\nBITMAP\n20", "DWIDTH 7 0\nBBX 5 7 0 0\nBITMAP\n20", 1);
+    assert!(MonoFontData::new(&bdf_font::parse_font(&proportional).unwrap()).is_err());
+}
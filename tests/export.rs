@@ -0,0 +1,511 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use bdf_font::{
+    atlas::{
+        self,
+        Atlas,
+        AtlasOptions,
+        PageSize,
+        Rect,
+    },
+    bmfont::{
+        self,
+        BmFontFormat,
+        BmFontOptions,
+    },
+    png::{
+        self,
+        ColorType,
+    },
+    source::{
+        self,
+        SourceOptions,
+    },
+    Font,
+    Glyph,
+};
+
+mod common;
+
+use common::{
+    fixture,
+    glyph,
+    SMALL,
+};
+
+//
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = ((p - i16::from(a)).abs(), (p - i16::from(b)).abs(), (p - i16::from(c)).abs());
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// the size and pixels of an 8 bit png, undoing whichever filter each row was written with
+fn decode_png(data: &[u8], channels: usize) -> (usize, usize, Vec<u8>) {
+    assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+
+    let u32_at = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize;
+    let (width, height) = (u32_at(16), u32_at(20));
+
+    let mut compressed = Vec::new();
+    let mut at = 8;
+    while at < data.len() {
+        let len = u32_at(at);
+        if &data[(at + 4)..(at + 8)] == b"IDAT" {
+            compressed.extend_from_slice(&data[(at + 8)..(at + 8 + len)]);
+        }
+        at += 12 + len;
+    }
+
+    let mut filtered = Vec::new();
+    ZlibDecoder::new(&compressed[..]).read_to_end(&mut filtered).unwrap();
+
+    let stride = width * channels;
+    let mut pixels: Vec<u8> = Vec::with_capacity(stride * height);
+    for (y, row) in filtered.chunks(stride + 1).enumerate() {
+        for i in 0..stride {
+            let a = if i >= channels { pixels[y * stride + i - channels] } else { 0 };
+            let b = if y > 0 { pixels[(y - 1) * stride + i] } else { 0 };
+            let c = if i >= channels && y > 0 { pixels[(y - 1) * stride + i - channels] } else { 0 };
+
+            let predicted = match row[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                4 => paeth(a, b, c),
+                f => panic!("unknown filter {}", f),
+            };
+            pixels.push(row[i + 1].wrapping_add(predicted));
+        }
+    }
+
+    (width, height, pixels)
+}
+
+// the value of `key=` in a line of a text descriptor
+fn field(line: &str, key: &str) -> i32 {
+    line.split_whitespace()
+        .find_map(|kv| kv.strip_prefix(key).and_then(|v| v.strip_prefix('=')))
+        .unwrap_or_else(|| panic!("no {} in {:?}", key, line))
+        .parse()
+        .unwrap()
+}
+
+//
+
+#[test]
+fn png_round_trip() {
+    let (width, height) = (7, 5);
+    let pixels: Vec<u8> = (0..(width * height * 3)).map(|i| (i * 37 % 251) as u8).collect();
+
+    let data = png::write_png(Vec::new(), width as u32, height as u32, ColorType::Rgb, &pixels).unwrap();
+    assert_eq!(decode_png(&data, 3), (width, height, pixels));
+
+    assert!(png::write_png(Vec::new(), 2, 2, ColorType::Gray, &[0; 3]).is_err());
+}
+
+// every glyph is described where its ink is on the page, measured from the top of the line
+#[test]
+fn bmfont_text() {
+    let font = fixture();
+    let out = bmfont::export(&font, &BmFontOptions::default()).unwrap();
+    let text = String::from_utf8(out.descriptor).unwrap();
+
+    assert_eq!(out.pages.len(), 1);
+    assert_eq!(out.pages[0].file, "font_0.png");
+
+    let common = text.lines().find(|l| l.starts_with("common ")).unwrap();
+    assert_eq!((field(common, "lineHeight"), field(common, "base"), field(common, "pages")), (10, 8, 1));
+    assert!(text.contains("chars count=8\n"));
+    assert!(text.contains("info face=\"small\" size=10 "));
+
+    let (width, _, pixels) = decode_png(&out.pages[0].png, 4);
+    for c in ['A', 'g', 'é'] {
+        let line = text.lines().find(|l| l.starts_with(&format!("char id={} ", c as u32))).unwrap();
+        let g = glyph(&font, c);
+        let bbx = &g.bounding_box;
+
+        assert_eq!((field(line, "width"), field(line, "height")), (bbx.width as i32, bbx.height as i32));
+        assert_eq!(field(line, "yoffset"), 8 - (bbx.y_offset + bbx.height as i32));
+        assert_eq!(field(line, "xadvance"), 6);
+
+        let (x0, y0) = (field(line, "x") as usize, field(line, "y") as usize);
+        for y in 0..g.bitmap.height() {
+            for x in 0..g.bitmap.width() {
+                let alpha = pixels[((y0 + y) * width + x0 + x) * 4 + 3];
+                assert_eq!(alpha == 255, g.bitmap.get(x, y) == Some(true), "{:?} at {}, {}", c, x, y);
+            }
+        }
+    }
+}
+
+// glyphs that do not fit on one page carry on to the next
+#[test]
+fn bmfont_pages() {
+    let options = BmFontOptions {
+        page_width: 8,
+        page_height: 8,
+        ..BmFontOptions::default()
+    };
+    let out = bmfont::export(&fixture(), &options).unwrap();
+    assert!(out.pages.len() > 1);
+
+    // numbered to the same width
+    let names: Vec<&str> = out.pages.iter().map(|p| p.file.as_str()).collect();
+    assert_eq!(names[0], if out.pages.len() > 10 { "font_00.png" } else { "font_0.png" });
+    assert!(names.iter().all(|n| n.len() == names[0].len()));
+
+    let text = String::from_utf8(out.descriptor).unwrap();
+    let pages: Vec<i32> = text.lines().filter(|l| l.starts_with("char ")).map(|l| field(l, "page")).collect();
+    assert_eq!(pages.iter().max().map(|&p| p as usize + 1), Some(out.pages.len()));
+}
+
+// the same chars as the text descriptor in the other two formats
+#[test]
+fn bmfont_xml_and_binary() {
+    let font = fixture();
+
+    let options = BmFontOptions {
+        format: BmFontFormat::Xml,
+        ..BmFontOptions::default()
+    };
+    let xml = String::from_utf8(bmfont::export(&font, &options).unwrap().descriptor).unwrap();
+    assert!(xml.starts_with("<?xml version=\"1.0\"?>\n<font>\n"));
+    assert!(xml.contains("<chars count=\"8\">"));
+    assert_eq!(xml.matches("<char id=").count(), 8);
+
+    let options = BmFontOptions {
+        format: BmFontFormat::Binary,
+        ..BmFontOptions::default()
+    };
+    let binary = bmfont::export(&font, &options).unwrap().descriptor;
+    assert_eq!(&binary[..4], b"BMF\x03");
+
+    // blocks of a type byte and a length, chars are 20 bytes each
+    let mut at = 4;
+    let mut kinds = Vec::new();
+    while at < binary.len() {
+        let len = u32::from_le_bytes([binary[at + 1], binary[at + 2], binary[at + 3], binary[at + 4]]) as usize;
+        kinds.push((binary[at], len));
+        at += 5 + len;
+    }
+    assert_eq!(at, binary.len());
+    assert_eq!(kinds.iter().map(|&(k, _)| k).collect::<Vec<_>>(), [1, 2, 3, 4]);
+    assert_eq!(kinds[3].1, 8 * 20);
+}
+
+// every placed glyph shows its own bitmap on its page
+fn assert_placed(font: &Font, atlas: &Atlas, chars: &[char]) {
+    for &c in chars {
+        let (g, placed) = (glyph(font, c), atlas.get(c).unwrap());
+        assert_eq!((placed.rect.width as usize, placed.rect.height as usize), (g.bitmap.width(), g.bitmap.height()));
+        assert_eq!((placed.x_offset, placed.y_offset), (g.bounding_box.x_offset, g.bounding_box.y_offset));
+
+        let page = &atlas.pages[placed.page];
+        for y in 0..g.bitmap.height() {
+            for x in 0..g.bitmap.width() {
+                let on_page = page.get(placed.rect.x as usize + x, placed.rect.y as usize + y);
+                assert_eq!(on_page, g.bitmap.get(x, y), "{:?} at {}, {}", c, x, y);
+            }
+        }
+    }
+}
+
+// grown by `by` on every side
+fn grow(rect: &Rect, by: u32) -> (i64, i64, i64, i64) {
+    let by = i64::from(by);
+    (i64::from(rect.x) - by, i64::from(rect.y) - by, i64::from(rect.x + rect.width) + by, i64::from(rect.y + rect.height) + by)
+}
+
+#[test]
+fn atlas_pack() {
+    let font = fixture();
+    let options = AtlasOptions {
+        padding: 1,
+        spacing: 2,
+        ..AtlasOptions::default()
+    };
+    let atlas = atlas::pack(&font, &options).unwrap();
+
+    assert_eq!(atlas.pages.len(), 1);
+    assert!(atlas.pages[0].width().is_power_of_two() && atlas.pages[0].height().is_power_of_two());
+    assert_eq!(atlas.glyphs.len(), 8);
+    assert_placed(&font, &atlas, &['?', 'A', 'H', 'g', 'i', 'x', 'é']);
+    assert_eq!(atlas.get(' ').unwrap().rect, Rect::default());
+    assert_eq!(atlas.get('A').unwrap().advance, 6);
+
+    // padding stays inside the page and padded bitmaps keep the spacing between them
+    let placed: Vec<&Rect> = atlas.glyphs.values().map(|p| &p.rect).filter(|r| r.width > 0).collect();
+    for (i, a) in placed.iter().enumerate() {
+        let (x0, y0, x1, y1) = grow(a, 1);
+        assert!(x0 >= 0 && y0 >= 0 && x1 <= atlas.pages[0].width() as i64 && y1 <= atlas.pages[0].height() as i64);
+
+        for b in &placed[(i + 1)..] {
+            let (ax0, ay0, ax1, ay1) = grow(a, 2);
+            let (bx0, by0, bx1, by1) = grow(b, 1);
+            assert!(ax1 <= bx0 || bx1 <= ax0 || ay1 <= by0 || by1 <= ay0, "{:?} and {:?} overlap", a, b);
+        }
+    }
+}
+
+// glyphs drawn the same share a rectangle unless asked not to
+#[test]
+fn atlas_dedup() {
+    let input = SMALL.replacen("BITMAP\n88\n88\n88\nF8\n", "BITMAP\n20\n50\n88\n88\nF8\n", 1)
+                                                  .replacen("88\n88\n88\nENDCHAR\nSTARTCHAR g", "88\n88\nENDCHAR\nSTARTCHAR g", 1);
+    let font = bdf_font::parse_font(&input).unwrap();
+
+    let atlas = atlas::pack(&font, &AtlasOptions::default()).unwrap();
+    assert_eq!(atlas.get('A').unwrap().rect, atlas.get('H').unwrap().rect);
+
+    let options = AtlasOptions {
+        dedup: false,
+        ..AtlasOptions::default()
+    };
+    let atlas = atlas::pack(&font, &options).unwrap();
+    assert_ne!(atlas.get('A').unwrap().rect, atlas.get('H').unwrap().rect);
+    assert_placed(&font, &atlas, &['A', 'H']);
+}
+
+// only the characters asked for, across as many fixed size pages as it takes
+#[test]
+fn atlas_pack_chars() {
+    let font = fixture();
+    let options = AtlasOptions {
+        page_size: PageSize::Fixed(6, 8),
+        ..AtlasOptions::default()
+    };
+    let atlas = atlas::pack_chars(&font, &['A', 'H', 'x', 'z'], &options).unwrap();
+
+    assert_eq!(atlas.glyphs.keys().copied().collect::<Vec<char>>(), ['A', 'H', 'x']);
+    assert_eq!(atlas.pages.len(), 3);
+    assert!(atlas.pages.iter().all(|p| (p.width(), p.height()) == (6, 8)));
+    assert_placed(&font, &atlas, &['A', 'H', 'x']);
+
+    let options = AtlasOptions {
+        page_size: PageSize::Fixed(4, 4),
+        ..AtlasOptions::default()
+    };
+    assert!(atlas::pack_chars(&font, &['A'], &options).is_err());
+}
+
+// the hex bytes of the array that starts on the line containing `start`
+fn array_bytes(text: &str, start: &str) -> Vec<u8> {
+    text.lines()
+        .skip_while(|l| !l.contains(start))
+        .skip(1)
+        .take_while(|l| !l.trim_start().starts_with(['}', ']']))
+        .flat_map(|l| l.split(',').map(str::trim).filter(|v| !v.is_empty()))
+        .map(|v| u8::from_str_radix(v.trim_start_matches("0x").trim_start_matches("0X"), 16).unwrap())
+        .collect()
+}
+
+// the numbers in a line of source, signs included
+fn numbers(line: &str) -> Vec<i32> {
+    line.split(|c: char| !c.is_ascii_digit() && c != '-')
+        .filter(|v| !v.is_empty() && *v != "-")
+        .map(|v| v.parse().unwrap())
+        .collect()
+}
+
+// a bitmap's pixels drawn from `bytes` most significant bit first, starting at bit `from`
+fn bits_pixels(bytes: &[u8], from: usize, width: usize, height: usize) -> Vec<String> {
+    (0..height).map(|y| {
+        (0..width).map(|x| {
+            let i = from + y * width + x;
+            if bytes[i / 8] & (0x80 >> (i % 8)) != 0 { '#' } else { '.' }
+        }).collect()
+    }).collect()
+}
+
+fn pixels(glyph: &Glyph) -> Vec<String> {
+    (0..glyph.bitmap.height()).map(|y| {
+        (0..glyph.bitmap.width()).map(|x| if glyph.bitmap.get(x, y) == Some(true) { '#' } else { '.' }).collect()
+    }).collect()
+}
+
+fn a_to_h() -> SourceOptions {
+    SourceOptions {
+        name: String::from("small"),
+        range: Some('A'..='H'),
+    }
+}
+
+// one glyph entry for every character in the range, the missing ones empty, bitmaps packed without row padding
+#[test]
+fn gfx_source() {
+    let font = fixture();
+    let text = String::from_utf8(source::write_gfx(Vec::new(), &font, &a_to_h()).unwrap()).unwrap();
+
+    let bitmaps = array_bytes(&text, "const uint8_t smallBitmaps[] PROGMEM");
+    let glyphs: Vec<&str> = text.lines().skip_while(|l| !l.contains("smallGlyphs[]")).skip(1).take(8).collect();
+    assert_eq!(glyphs.iter().filter(|l| l.ends_with("missing")).count(), 6);
+
+    // offset, width, height, advance, x offset and the top of the bitmap from the baseline
+    let a = numbers(glyphs[0].split("//").next().unwrap());
+    let h = numbers(glyphs[7].split("//").next().unwrap());
+    assert_eq!(a, [0, 5, 7, 6, 0, -7]);
+    assert_eq!(h, [5, 5, 7, 6, 0, -7]);
+    assert_eq!(bitmaps.len(), 10);
+
+    assert_eq!(bits_pixels(&bitmaps, 0, 5, 7), pixels(glyph(&font, 'A')));
+    assert_eq!(bits_pixels(&bitmaps, 5 * 8, 5, 7), pixels(glyph(&font, 'H')));
+    assert!(text.ends_with("  0x41, 0x48, 10 };\n"));
+}
+
+// reads `count` bits least significant first
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            if self.bytes[self.at / 8] >> (self.at % 8) & 1 != 0 {
+                value |= 1 << i;
+            }
+            self.at += 1;
+        }
+        value
+    }
+
+    fn read_signed(&mut self, count: u32) -> i32 {
+        self.read(count) as i32 - (1 << (count - 1))
+    }
+}
+
+// the bytes of a C string literal split over lines, octal escapes and all
+fn c_string(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for line in text.lines().filter(|l| l.trim_start().starts_with('"')) {
+        let literal = line.trim().trim_end_matches(';');
+        let mut chars = literal[1..(literal.len() - 1)].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                let octal: String = chars.by_ref().take(3).collect();
+                bytes.push(u8::from_str_radix(&octal, 8).unwrap());
+            } else {
+                bytes.push(c as u8);
+            }
+        }
+    }
+    bytes
+}
+
+// a glyph of a u8g2 font decoded as u8g2 draws it
+#[test]
+fn u8g2_source() {
+    let font = fixture();
+    let text = String::from_utf8(source::write_u8g2(Vec::new(), &font, &a_to_h()).unwrap()).unwrap();
+    let data = c_string(&text);
+
+    assert!(text.contains(&format!("const uint8_t small[{}] U8G2_FONT_SECTION(\"small\") =", data.len() + 1)));
+    assert_eq!(data[0], 2);
+
+    let bits: Vec<u32> = data[2..9].iter().map(|&b| u32::from(b)).collect();
+    let (bits_0, bits_1, bits_w, bits_h, bits_x, bits_y, bits_advance) = (bits[0], bits[1], bits[2], bits[3], bits[4], bits[5], bits[6]);
+
+    // the glyphs below U+0100 follow the 23 byte header, each with its character and size
+    let mut at = 23;
+    for c in ['A', 'H'] {
+        assert_eq!(data[at], c as u8);
+        let mut reader = BitReader { bytes: &data[(at + 2)..(at + usize::from(data[at + 1]))], at: 0 };
+
+        let (width, height) = (reader.read(bits_w) as usize, reader.read(bits_h) as usize);
+        let (x, y, advance) = (reader.read_signed(bits_x), reader.read_signed(bits_y), reader.read_signed(bits_advance));
+        assert_eq!((width, height, x, y, advance), (5, 7, 0, 0, 6));
+
+        let mut drawn = String::new();
+        while drawn.len() < width * height {
+            let (zeros, ones) = (reader.read(bits_0) as usize, reader.read(bits_1) as usize);
+            loop {
+                drawn += &".".repeat(zeros);
+                drawn += &"#".repeat(ones);
+                if drawn.len() >= width * height || reader.read(1) == 0 {
+                    break;
+                }
+            }
+        }
+
+        let rows: Vec<String> = drawn.as_bytes().chunks(width).map(|r| String::from_utf8(r.to_vec()).unwrap()).collect();
+        assert_eq!(rows, pixels(glyph(&font, c)));
+
+        at += usize::from(data[at + 1]);
+    }
+    assert_eq!(&data[at..(at + 2)], [0, 0]);
+}
+
+// ranges of consecutive characters, glyph ids counted from 1
+#[test]
+fn lvgl_source() {
+    let font = fixture();
+    let text = String::from_utf8(source::write_lvgl(Vec::new(), &font, &a_to_h()).unwrap()).unwrap();
+
+    let bitmaps = array_bytes(&text, "glyph_bitmap[] = {");
+    assert_eq!(bits_pixels(&bitmaps, 5 * 8, 5, 7), pixels(glyph(&font, 'H')));
+
+    assert!(text.contains("{.bitmap_index = 0, .adv_w = 96, .box_w = 5, .box_h = 7, .ofs_x = 0, .ofs_y = 0} /* 0x41 'A' */"));
+    assert!(text.contains("{.bitmap_index = 5, .adv_w = 96, .box_w = 5, .box_h = 7, .ofs_x = 0, .ofs_y = 0} /* 0x48 'H' */"));
+    assert!(text.contains(".range_start = 65, .range_length = 1, .glyph_id_start = 1,"));
+    assert!(text.contains(".range_start = 72, .range_length = 1, .glyph_id_start = 2,"));
+    assert!(text.contains(".cmap_num = 2,"));
+    assert!(text.contains(".line_height = 10,\n    .base_line = 2,"));
+}
+
+// a byte per row for glyphs up to 8 wide
+#[test]
+fn rust_source() {
+    let font = fixture();
+    let text = String::from_utf8(source::write_rust(Vec::new(), &font, &a_to_h()).unwrap()).unwrap();
+
+    assert!(text.contains("pub mod small {"));
+    assert!(text.contains("pub const ASCENT: i32 = 8;"));
+    assert!(text.contains("Glyph { c: 'A', offset: 0, width: 5, height: 7, x_offset: 0, y_offset: 0, advance: 6 },"));
+    assert!(text.contains("Glyph { c: 'H', offset: 7, width: 5, height: 7, x_offset: 0, y_offset: 0, advance: 6 },"));
+
+    let bitmaps = array_bytes(&text, "pub static BITMAPS: [u8; 14]");
+    assert_eq!(bitmaps[..7], [0x20, 0x50, 0x88, 0x88, 0xf8, 0x88, 0x88]);
+}
+
+// rows read with more bytes than the width needs are written with just enough
+#[test]
+fn rust_source_padded_rows() {
+    let input = SMALL.replacen("BITMAP\n20\n50\n88\n88\nF8\n88\n88\n", "BITMAP\n2000\n5000\n8800\n8800\nF800\n8800\n8800\n", 1);
+    let font = bdf_font::parse_font(&input).unwrap();
+    let text = String::from_utf8(source::write_rust(Vec::new(), &font, &a_to_h()).unwrap()).unwrap();
+
+    assert!(text.contains("Glyph { c: 'H', offset: 7, "));
+    let bitmaps = array_bytes(&text, "pub static BITMAPS: [u8; 14]");
+    assert_eq!(bitmaps[..7], [0x20, 0x50, 0x88, 0x88, 0xf8, 0x88, 0x88]);
+}
+
+// names that are not identifiers and ranges without glyphs are refused
+#[test]
+fn source_options() {
+    let font = fixture();
+
+    let options = SourceOptions {
+        name: String::from("2small"),
+        ..SourceOptions::default()
+    };
+    assert!(source::write_gfx(Vec::new(), &font, &options).is_err());
+
+    let options = SourceOptions {
+        range: Some('0'..='9'),
+        ..SourceOptions::default()
+    };
+    assert!(source::write_rust(Vec::new(), &font, &options).is_err());
+}
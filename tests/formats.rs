@@ -0,0 +1,346 @@
+use std::{
+    collections::BTreeSet,
+    io::Write,
+};
+
+use flate2::{
+    write::GzEncoder,
+    Compression,
+};
+
+use bdf_font::{
+    fnt::{
+        self,
+        FntOptions,
+        FntVersion,
+    },
+    hex,
+    otb::{
+        self,
+        OtbOptions,
+    },
+    pcf::{
+        self,
+        PcfOptions,
+    },
+    psf::{
+        self,
+        PsfOptions,
+        PsfVersion,
+    },
+    Encoding,
+    Error,
+    Font,
+    Glyph,
+};
+
+mod common;
+
+use common::{
+    fixture,
+    glyph,
+    SMALL,
+};
+
+//
+
+const CHARS: [char; 8] = [' ', '?', 'A', 'H', 'g', 'i', 'x', 'é'];
+
+fn advance(font: &Font, glyph: &Glyph) -> i32 {
+    glyph.device_width.or(font.device_width).map(|d| d.x).unwrap_or(0)
+}
+
+// the inked pixels from the origin with y growing up, which stay put when a format changes the cell around them
+fn ink(glyph: &Glyph) -> BTreeSet<(i32, i32)> {
+    let bbx = &glyph.bounding_box;
+    let top = bbx.y_offset + bbx.height as i32 - 1;
+
+    let mut ink = BTreeSet::new();
+    for y in 0..glyph.bitmap.height() {
+        for x in 0..glyph.bitmap.width() {
+            if glyph.bitmap.get(x, y) == Some(true) {
+                ink.insert((bbx.x_offset + x as i32, top - y as i32));
+            }
+        }
+    }
+
+    ink
+}
+
+// every character comes back with the same ink, and the same advance unless the format sets its own
+fn assert_same_glyphs(original: &Font, read: &Font, advance_of: impl Fn(i32) -> i32) {
+    for &c in &CHARS {
+        let (a, b) = (glyph(original, c), glyph(read, c));
+        assert_eq!(ink(a), ink(b), "ink of {:?}", c);
+        assert_eq!(advance_of(advance(original, a)), advance(read, b), "advance of {:?}", c);
+    }
+}
+
+//
+
+#[test]
+fn pcf_round_trip() {
+    let font = fixture();
+
+    for &glyph_pad in &[1, 2, 4, 8] {
+        for &scan_unit in &[1, 2, 4] {
+            if scan_unit > glyph_pad {
+                continue;
+            }
+
+            for &(msb_bit_first, msb_byte_first) in &[(true, true), (true, false), (false, true), (false, false)] {
+                let options = PcfOptions {
+                    glyph_pad,
+                    scan_unit,
+                    msb_bit_first,
+                    msb_byte_first,
+                };
+                let data = pcf::write_font(Vec::new(), &font, &options).unwrap();
+                let read = pcf::parse_font(&data).unwrap();
+
+                assert_eq!(read.glyphs.len(), font.glyphs.len(), "{:?}", options);
+                assert_eq!(read.name, font.name, "{:?}", options);
+                assert_same_glyphs(&font, &read, |a| a);
+                for (a, b) in font.glyphs.iter().zip(&read.glyphs) {
+                    assert_eq!(a.name, b.name);
+                    assert_eq!(a.scalable_width, b.scalable_width);
+                }
+            }
+        }
+    }
+}
+
+// a glyph with a code outside the charset is written unencoded rather than over the glyph with that code
+#[test]
+fn pcf_non_standard_encoding() {
+    let input = SMALL.replacen("ENCODING 32\n", "ENCODING -1 65\n", 1);
+    let font = bdf_font::parse_font(&input).unwrap();
+
+    let data = pcf::write_font(Vec::new(), &font, &PcfOptions::default()).unwrap();
+    let read = pcf::parse_font(&data).unwrap();
+
+    assert_eq!(read.glyphs.len(), font.glyphs.len());
+    assert_eq!(glyph(&read, 'A').name, "A");
+    assert_eq!(ink(glyph(&read, 'A')), ink(glyph(&font, 'A')));
+
+    let space = read.glyphs.iter().find(|g| g.name == "space").unwrap();
+    assert_eq!(space.encoding, Encoding::Unencoded);
+}
+
+const SMALL_PCF: &[u8] = include_bytes!("fixtures/small_pad2_lsbit.pcf");
+
+// written from small.bdf by an encoder outside this crate with a glyph pad of 2, a scan unit of 2,
+//   the least significant bit first and big endian bytes, so rows are swapped within each unit
+#[test]
+fn pcf_reads_non_default_format() {
+    let font = fixture();
+    let read = pcf::parse_font(SMALL_PCF).unwrap();
+
+    assert_eq!(read.name, font.name);
+    assert_same_glyphs(&font, &read, |a| a);
+    assert!(bdf_font::BdfWriter::new(Vec::new()).write_font(&read).is_ok());
+}
+
+// .pcf.gz files as X servers install them
+#[test]
+fn pcf_reads_gzip() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(SMALL_PCF).unwrap();
+    let data = encoder.finish().unwrap();
+
+    let read = pcf::read_font(&data[..]).unwrap();
+    assert_same_glyphs(&fixture(), &read, |a| a);
+}
+
+// a small file that inflates to more than any font is refused rather than read into memory
+#[test]
+fn pcf_gzip_bomb() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    let zeros = vec![0; 1 << 20];
+    for _ in 0..33 {
+        encoder.write_all(&zeros).unwrap();
+    }
+    let data = encoder.finish().unwrap();
+
+    assert!(matches!(pcf::parse_font(&data), Err(Error::InvalidFormat("pcf", _))));
+}
+
+// a file cut short is an error rather than a panic
+#[test]
+fn pcf_truncated() {
+    for len in [0, 4, 8, 100, SMALL_PCF.len() - 1] {
+        assert!(pcf::parse_font(&SMALL_PCF[..len]).is_err(), "{} bytes", len);
+    }
+}
+
+#[test]
+fn psf_round_trip() {
+    let font = fixture();
+
+    // without a unicode table glyphs are stored and read back at the position of their encoding
+    for &unicode_table in &[true, false] {
+        let options = PsfOptions {
+            version: PsfVersion::Psf2,
+            unicode_table,
+        };
+        let data = psf::write_font(Vec::new(), &font, &[], &options).unwrap();
+        let (read, _) = psf::parse_font(&data).unwrap();
+
+        // every glyph fills the cell, the baseline is found again from the ink of 'H'
+        assert_eq!(read.bounding_box, font.bounding_box);
+        assert_same_glyphs(&font, &read, |a| a);
+    }
+
+    // psf1 cells are always 8 pixels wide
+    let options = PsfOptions {
+        version: PsfVersion::Psf1,
+        unicode_table: true,
+    };
+    assert!(psf::write_font(Vec::new(), &font, &[], &options).is_err());
+}
+
+#[test]
+fn hex_round_trip() {
+    let font = fixture();
+
+    // glyphs are widened to 8 pixel cells
+    let data = hex::write_font(Vec::new(), &font).unwrap();
+    let read = hex::parse_font(std::str::from_utf8(&data).unwrap()).unwrap();
+    assert_same_glyphs(&font, &read, |_| 8);
+
+    let data = hex::write_hexdraw(Vec::new(), &font).unwrap();
+    let read = hex::parse_hexdraw(std::str::from_utf8(&data).unwrap()).unwrap();
+    assert_same_glyphs(&font, &read, |_| 8);
+}
+
+// one line per glyph, the code point then every row of the cell in hex
+#[test]
+fn hex_lines() {
+    let read = hex::parse_font("0041:0000000018242442427E424242420000\n").unwrap();
+    let a = glyph(&read, 'A');
+
+    assert_eq!((a.bitmap.width(), a.bitmap.height()), (8, 16));
+    assert_eq!(a.bitmap.get(3, 4), Some(true));
+    assert_eq!(a.bitmap.get(2, 4), Some(false));
+
+    let data = hex::write_font(Vec::new(), &read).unwrap();
+    assert_eq!(std::str::from_utf8(&data).unwrap(), "0041:0000000018242442427E424242420000\n");
+}
+
+// a code point may only be defined once, in either format
+#[test]
+fn hex_duplicates() {
+    let input = "0041:0000000018242442427E424242420000\n0041:00000000000000000000000000000000\n";
+    let err = hex::parse_font(input).unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(matches!(err.error, Error::DuplicateCodepoint(0x41)));
+
+    let font = hex::parse_font("0041:0000000018242442427E424242420000\n").unwrap();
+    let data = hex::write_hexdraw(Vec::new(), &font).unwrap();
+    let text = std::str::from_utf8(&data).unwrap();
+    let err = hex::parse_hexdraw(&format!("{}\n{}", text, text)).unwrap_err();
+    assert!(matches!(err.error, Error::DuplicateCodepoint(0x41)));
+}
+
+// there is no otb reader, so check the file holds the tables a bitmap-only sfnt needs and that they lie within it
+#[test]
+fn otb_tables() {
+    let font = fixture();
+    let data = otb::write_font(Vec::new(), &font, &OtbOptions::default()).unwrap();
+
+    let u16_at = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+    let u32_at = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+
+    assert_eq!(u32_at(0), 0x0001_0000);
+
+    let mut tags = Vec::new();
+    for i in 0..usize::from(u16_at(4)) {
+        let entry = 12 + i * 16;
+        let (offset, length) = (u32_at(entry + 8) as usize, u32_at(entry + 12) as usize);
+        assert!(offset + length <= data.len());
+        tags.push(String::from_utf8(data[entry..(entry + 4)].to_vec()).unwrap());
+    }
+
+    for tag in &["EBDT", "EBLC", "OS/2", "cmap", "head", "hhea", "hmtx", "maxp", "name", "post"] {
+        assert!(tags.iter().any(|t| t == tag), "missing {}", tag);
+    }
+
+    // the glyphs and .notdef
+    let maxp = tags.iter().position(|t| t == "maxp").unwrap();
+    let maxp = u32_at(12 + maxp * 16 + 8) as usize;
+    assert_eq!(usize::from(u16_at(maxp + 4)), font.glyphs.len() + 1);
+}
+
+// glyph names only fit a post table up to 32510 glyphs besides .notdef, larger fonts get a table without them
+#[test]
+fn otb_post_names() {
+    let post_of = |count: u32| {
+        let mut text = String::from(&SMALL[..SMALL.find("\nCHARS ").unwrap() + 1]);
+        text += &format!("CHARS {}\n", count);
+        for code in 0x4e00..(0x4e00 + count) {
+            text += &format!("STARTCHAR uni{:04X}\nENCODING {}\nSWIDTH 576 0\nDWIDTH 6 0\nBBX 1 1 0 0\nBITMAP\n80\nENDCHAR\n", code, code);
+        }
+        text += "ENDFONT\n";
+        let font = bdf_font::parse_font(&text).unwrap();
+
+        let data = otb::write_font(Vec::new(), &font, &OtbOptions::default()).unwrap();
+        sfnt_table(&data, "post").to_vec()
+    };
+
+    let named = post_of(32510);
+    assert_eq!(&named[0..4], &[0, 2, 0, 0]);
+    assert_eq!(u16::from_be_bytes([named[32], named[33]]), 32511);
+    let last = 34 + 2 * 32510;
+    assert_eq!(u16::from_be_bytes([named[last], named[last + 1]]), 32767);
+
+    let unnamed = post_of(32511);
+    assert_eq!(&unnamed[0..4], &[0, 3, 0, 0]);
+    assert_eq!(unnamed.len(), 32);
+}
+
+// the contents of one table of an sfnt
+fn sfnt_table<'a>(data: &'a [u8], tag: &str) -> &'a [u8] {
+    let u32_at = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize;
+
+    let tables = usize::from(u16::from_be_bytes([data[4], data[5]]));
+    let entry = (0..tables).map(|i| 12 + i * 16)
+                           .find(|&entry| &data[entry..(entry + 4)] == tag.as_bytes())
+                           .unwrap_or_else(|| panic!("missing {}", tag));
+
+    &data[u32_at(entry + 8)..(u32_at(entry + 8) + u32_at(entry + 12))]
+}
+
+// rows read with more bytes than the width needs are stored with just enough
+#[test]
+fn otb_padded_rows() {
+    let padded = SMALL.replacen("BITMAP\n88\n50\n20\n50\n88\n", "BITMAP\n8800\n5000\n2000\n5000\n8800\n", 1);
+    let padded = bdf_font::parse_font(&padded).unwrap();
+
+    let options = OtbOptions::default();
+    let data = otb::write_font(Vec::new(), &fixture(), &options).unwrap();
+    let padded_data = otb::write_font(Vec::new(), &padded, &options).unwrap();
+
+    assert_eq!(sfnt_table(&padded_data, "EBDT"), sfnt_table(&data, "EBDT"));
+    assert_eq!(sfnt_table(&padded_data, "EBLC"), sfnt_table(&data, "EBLC"));
+}
+
+#[test]
+fn fnt_round_trip() {
+    let font = fixture();
+
+    for version in [FntVersion::V2, FntVersion::V3] {
+        let options = FntOptions {
+            version,
+        };
+        let data = fnt::write_font(Vec::new(), &font, &options).unwrap();
+        let read = fnt::parse_font(&data).unwrap();
+        assert_same_glyphs(&font, &read, |a| a);
+
+        let data = fnt::write_fon(Vec::new(), &[fixture(), fixture()], &options).unwrap();
+        let fonts = fnt::parse_fon(&data).unwrap();
+        assert_eq!(fonts.len(), 2);
+        for read in &fonts {
+            assert_same_glyphs(&font, read, |a| a);
+        }
+    }
+}
@@ -0,0 +1,662 @@
+use bdf_font::{
+    layout::{
+        self,
+        Align,
+        Bounds,
+        Direction,
+        Layout,
+        LayoutOptions,
+        LineBreak,
+    },
+    render::{
+        GrayCanvas,
+        RgbaCanvas,
+    },
+    stack::FontStack,
+    Bitmap,
+    BoundingBox,
+    Font,
+    XYPair,
+};
+
+mod common;
+
+use common::{
+    bare,
+    fixture,
+    glyph_mut,
+    SMALL,
+};
+
+//
+
+// the fixture with an A sixteen pixels wide but given rows of a single byte
+fn short_rows() -> Font {
+    let text = SMALL
+        .replace("ENCODING 65\nSWIDTH 576 0\nDWIDTH 6 0\nBBX 5 7 0 0\nBITMAP\n20\n50\n88\n88\nF8\n88\n88\n",
+                 "ENCODING 65\nSWIDTH 576 0\nDWIDTH 6 0\nBBX 16 2 0 0\nBITMAP\nFF\nFF\n");
+    bdf_font::parse_font(&text).unwrap()
+}
+
+// the fixture set for vertical writing, with A moving further down and sitting further left
+fn vertical() -> Font {
+    let mut font = fixture();
+    font.device_width_alt = Some(XYPair::new(0, -12));
+    font.vector = Some(XYPair::new(3, 9));
+
+    let a = glyph_mut(&mut font, 'A');
+    a.device_width_alt = Some(XYPair::new(0, -14));
+    a.vector = Some(XYPair::new(2, 9));
+    font
+}
+
+// a taller font for stacking under the fixture, with a Z, its own A and Z as DEFAULT_CHAR
+const SYMBOLS: &str = "\
+STARTFONT 2.1
+FONT -test-symbols-medium-r-normal--12-120-75-75-c-50-iso10646-1
+SIZE 12 75 75
+FONTBOUNDINGBOX 5 13 0 -3
+STARTPROPERTIES 5
+CHARSET_REGISTRY \"ISO10646\"
+CHARSET_ENCODING \"1\"
+FONT_ASCENT 10
+FONT_DESCENT 3
+DEFAULT_CHAR 90
+ENDPROPERTIES
+CHARS 2
+STARTCHAR Z
+ENCODING 90
+SWIDTH 416 0
+DWIDTH 5 0
+BBX 4 4 0 0
+BITMAP
+F0
+20
+40
+F0
+ENDCHAR
+STARTCHAR A
+ENCODING 65
+SWIDTH 416 0
+DWIDTH 5 0
+BBX 4 2 0 0
+BITMAP
+F0
+F0
+ENDCHAR
+ENDFONT
+";
+
+fn symbols() -> Font {
+    bdf_font::parse_font(SYMBOLS).unwrap()
+}
+
+// an acute placed off to the left and above where it belongs, and a dot below
+const MARKS: &str = "\
+STARTCHAR acutecomb
+ENCODING 769
+SWIDTH 0 0
+DWIDTH 0 0
+BBX 2 2 -4 6
+BITMAP
+40
+80
+ENDCHAR
+STARTCHAR dotbelowcomb
+ENCODING 803
+SWIDTH 0 0
+DWIDTH 0 0
+BBX 1 1 -3 -2
+BITMAP
+80
+ENDCHAR
+";
+
+// the fixture with the combining marks
+fn with_marks() -> Font {
+    let text = SMALL
+        .replace("CHARS 8", "CHARS 10")
+        .replace("ENDFONT", &format!("{}ENDFONT", MARKS));
+    bdf_font::parse_font(&text).unwrap()
+}
+
+// the bitmap as rows of `#` and `.`
+fn shown(bitmap: &Bitmap) -> Vec<String> {
+    (0..bitmap.height()).map(|y| {
+        (0..bitmap.width()).map(|x| if bitmap.get(x, y) == Some(true) { '#' } else { '.' }).collect()
+    }).collect()
+}
+
+// each line's text and the pen position of each glyph on it
+fn lines(text: &str, layout: &Layout<'_>) -> Vec<(String, Vec<i32>)> {
+    layout.lines.iter().map(|line| {
+        (text[line.range.clone()].to_string(), line.glyphs.iter().map(|g| g.x).collect())
+    }).collect()
+}
+
+fn wrapped(max_width: i32) -> LayoutOptions {
+    LayoutOptions {
+        max_width: Some(max_width),
+        ..LayoutOptions::default()
+    }
+}
+
+//
+
+// glyphs sit on the baseline with their own offsets, the pen moves on by each advance
+#[test]
+fn render_text() {
+    let font = fixture();
+    let mut canvas = Bitmap::new(12, 10);
+    assert_eq!(font.render("Ag", &mut canvas).unwrap(), (12, 8));
+
+    assert_eq!(shown(&canvas), [
+        "............",
+        "..#.........",
+        ".#.#........",
+        "#...#..####.",
+        "#...#.#...#.",
+        "#####.#...#.",
+        "#...#..####.",
+        "#...#.....#.",
+        "......#...#.",
+        ".......###..",
+    ]);
+}
+
+// rows shorter than the glyph are blank past their end
+#[test]
+fn render_short_rows() {
+    let font = short_rows();
+    let mut canvas = Bitmap::new(16, 10);
+    assert_eq!(font.render("A", &mut canvas).unwrap(), (6, 8));
+
+    let rows = shown(&canvas);
+    assert_eq!(rows[6], "########........");
+    assert_eq!(rows[7], "########........");
+    assert_eq!(rows.concat().matches('#').count(), 16);
+}
+
+// the pen can start anywhere, ink off the canvas is dropped
+#[test]
+fn render_at_clips() {
+    let font = fixture();
+    let mut canvas = Bitmap::new(4, 4);
+    assert_eq!(font.render_at("A", -2, 3, &mut canvas).unwrap(), (4, 3));
+
+    assert_eq!(shown(&canvas), [
+        "###.",
+        "..#.",
+        "..#.",
+        "....",
+    ]);
+}
+
+// characters the font lacks are drawn as DEFAULT_CHAR
+#[test]
+fn render_default_char() {
+    let font = fixture();
+
+    let mut missing = Bitmap::new(6, 10);
+    font.render("Z", &mut missing).unwrap();
+    let mut question = Bitmap::new(6, 10);
+    font.render("?", &mut question).unwrap();
+
+    assert_eq!(shown(&missing), shown(&question));
+    assert!(shown(&missing).concat().contains('#'));
+}
+
+// a glyph without DWIDTH moves the pen by the font's
+#[test]
+fn render_font_advance() {
+    let mut font = fixture();
+    font.device_width = Some(XYPair::new(7, 0));
+    glyph_mut(&mut font, 'A').device_width = None;
+
+    let mut canvas = Bitmap::new(20, 10);
+    assert_eq!(font.render("AH", &mut canvas).unwrap(), (13, 8));
+    assert_eq!(canvas.get(7, 2), Some(true));
+    assert_eq!(canvas.get(6, 2), Some(false));
+}
+
+#[test]
+fn render_gray_and_rgba() {
+    let font = fixture();
+
+    let mut gray = GrayCanvas::new(6, 10);
+    gray.ink = 128;
+    font.render("i", &mut gray).unwrap();
+    assert_eq!(gray.pixels.iter().filter(|&&p| p == 128).count(), 9);
+    assert_eq!(gray.pixels.iter().filter(|&&p| p == 0).count(), 60 - 9);
+    assert_eq!(gray.pixels[6 + 2], 128);
+
+    let mut rgba = RgbaCanvas::new(6, 10);
+    rgba.color = [255, 0, 0, 255];
+    font.render("i", &mut rgba).unwrap();
+    let inked: Vec<&[u8]> = rgba.pixels.chunks(4).filter(|p| p[3] != 0).collect();
+    assert_eq!(inked.len(), 9);
+    assert!(inked.iter().all(|p| p == &[255, 0, 0, 255]));
+    assert_eq!(&rgba.pixels[(6 + 2) * 4..(6 + 3) * 4], &[255, 0, 0, 255]);
+}
+
+// a font without a charset is looked up by code
+#[test]
+fn render_without_charset() {
+    let font = fixture();
+    let mut expected = Bitmap::new(18, 10);
+    font.render("AgZ", &mut expected).unwrap();
+
+    let font = bare();
+    let mut canvas = Bitmap::new(18, 10);
+    assert_eq!(font.render("AgZ", &mut canvas).unwrap(), (18, 8));
+    assert_eq!(shown(&canvas), shown(&expected));
+}
+
+//
+
+#[test]
+fn measure_text() {
+    let font = fixture();
+    assert_eq!(layout::line_height(&font), 10);
+
+    let measure = layout::measure(&font, "Ag").unwrap();
+    assert_eq!(measure.advance, 12);
+    assert_eq!(measure.ink, Some(Bounds { left: 0, top: -7, right: 11, bottom: 2 }));
+    assert_eq!((measure.ascent, measure.descent, measure.line_height), (8, 2, 10));
+
+    assert_eq!(layout::measure(&font, " ").unwrap().ink, None);
+    assert_eq!(layout::measure(&font, "\tA").unwrap().advance, 54);
+}
+
+// glyphs with rows shorter than their width measure and lay out by their width
+#[test]
+fn measure_short_rows() {
+    let font = short_rows();
+
+    let measure = layout::measure(&font, "A").unwrap();
+    assert_eq!(measure.advance, 6);
+    assert_eq!(measure.ink, Some(Bounds { left: 0, top: -2, right: 8, bottom: 0 }));
+
+    let layout = layout::layout(&font, "AH", &LayoutOptions::default()).unwrap();
+    assert_eq!(lines("AH", &layout)[0].1, [0, 6]);
+    assert_eq!(layout.ink(), Some(Bounds { left: 0, top: 1, right: 11, bottom: 8 }));
+}
+
+// lines break after spaces, which hang past the width, and words too long for a line are split
+#[test]
+fn layout_wraps() {
+    let font = fixture();
+
+    let text = "AH gi xA";
+    let layout = layout::layout(&font, text, &wrapped(30)).unwrap();
+    assert_eq!(lines(text, &layout), [
+        ("AH gi ".to_string(), vec![0, 6, 18, 24]),
+        ("xA".to_string(), vec![0, 6]),
+    ]);
+    assert_eq!(layout.lines.iter().map(|l| (l.baseline, l.width)).collect::<Vec<_>>(), [(8, 30), (18, 12)]);
+    assert_eq!((layout.width, layout.height), (30, 20));
+
+    let text = "AAAAAAA";
+    let layout = layout::layout(&font, text, &wrapped(24)).unwrap();
+    assert_eq!(lines(text, &layout).iter().map(|l| l.0.as_str()).collect::<Vec<_>>(), ["AAAA", "AAA"]);
+}
+
+// the unicode rules also break after hyphens, and mandatory breaks always end a line
+#[test]
+fn layout_breaks() {
+    let font = fixture();
+
+    let text = "AH-Hi";
+    let unicode = layout::layout(&font, text, &wrapped(24)).unwrap();
+    assert_eq!(lines(text, &unicode).iter().map(|l| l.0.as_str()).collect::<Vec<_>>(), ["AH-", "Hi"]);
+
+    let options = LayoutOptions {
+        line_break: LineBreak::Whitespace,
+        ..wrapped(24)
+    };
+    let whitespace = layout::layout(&font, text, &options).unwrap();
+    assert_eq!(lines(text, &whitespace).iter().map(|l| l.0.as_str()).collect::<Vec<_>>(), ["AH-H", "i"]);
+
+    let text = "A\r\nH\nx";
+    let layout = layout::layout(&font, text, &LayoutOptions::default()).unwrap();
+    assert_eq!(layout.lines.iter().map(|l| l.range.clone()).collect::<Vec<_>>(), [0..1, 3..4, 5..6]);
+    assert_eq!(layout.height, 30);
+}
+
+#[test]
+fn layout_align() {
+    let font = fixture();
+    let text = "A HA";
+
+    let shifts: Vec<Vec<i32>> = [Align::Left, Align::Center, Align::Right, Align::Justify].iter().map(|&align| {
+        let options = LayoutOptions {
+            align,
+            ..wrapped(60)
+        };
+        lines(text, &layout::layout(&font, text, &options).unwrap())[0].1.clone()
+    }).collect();
+
+    assert_eq!(shifts, [
+        vec![0, 12, 18],
+        vec![18, 30, 36],
+        vec![36, 48, 54],
+        // the last line of a paragraph is not justified
+        vec![0, 12, 18],
+    ]);
+}
+
+// justified lines share out the slack between their interior spaces
+#[test]
+fn layout_justify() {
+    let font = fixture();
+    let text = "A H A x";
+    let options = LayoutOptions {
+        align: Align::Justify,
+        ..wrapped(37)
+    };
+
+    let layout = layout::layout(&font, text, &options).unwrap();
+    assert_eq!(lines(text, &layout), [
+        ("A H A ".to_string(), vec![0, 16, 31]),
+        ("x".to_string(), vec![0]),
+    ]);
+    assert_eq!(layout.lines[0].width, 37);
+}
+
+// tabs go to the next explicit stop, then every tab width after the last one
+#[test]
+fn layout_tabs() {
+    let font = fixture();
+    let text = "\tA\tH\tx";
+    let options = LayoutOptions {
+        tab_stops: vec![10],
+        tab_width: Some(20),
+        ..LayoutOptions::default()
+    };
+
+    let layout = layout::layout(&font, text, &options).unwrap();
+    assert_eq!(lines(text, &layout)[0].1, [10, 30, 50]);
+
+    let layout = layout::layout(&font, text, &LayoutOptions::default()).unwrap();
+    assert_eq!(lines(text, &layout)[0].1, [48, 96, 144]);
+}
+
+// a layout draws what rendering the lines one by one would
+#[test]
+fn layout_render() {
+    let font = fixture();
+    let layout = layout::layout(&font, "Ag iH", &wrapped(12)).unwrap();
+    assert_eq!(layout.ink(), Some(Bounds { left: 0, top: 1, right: 11, bottom: 18 }));
+
+    let mut drawn = Bitmap::new(12, 20);
+    layout.render(0, 0, &mut drawn);
+
+    let mut expected = Bitmap::new(12, 20);
+    font.render_at("Ag", 0, 8, &mut expected).unwrap();
+    font.render_at("iH", 0, 18, &mut expected).unwrap();
+    assert_eq!(shown(&drawn), shown(&expected));
+}
+
+// a font without a charset is measured and laid out by code
+#[test]
+fn layout_without_charset() {
+    let (font, bare) = (fixture(), bare());
+
+    assert_eq!(layout::measure(&bare, "AgZ").unwrap(), layout::measure(&font, "AgZ").unwrap());
+
+    let text = "AH gi xA";
+    let expected = layout::layout(&font, text, &wrapped(30)).unwrap();
+    let layout = layout::layout(&bare, text, &wrapped(30)).unwrap();
+    assert_eq!(lines(text, &layout), lines(text, &expected));
+    assert_eq!(layout.ink(), expected.ink());
+}
+
+//
+
+// DWIDTH1 and VVECTOR are signed, with y growing up
+#[test]
+fn parse_vertical_metrics() {
+    let text = SMALL
+        .replace("SIZE 10 75 75\n", "SIZE 10 75 75\nMETRICSSET 2\nSWIDTH1 0 -1000\nDWIDTH1 0 -12\nVVECTOR 3 9\n");
+    let font = bdf_font::parse_font(&text).unwrap();
+    assert_eq!(font.device_width_alt, Some(XYPair::new(0, -12)));
+    assert_eq!(font.vector, Some(XYPair::new(3, 9)));
+}
+
+// each glyph hangs from origin 1 by its VVECTOR and the pen moves down by its DWIDTH1
+#[test]
+fn render_vertical() {
+    let font = vertical();
+    let mut drawn = Bitmap::new(20, 30);
+    assert_eq!(font.render_vertical_at("AH", 10, 0, &mut drawn).unwrap(), (10, 26));
+
+    let mut expected = Bitmap::new(20, 30);
+    font.render_at("A", 8, 9, &mut expected).unwrap();
+    font.render_at("H", 7, 23, &mut expected).unwrap();
+    assert_eq!(shown(&drawn), shown(&expected));
+}
+
+// without DWIDTH1 and VVECTOR glyphs are centred on the column and a line apart
+#[test]
+fn render_vertical_defaults() {
+    let font = fixture();
+    let mut drawn = Bitmap::new(6, 20);
+    assert_eq!(font.render_vertical("Ai", &mut drawn).unwrap(), (3, 20));
+
+    let mut expected = Bitmap::new(6, 20);
+    font.render_at("A", 0, 8, &mut expected).unwrap();
+    font.render_at("i", 0, 18, &mut expected).unwrap();
+    assert_eq!(shown(&drawn), shown(&expected));
+}
+
+// columns fill top to bottom and stack from the right
+#[test]
+fn layout_vertical() {
+    let font = vertical();
+    let options = LayoutOptions {
+        direction: Direction::Vertical,
+        ..wrapped(30)
+    };
+
+    let layout = layout::layout(&font, "AHx", &options).unwrap();
+    let placed: Vec<Vec<(char, i32, i32)>> = layout.lines.iter().map(|line| {
+        line.glyphs.iter().map(|g| (g.c, g.x, g.y)).collect()
+    }).collect();
+    assert_eq!(placed, [
+        vec![('A', 7, 9), ('H', 6, 23)],
+        vec![('x', 0, 9)],
+    ]);
+    assert_eq!(layout.lines.iter().map(|l| (l.baseline, l.width)).collect::<Vec<_>>(), [(9, 26), (3, 12)]);
+    assert_eq!((layout.width, layout.height), (12, 30));
+}
+
+//
+
+// each character comes from the first font that has it
+#[test]
+fn stack_glyph_for() {
+    let stack = FontStack::new(vec![fixture(), symbols()]);
+    assert_eq!(stack.fonts().len(), 2);
+
+    let from = |c: char| stack.glyph_for(c).map(|(font, glyph)| (font.name.clone(), glyph.name.clone()));
+    let small = String::from("-test-small-medium-r-normal--10-100-75-75-c-60-iso10646-1");
+    let symbols = String::from("-test-symbols-medium-r-normal--12-120-75-75-c-50-iso10646-1");
+    assert_eq!(from('A'), Some((small.clone(), String::from("A"))));
+    assert_eq!(from('é'), Some((small, String::from("eacute"))));
+    assert_eq!(from('Z'), Some((symbols, String::from("Z"))));
+    assert_eq!(from('€'), None);
+}
+
+// glyphs from every font share a baseline low enough for the tallest of them
+#[test]
+fn stack_render() {
+    let stack = FontStack::new(vec![fixture(), symbols()]);
+    assert_eq!(stack.line_height().unwrap(), 13);
+
+    let mut drawn = Bitmap::new(20, 13);
+    assert_eq!(stack.render("AZ€", &mut drawn).unwrap(), (17, 10));
+
+    // the primary font's DEFAULT_CHAR stands in for what no font has
+    let mut expected = Bitmap::new(20, 13);
+    fixture().render_at("A", 0, 10, &mut expected).unwrap();
+    symbols().render_at("Z", 6, 10, &mut expected).unwrap();
+    fixture().render_at("?", 11, 10, &mut expected).unwrap();
+    assert_eq!(shown(&drawn), shown(&expected));
+
+    let measure = stack.measure("AZ").unwrap();
+    assert_eq!((measure.advance, measure.ascent, measure.descent), (11, 10, 3));
+    assert_eq!(measure.ink, Some(Bounds { left: 0, top: -7, right: 10, bottom: 0 }));
+}
+
+// fonts pushed later only fill the gaps
+#[test]
+fn stack_push() {
+    let mut stack = FontStack::new(vec![symbols()]);
+    stack.push(fixture());
+
+    assert_eq!(stack.glyph_for('A').unwrap().1.bitmap.height(), 2);
+    assert_eq!(stack.glyph_for('g').unwrap().1.name, "g");
+
+    let layout = stack.layout("AgZ", &LayoutOptions::default()).unwrap();
+    assert_eq!(lines("AgZ", &layout)[0].1, [0, 5, 11]);
+    assert_eq!(layout.lines[0].baseline, 10);
+    assert_eq!(layout.height, 13);
+
+    assert!(FontStack::new(Vec::new()).measure("A").is_err());
+}
+
+// a font without a charset joins the stack by code
+#[test]
+fn stack_without_charset() {
+    let mut stack = FontStack::new(vec![bare()]);
+    stack.push(symbols());
+    assert_eq!(stack.glyph_for('A').unwrap().1.name, "A");
+    assert_eq!(stack.glyph_for('Z').unwrap().1.name, "Z");
+
+    let mut drawn = Bitmap::new(12, 13);
+    assert_eq!(stack.render("AZ", &mut drawn).unwrap(), (11, 10));
+    assert_eq!(stack.measure("AZ").unwrap().advance, 11);
+}
+
+//
+
+// marks are centred over the ink of their base with a row between, and move the pen on by nothing
+#[test]
+fn render_marks() {
+    let font = with_marks();
+    let mut canvas = Bitmap::new(12, 14);
+    assert_eq!(font.render_at("A\u{301}g\u{323}", 0, 10, &mut canvas).unwrap(), (12, 10));
+
+    assert_eq!(shown(&canvas), [
+        "..#.........",
+        ".#..........",
+        "............",
+        "..#.........",
+        ".#.#........",
+        "#...#..####.",
+        "#...#.#...#.",
+        "#####.#...#.",
+        "#...#..####.",
+        "#...#.....#.",
+        "......#...#.",
+        ".......###..",
+        "............",
+        "........#...",
+    ]);
+}
+
+// later marks stack on the earlier ones, selectors and marks the font lacks draw nothing
+#[test]
+fn render_stacked_marks() {
+    let font = with_marks();
+
+    let mut stacked = Bitmap::new(6, 14);
+    font.render_at("i\u{301}\u{301}", 0, 13, &mut stacked).unwrap();
+    assert_eq!(&shown(&stacked)[..7], [
+        "..#...",
+        ".#....",
+        "......",
+        "..#...",
+        ".#....",
+        "......",
+        "..#...",
+    ]);
+
+    let mut plain = Bitmap::new(6, 10);
+    font.render("A", &mut plain).unwrap();
+    let mut ignored = Bitmap::new(6, 10);
+    assert_eq!(font.render("A\u{fe0f}\u{308}", &mut ignored).unwrap(), (6, 8));
+    assert_eq!(shown(&ignored), shown(&plain));
+}
+
+// a cluster composed into one glyph looks as it renders
+#[test]
+fn compose_cluster() {
+    let font = with_marks();
+    let glyph = font.compose("A\u{301}").unwrap().unwrap();
+
+    assert_eq!(glyph.name, "uni0041_uni0301");
+    assert_eq!(glyph.device_width, Some(XYPair::new(6, 0)));
+    assert_eq!(glyph.bounding_box, BoundingBox::new(5, 10, 0, 0));
+    assert_eq!(shown(&glyph.bitmap), [
+        "..#..",
+        ".#...",
+        ".....",
+        "..#..",
+        ".#.#.",
+        "#...#",
+        "#...#",
+        "#####",
+        "#...#",
+        "#...#",
+    ]);
+
+    assert!(font.compose("\u{301}").unwrap().is_some());
+    assert!(fixture().compose("").unwrap().is_none());
+}
+
+// measuring and laying out place marks where rendering does, and never break a line before one
+#[test]
+fn layout_marks() {
+    let font = with_marks();
+
+    let measure = layout::measure(&font, "A\u{301}g\u{323}").unwrap();
+    assert_eq!(measure.advance, 12);
+    assert_eq!(measure.ink, Some(Bounds { left: 0, top: -10, right: 11, bottom: 4 }));
+
+    let text = "AH\u{301}A";
+    let layout = layout::layout(&font, text, &wrapped(12)).unwrap();
+    let placed: Vec<Vec<(char, i32, i32)>> = layout.lines.iter().map(|line| {
+        line.glyphs.iter().map(|g| (g.c, g.x, g.y)).collect()
+    }).collect();
+    assert_eq!(placed, [
+        vec![('A', 0, 8), ('H', 6, 8), ('\u{301}', 11, 6)],
+        vec![('A', 0, 18)],
+    ]);
+}
+
+// a mark from a later font sits on a base from the first
+#[test]
+fn stack_marks() {
+    let marks = format!("\
+STARTFONT 2.1
+FONT -test-marks-medium-r-normal--10-100-75-75-c-0-iso10646-1
+SIZE 10 75 75
+FONTBOUNDINGBOX 2 10 -4 -2
+STARTPROPERTIES 4
+CHARSET_REGISTRY \"ISO10646\"
+CHARSET_ENCODING \"1\"
+FONT_ASCENT 8
+FONT_DESCENT 2
+ENDPROPERTIES
+CHARS 2
+{}ENDFONT
+", MARKS);
+    let stack = FontStack::new(vec![fixture(), bdf_font::parse_font(&marks).unwrap()]);
+
+    let mut drawn = Bitmap::new(12, 14);
+    stack.render_at("A\u{301}g\u{323}", 0, 10, &mut drawn).unwrap();
+    let mut expected = Bitmap::new(12, 14);
+    with_marks().render_at("A\u{301}g\u{323}", 0, 10, &mut expected).unwrap();
+    assert_eq!(shown(&drawn), shown(&expected));
+}
@@ -0,0 +1,165 @@
+use bdf_font::{
+    xlfd,
+    BdfBlock,
+    Error,
+    Font,
+    Xlfd,
+};
+
+mod common;
+
+use common::{
+    fixture,
+    SMALL,
+};
+
+//
+
+const FIXED: &str = "-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646-1";
+
+fn parse(name: &str) -> Xlfd {
+    name.parse().unwrap()
+}
+
+fn invalid(name: &str) -> &'static str {
+    match parse(name).validate() {
+        Err(Error::XlfdValidation(msg)) => msg,
+        other => panic!("{} gave {:?}", name, other),
+    }
+}
+
+// the fixture under another FONT name
+fn named(name: &str) -> Font {
+    let text = SMALL.replace("FONT -test-small-medium-r-normal--10-100-75-75-c-60-iso10646-1", &format!("FONT {}", name));
+    bdf_font::parse_font(&text).unwrap()
+}
+
+//
+
+#[test]
+fn parse_name() {
+    let xlfd = parse(FIXED);
+    assert_eq!(xlfd.foundry.as_deref(), Some("misc"));
+    assert_eq!(xlfd.family_name.as_deref(), Some("fixed"));
+    assert_eq!(xlfd.weight_name.as_deref(), Some("medium"));
+    assert_eq!(xlfd.slant.as_deref(), Some("r"));
+    assert_eq!(xlfd.setwidth_name.as_deref(), Some("normal"));
+    assert_eq!(xlfd.add_style_name, None);
+    assert_eq!((xlfd.pixel_size, xlfd.point_size), (Some(13), Some(120)));
+    assert_eq!((xlfd.resolution_x, xlfd.resolution_y), (Some(75), Some(75)));
+    assert_eq!(xlfd.spacing.as_deref(), Some("c"));
+    assert_eq!(xlfd.average_width, Some(70));
+    assert_eq!(xlfd.charset_registry.as_deref(), Some("iso10646"));
+    assert_eq!(xlfd.charset_encoding.as_deref(), Some("1"));
+
+    assert!(xlfd.validate().is_ok());
+    assert_eq!(xlfd.to_string(), FIXED);
+}
+
+#[test]
+fn parse_bad_names() {
+    let error = |name: &str| match name.parse::<Xlfd>() {
+        Err(Error::XlfdValidation(msg)) => msg,
+        other => panic!("{} gave {:?}", name, other),
+    };
+
+    assert_eq!(error("misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646-1"), "name must start with '-'");
+    assert_eq!(error("-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646"), "name must have 14 fields");
+    assert_eq!(error("-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646-1-x"), "name must have 14 fields");
+    assert_eq!(error("fixed"), "name must start with '-'");
+
+    let bad_size = "-misc-fixed-medium-r-normal--big-120-75-75-c-70-iso10646-1".parse::<Xlfd>();
+    assert!(matches!(bad_size, Err(Error::InvalidValue(id, _)) if id == "PIXEL_SIZE"));
+}
+
+#[test]
+fn validate_fields() {
+    assert_eq!(invalid("-misc-fixed-medium-x-normal--13-120-75-75-c-70-iso10646-1"), "SLANT must be one of R, I, O, RI, RO or OT");
+    assert_eq!(invalid("-misc-fixed-medium-r-normal--13-120-75-75-q-70-iso10646-1"), "SPACING must be one of P, M or C");
+    assert!(parse("-misc-fixed-medium-RI-normal--13-120-75-75-M-70-iso10646-1").validate().is_ok());
+
+    // fields set directly can hold what a name cannot
+    let mut xlfd = parse(FIXED);
+    xlfd.family_name = Some(String::from("fixed-wide"));
+    assert_eq!(xlfd.validate().map_err(|e| e.to_string()), Err(String::from("invalid xlfd: field contains '-'")));
+}
+
+// names round the point size and fonts are often a size off, so a tenth or a pixel of slack is allowed
+#[test]
+fn validate_sizes() {
+    let mismatch = "PIXEL_SIZE does not agree with POINT_SIZE and RESOLUTION_Y";
+
+    // 9x18 is 12 points at 100 dpi, which is 16.6 pixels
+    assert!(parse("-misc-fixed-medium-r-normal--18-120-100-100-c-90-iso10646-1").validate().is_ok());
+    assert!(parse("-misc-fixed-medium-r-normal--6-60-75-75-c-40-iso10646-1").validate().is_ok());
+    assert_eq!(invalid("-misc-fixed-medium-r-normal--20-120-100-100-c-90-iso10646-1"), mismatch);
+    assert_eq!(invalid("-misc-fixed-medium-r-normal--8-60-75-75-c-40-iso10646-1"), mismatch);
+
+    // scalable and unset sizes are not compared
+    assert!(parse("-misc-fixed-medium-r-normal--0-0-75-75-c-0-iso10646-1").validate().is_ok());
+    assert!(parse("-misc-fixed-medium-r-normal--13---75-c-70-iso10646-1").validate().is_ok());
+}
+
+#[test]
+fn check_name() {
+    assert!(fixture().check_name().is_ok());
+    // case and empty fields are not compared
+    assert!(named("-TEST-SMALL-MEDIUM-R-NORMAL--10-100-75-75-C--ISO10646-1").check_name().is_ok());
+
+    let family = named("-test-large-medium-r-normal--10-100-75-75-c-60-iso10646-1").check_name();
+    assert!(matches!(family, Err(Error::XlfdMismatch("FAMILY_NAME"))));
+    let size = named("-test-small-medium-r-normal--12-100-75-75-c-60-iso10646-1").check_name();
+    assert!(matches!(size, Err(Error::XlfdMismatch("PIXEL_SIZE"))));
+
+    assert!(matches!(named("small").check_name(), Err(Error::XlfdValidation(_))));
+}
+
+//
+
+#[test]
+fn matches_wildcards() {
+    let terminus = parse("-xos4-terminus-bold-r-normal--16-160-72-72-c-80-iso10646-1");
+    assert!(terminus.matches("-*-terminus-bold-*-*-*-16-*"));
+    assert!(!terminus.matches("-*-terminus-medium-*-*-*-16-*"));
+    assert!(!terminus.matches("-*-terminus-bold-*-*-*-14-*"));
+
+    // '?' is any one character, '*' any run including none and across fields
+    assert!(terminus.matches("-xos?-terminus-bold-r-normal--16-160-72-72-c-80-iso10646-?"));
+    assert!(!terminus.matches("-xos?-terminus-bold-r-normal--1?-160-72-72-c-80-iso10646-1?"));
+    assert!(!terminus.matches("-xos??-terminus-*"));
+    assert!(terminus.matches("*"));
+    assert!(terminus.matches("-xos4-*-iso10646-1"));
+    assert!(!terminus.matches("-xos4-*-iso8859-1"));
+
+    assert!(terminus.matches("-*-TERMINUS-Bold-*-*-*-16-*"));
+}
+
+// exact matches first, then fonts only a size off, nearest first with scalable fonts as good as exact
+#[test]
+fn rank_by_size() {
+    let fonts: Vec<Font> = [
+        "-xos4-terminus-bold-r-normal--14-140-72-72-c-80-iso10646-1",
+        "-xos4-terminus-medium-r-normal--16-160-72-72-c-80-iso10646-1",
+        "-xos4-terminus-bold-r-normal--20-200-72-72-c-100-iso10646-1",
+        "-xos4-terminus-bold-r-normal--16-160-72-72-c-80-iso10646-1",
+        "-misc-fixed-bold-r-normal--16-160-72-72-c-80-iso10646-1",
+        "-xos4-terminus-bold-r-normal--0-0-72-72-c-0-iso10646-1",
+    ].iter().map(|name| {
+        let mut font = fixture();
+        font.xlfd = parse(name);
+        font
+    }).collect();
+
+    let sizes = |pattern: &str| -> Vec<Option<i32>> {
+        xlfd::rank_fonts(&fonts, pattern).iter().map(|font| font.xlfd.pixel_size).collect()
+    };
+
+    assert_eq!(sizes("-*-terminus-bold-r-*-*-16-*-*-*-*-*-iso10646-1"), [Some(16), Some(0), Some(14), Some(20)]);
+    assert_eq!(sizes("-*-terminus-bold-r-*-*-19-*-*-*-*-*-iso10646-1"), [Some(0), Some(20), Some(16), Some(14)]);
+    // point sizes are turned into pixels at the resolution asked for
+    assert_eq!(sizes("-*-terminus-bold-r-*-*-*-100-100-100-*-*-*-*"), [Some(0), Some(14), Some(16), Some(20)]);
+
+    // other patterns only pick out the matching fonts, in the order given
+    assert_eq!(sizes("*terminus-bold*"), [Some(14), Some(20), Some(16), Some(0)]);
+    assert!(sizes("-*-courier-*").is_empty());
+}